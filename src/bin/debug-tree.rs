@@ -0,0 +1,164 @@
+use debug_tree::{Style, Tree, TreeBuilder, TreeConfig};
+use std::io::Read;
+use std::process::exit;
+
+const USAGE: &str = "\
+Renders a tree read from a file, or from stdin.
+
+Usage: debug-tree [OPTIONS] [FILE]
+
+Options:
+  --format <FORMAT>  json, jsonl or indent [default: detected from the input]
+  --spaces <N>       Spaces per level of indented text [default: 2]
+  --style <STYLE>    light, rounded, double, heavy or dashed [default: light]
+  --indent <N>       Indentation of each level of the output [default: 2]
+  --depth <N>        Only render nodes up to a depth of N, where top-level nodes have a depth of 0
+  --color            Dim the lines of the tree
+  -h, --help         Print this help
+
+Formats:
+  json    A node is {\"text\": \"..\", \"children\": [..]} or a string.
+          The input is the root node, or an array of top-level nodes.
+  jsonl   One operation per line: {\"op\": \"leaf\", \"text\": \"..\"}, {\"op\": \"enter\"},
+          {\"op\": \"exit\"} or {\"op\": \"clear\"}.
+  indent  One node per line, indented by a number of spaces or a tab for each level.";
+
+struct Options {
+    format: Option<String>,
+    spaces: usize,
+    style: Style,
+    indent: usize,
+    depth: Option<usize>,
+    color: bool,
+    file: Option<String>,
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("debug-tree: {}", message);
+    exit(2)
+}
+
+fn number(name: &str, value: Option<String>) -> usize {
+    value
+        .and_then(|x| x.parse().ok())
+        .unwrap_or_else(|| fail(&format!("{} expects a number", name)))
+}
+
+fn parse_args() -> Options {
+    let mut options = Options {
+        format: None,
+        spaces: 2,
+        style: Style::Light,
+        indent: 2,
+        depth: None,
+        color: false,
+        file: None,
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => match args.next() {
+                Some(x) => options.format = Some(x),
+                None => fail("--format expects json, jsonl or indent"),
+            },
+            "--spaces" => options.spaces = number("--spaces", args.next()),
+            "--indent" => options.indent = number("--indent", args.next()),
+            "--depth" => options.depth = Some(number("--depth", args.next())),
+            "--color" => options.color = true,
+            "--style" => {
+                options.style = match args.next().as_deref() {
+                    Some("light") => Style::Light,
+                    Some("rounded") => Style::Rounded,
+                    Some("double") => Style::Double,
+                    Some("heavy") => Style::Heavy,
+                    Some("dashed") => Style::Dashed,
+                    _ => fail("--style expects light, rounded, double, heavy or dashed"),
+                }
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                exit(0)
+            }
+            x if x.starts_with('-') => fail(&format!("unknown option {}\n\n{}", x, USAGE)),
+            _ => options.file = Some(arg),
+        }
+    }
+    options
+}
+
+fn read_input(file: &Option<String>) -> String {
+    let mut input = String::new();
+    let result = match file {
+        Some(path) => std::fs::File::open(path).and_then(|mut x| x.read_to_string(&mut input)),
+        None => std::io::stdin().read_to_string(&mut input),
+    };
+    if let Err(err) = result {
+        fail(&err.to_string());
+    }
+    input
+}
+
+/// Detects the format of `input`: a single JSON document, JSON Lines, or indented text.
+fn detect_format(input: &str) -> &'static str {
+    match input.trim_start().chars().next() {
+        Some('[') => "json",
+        Some('{') if Tree::from_json(input).is_ok() => "json",
+        Some('{') => "jsonl",
+        _ => "indent",
+    }
+}
+
+fn limit_depth(tree: &mut Tree, depth: usize) {
+    for x in tree.children.iter_mut() {
+        match depth {
+            0 => x.children.clear(),
+            _ => limit_depth(x, depth - 1),
+        }
+    }
+}
+
+/// Dims the leading tree symbols of each line.
+fn dim_symbols(s: &str) -> String {
+    s.split('\n')
+        .map(|line| {
+            let text = line
+                .trim_start_matches(|c: char| c == ' ' || ('\u{2500}'..='\u{257f}').contains(&c));
+            let symbols = &line[..line.len() - text.len()];
+            match symbols.trim().is_empty() {
+                true => line.to_string(),
+                false => format!("\x1b[2m{}\x1b[0m{}", symbols, text),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn main() {
+    let options = parse_args();
+    let input = read_input(&options.file);
+    let format = options
+        .format
+        .clone()
+        .unwrap_or_else(|| detect_format(&input).to_string());
+    let tree = match format.as_str() {
+        "json" => Tree::from_json(&input),
+        "jsonl" => TreeBuilder::from_jsonl(&input).map(|x| x.snapshot()),
+        "indent" => Tree::from_indented_str(&input, options.spaces),
+        _ => fail("--format expects json, jsonl or indent"),
+    };
+    let mut tree = tree.unwrap_or_else(|err| fail(&err));
+    if let Some(depth) = options.depth {
+        limit_depth(&mut tree, depth);
+    }
+    let builder = TreeBuilder::from(tree);
+    builder.set_config_override(
+        TreeConfig::new()
+            .style(options.style)
+            .indent(options.indent),
+    );
+    let output = builder.peek_string();
+    match options.color {
+        true => println!("{}", dim_symbols(&output)),
+        false => println!("{}", output),
+    }
+}
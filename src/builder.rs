@@ -0,0 +1,113 @@
+use crate::{Level, Style, TreeBuilder, TreeConfig, TreeSymbols};
+
+/// Collects the settings of a `TreeBuilder` in one place before constructing it.
+///
+/// # Example
+///
+/// ```
+/// use debug_tree::{TreeBuilder, Style};
+/// let tree = TreeBuilder::builder()
+///     .indent(4)
+///     .style(Style::Rounded)
+///     .root_text("Report")
+///     .build();
+/// tree.add_leaf("Leaf");
+/// assert_eq!("\
+/// Report
+/// ╰──╼ Leaf", &tree.peek_string());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Builder {
+    config: Option<TreeConfig>,
+    enabled: Option<bool>,
+    level: Option<Level>,
+    root_text: Option<String>,
+}
+
+impl Builder {
+    /// Create a builder with the default settings.
+    pub fn new() -> Self {
+        Builder {
+            config: None,
+            enabled: None,
+            level: None,
+            root_text: None,
+        }
+    }
+
+    fn config_mut(&mut self) -> &mut TreeConfig {
+        self.config.get_or_insert_with(TreeConfig::default)
+    }
+
+    /// Use `config` as the configuration override.
+    pub fn config(mut self, config: TreeConfig) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Set the indentation of the configuration override.
+    pub fn indent(mut self, indent: usize) -> Self {
+        self.config_mut().indent = indent;
+        self
+    }
+
+    /// Set the symbols of the configuration override.
+    pub fn symbols(mut self, symbols: TreeSymbols) -> Self {
+        self.config_mut().symbols = symbols;
+        self
+    }
+
+    /// Set the symbols of the configuration override to a built-in style.
+    pub fn style(mut self, style: Style) -> Self {
+        self.config_mut().symbols = style.symbols();
+        self
+    }
+
+    /// Set the initial enabled state.
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = Some(enabled);
+        self
+    }
+
+    /// Set the most verbose level that is recorded.
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    /// Set the text of the root node.
+    pub fn root_text(mut self, text: &str) -> Self {
+        self.root_text = Some(text.to_string());
+        self
+    }
+
+    /// Construct the `TreeBuilder`.
+    pub fn build(self) -> TreeBuilder {
+        let tree = TreeBuilder::new();
+        self.apply(&tree);
+        tree
+    }
+
+    /// Apply the settings that have been set on this builder to an existing `tree`.
+    /// Settings that have not been set are left unchanged.
+    pub fn apply(&self, tree: &TreeBuilder) {
+        if let Some(config) = &self.config {
+            tree.set_config_override(config.clone());
+        }
+        if let Some(text) = &self.root_text {
+            tree.set_root_text(text);
+        }
+        if let Some(enabled) = self.enabled {
+            tree.set_enabled(enabled);
+        }
+        if let Some(level) = self.level {
+            tree.set_level(level);
+        }
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,157 @@
+#[cfg(unix)]
+mod imp {
+    use libc::c_int;
+    use once_cell::sync::Lazy;
+    use std::cell::Cell;
+    use std::fs::{File, OpenOptions};
+    use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    const STREAMS: [c_int; 2] = [libc::STDOUT_FILENO, libc::STDERR_FILENO];
+
+    /// Only one capture can redirect the standard streams of the process at a time.
+    static CAPTURE_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    thread_local! {
+        /// The number of captures running on this thread, which already hold `CAPTURE_LOCK`.
+        static DEPTH: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// Counts a capture running on this thread until dropped.
+    struct Nested;
+
+    impl Nested {
+        fn enter() -> Nested {
+            DEPTH.with(|x| x.set(x.get() + 1));
+            Nested
+        }
+    }
+
+    impl Drop for Nested {
+        fn drop(&mut self) {
+            DEPTH.with(|x| x.set(x.get() - 1));
+        }
+    }
+
+    /// Creates a new file that only this process can open, and removes its name,
+    /// so that it is deleted once closed.
+    /// The file is never an existing file or symlink, even if its name was guessed.
+    fn temp_file() -> std::io::Result<File> {
+        loop {
+            let path = std::env::temp_dir().join(format!(
+                "debug_tree_capture_{}_{}",
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::SeqCst)
+            ));
+            match OpenOptions::new()
+                .create_new(true)
+                .read(true)
+                .write(true)
+                .mode(0o600)
+                .open(&path)
+            {
+                Ok(file) => {
+                    let _ = std::fs::remove_file(&path);
+                    return Ok(file);
+                }
+                Err(err) if err.kind() == ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Redirects stdout and stderr to a file, and restores them when dropped.
+    struct Redirect {
+        saved: Vec<c_int>,
+        file: File,
+    }
+
+    impl Redirect {
+        fn new() -> std::io::Result<Redirect> {
+            let file = temp_file()?;
+            flush();
+            let mut redirect = Redirect {
+                saved: vec![],
+                file,
+            };
+            for &fd in STREAMS.iter() {
+                let saved = unsafe { libc::dup(fd) };
+                if saved < 0 || unsafe { libc::dup2(redirect.file.as_raw_fd(), fd) } < 0 {
+                    if saved >= 0 {
+                        unsafe { libc::close(saved) };
+                    }
+                    return Err(std::io::Error::last_os_error());
+                }
+                redirect.saved.push(saved);
+            }
+            Ok(redirect)
+        }
+
+        fn restore(&mut self) {
+            flush();
+            for (&fd, &saved) in STREAMS.iter().zip(&self.saved) {
+                unsafe {
+                    libc::dup2(saved, fd);
+                    libc::close(saved);
+                }
+            }
+            self.saved.clear();
+        }
+
+        fn output(mut self) -> String {
+            self.restore();
+            let mut output = vec![];
+            let _ = self.file.seek(SeekFrom::Start(0));
+            let _ = self.file.read_to_end(&mut output);
+            String::from_utf8_lossy(&output).into_owned()
+        }
+    }
+
+    impl Drop for Redirect {
+        fn drop(&mut self) {
+            self.restore();
+        }
+    }
+
+    fn flush() {
+        let _ = std::io::stdout().flush();
+        let _ = std::io::stderr().flush();
+    }
+
+    pub fn capture<R, F: FnOnce() -> R>(f: F) -> (R, String) {
+        if DEPTH.with(Cell::get) > 0 {
+            // The streams are already redirected by an outer capture, which gets the output
+            return (f(), String::new());
+        }
+        let _nested = Nested::enter();
+        let _lock = CAPTURE_LOCK.lock().unwrap_or_else(|x| x.into_inner());
+        match Redirect::new() {
+            Ok(redirect) => {
+                let result = f();
+                (result, redirect.output())
+            }
+            Err(err) => {
+                eprintln!("debug_tree: failed to capture output: {}", err);
+                (f(), String::new())
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    pub fn capture<R, F: FnOnce() -> R>(f: F) -> (R, String) {
+        (f(), String::new())
+    }
+}
+
+/// Calls `f` with the stdout and stderr of the process redirected,
+/// and returns its result along with everything it wrote to them.
+///
+/// Output is only captured on Unix. Elsewhere, `f` is called without capturing anything.
+/// A capture within another on the same thread captures nothing, leaving its output to the outer one.
+pub(crate) use imp::capture;
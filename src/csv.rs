@@ -0,0 +1,85 @@
+use crate::internal::Timing;
+use crate::Tree;
+use std::collections::HashMap;
+
+/// Quotes a CSV field if it contains a separator, a quote or a line break.
+fn field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+impl Tree {
+    /// Returns a CSV table with a row for each branch beneath this tree, with the columns:
+    ///
+    /// * `path`: the text of the branch and its ancestors, separated by `/`.
+    /// * `depth`: the depth of the branch, where top-level nodes are at depth 0.
+    /// * `children`: the number of direct children of the branch.
+    /// * `duration_us`: the microseconds between entering and exiting the branch, when it has
+    ///   been timed with [set_timing](crate::TreeBuilder::set_timing), and is empty otherwise.
+    /// * `calls`: the number of branches with the same path.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// {
+    ///     let _request = tree.add_branch("Request");
+    ///     for _ in 0..2 {
+    ///         let _query = tree.add_branch("Query, cached");
+    ///         tree.add_leaf("Hit");
+    ///     }
+    /// }
+    /// assert_eq!(
+    ///     "path,depth,children,duration_us,calls\n\
+    ///      Request,0,2,,1\n\
+    ///      \"Request/Query, cached\",1,1,,2\n\
+    ///      \"Request/Query, cached\",1,1,,2\n",
+    ///     tree.snapshot().to_csv_stats()
+    /// );
+    /// ```
+    pub fn to_csv_stats(&self) -> String {
+        fn rows<'t>(
+            tree: &'t Tree,
+            prefix: Option<&str>,
+            depth: usize,
+            out: &mut Vec<(String, usize, &'t Tree)>,
+        ) {
+            for x in tree.children.iter().filter(|x| !x.children.is_empty()) {
+                let text = x.text.as_deref().unwrap_or("");
+                let path = match prefix {
+                    Some(prefix) => format!("{}/{}", prefix, text),
+                    None => text.to_string(),
+                };
+                out.push((path.clone(), depth, x));
+                rows(x, Some(&path), depth + 1, out);
+            }
+        }
+        let mut branches = Vec::new();
+        rows(self, None, 0, &mut branches);
+        let mut calls: HashMap<&str, usize> = HashMap::new();
+        for (path, _, _) in &branches {
+            *calls.entry(path).or_default() += 1;
+        }
+        let mut out = String::from("path,depth,children,duration_us,calls\n");
+        for (path, depth, tree) in &branches {
+            let duration = tree
+                .metadata::<Timing>()
+                .and_then(Timing::duration)
+                .map(|x| x.as_micros().to_string())
+                .unwrap_or_default();
+            out.push_str(&format!(
+                "{},{},{},{},{}\n",
+                field(path),
+                depth,
+                tree.children.len(),
+                duration,
+                calls[path.as_str()]
+            ));
+        }
+        out
+    }
+}
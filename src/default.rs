@@ -1,4 +1,24 @@
+use crate::builder::Builder;
+use crate::internal::TreeBuilderBase;
+use crate::sync::Lock;
 use crate::TreeBuilder;
+use once_cell::sync::Lazy;
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex, Weak};
+
+/// Settings applied to every thread's default tree, and the default trees created so far.
+struct DefaultSettings {
+    builder: Builder,
+    trees: Vec<Weak<Lock<TreeBuilderBase>>>,
+}
+
+static DEFAULT_SETTINGS: Lazy<Mutex<DefaultSettings>> = Lazy::new(|| {
+    Mutex::new(DefaultSettings {
+        builder: Builder::new(),
+        trees: vec![],
+    })
+});
 
 /// Returns the default tree for the current thread
 ///
@@ -10,10 +30,127 @@ use crate::TreeBuilder;
 /// assert_eq!("A new leaf", default_tree().peek_string());
 /// ```
 pub fn default_tree() -> TreeBuilder {
-    thread_local! {
-        static DEFAULT_BUILDER: TreeBuilder = TreeBuilder::new();
+    match SCOPED_DEFAULTS.with(|x| x.borrow().last().cloned()) {
+        Some(x) => x,
+        None => DEFAULT_BUILDER.with(|f| f.clone()),
+    }
+}
+
+thread_local! {
+    /// Trees that replace the default tree of this thread, innermost last.
+    /// See [set_default_scoped].
+    static SCOPED_DEFAULTS: RefCell<Vec<TreeBuilder>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Restores the previous default tree of the thread when dropped.
+/// Returned by [set_default_scoped].
+/// If guards are dropped out of order, dropping one also ends the overrides of the guards
+/// created after it, and dropping those later has no effect.
+#[must_use = "the default tree is restored as soon as the guard is dropped"]
+pub struct ScopedDefault {
+    depth: usize,
+    /// The guard restores the default tree of the thread that created it.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for ScopedDefault {
+    fn drop(&mut self) {
+        SCOPED_DEFAULTS.with(|x| x.borrow_mut().truncate(self.depth));
+    }
+}
+
+/// Makes `tree` the default tree of the current thread until the returned guard is dropped,
+/// so that the default tree macros, including those used by dependencies, add to it.
+/// This lets a library contribute to its caller's tree.
+///
+/// # Example
+///
+/// ```
+/// use debug_tree::{add_leaf, default_tree, set_default_scoped, TreeBuilder};
+/// fn library() {
+///     add_leaf!("Called the library");
+/// }
+/// let tree = TreeBuilder::new();
+/// {
+///     let _guard = set_default_scoped(&tree);
+///     library();
+/// }
+/// library();
+/// assert_eq!("Called the library", tree.peek_string());
+/// assert_eq!("Called the library", default_tree().peek_string());
+/// ```
+pub fn set_default_scoped(tree: &TreeBuilder) -> ScopedDefault {
+    SCOPED_DEFAULTS.with(|x| {
+        let mut x = x.borrow_mut();
+        x.push(tree.clone());
+        ScopedDefault {
+            depth: x.len() - 1,
+            _not_send: PhantomData,
+        }
+    })
+}
+
+thread_local! {
+    static DEFAULT_BUILDER: TreeBuilder = {
+        let mut settings = DEFAULT_SETTINGS.lock().unwrap();
+        let tree = settings.builder.clone().build();
+        settings.trees.retain(|x| x.strong_count() > 0);
+        settings.trees.push(Arc::downgrade(&tree.0));
+        tree
+    };
+}
+
+/// The default trees of the threads that are still running.
+pub(crate) fn default_trees() -> Vec<TreeBuilder> {
+    let mut settings = DEFAULT_SETTINGS.lock().unwrap();
+    settings.trees.retain(|x| x.strong_count() > 0);
+    settings
+        .trees
+        .iter()
+        .filter_map(|x| x.upgrade())
+        .map(TreeBuilder::from_shared)
+        .collect()
+}
+
+/// Returns whether the default tree is enabled and the module at `module_path` is enabled by
+/// the `RUST_DEBUG_TREE` [filter](crate::filter::ModuleFilter).
+/// This is used by the default tree macros, with the module path of the caller.
+pub fn is_default_tree_enabled(module_path: &str) -> bool {
+    crate::filter::is_module_enabled(module_path)
+        && match SCOPED_DEFAULTS.with(|x| x.borrow().last().map(TreeBuilder::is_enabled)) {
+            Some(x) => x,
+            None => DEFAULT_BUILDER.with(|x| x.is_enabled()),
+        }
+}
+
+/// Updates the settings of the default tree for every thread.
+///
+/// The settings are applied to the default trees that already exist,
+/// and to those created later by other threads.
+/// `update` is called without holding any lock, so it may use the default tree.
+///
+/// # Example
+///
+/// ```
+/// use debug_tree::{configure_default, default_tree, Style};
+/// configure_default(|x| x.indent(4).style(Style::Rounded));
+/// std::thread::spawn(|| {
+///     default_tree().add_leaf("1");
+///     let _branch = default_tree().enter_scoped();
+///     default_tree().add_leaf("1.1");
+///     assert_eq!("1\n╰──╼ 1.1", default_tree().string());
+/// }).join().unwrap();
+/// ```
+pub fn configure_default<F: FnOnce(Builder) -> Builder>(update: F) {
+    // The default tree of a thread locks the settings when it is created, which `update` may do
+    let builder = DEFAULT_SETTINGS.lock().unwrap().builder.clone();
+    let builder = update(builder);
+    let mut settings = DEFAULT_SETTINGS.lock().unwrap();
+    settings.builder = builder;
+    settings.trees.retain(|x| x.strong_count() > 0);
+    for tree in settings.trees.iter().filter_map(|x| x.upgrade()) {
+        settings.builder.apply(&TreeBuilder::from_shared(tree));
     }
-    DEFAULT_BUILDER.with(|f| f.clone())
 }
 
 /// Adds a leaf to the default tree with the given text and formatting arguments
@@ -33,12 +170,55 @@ pub fn default_tree() -> TreeBuilder {
 /// ```
 #[macro_export]
 macro_rules! add_leaf {
-        ($($arg:tt)*) => {
-            if $crate::default::default_tree().is_enabled() {
-                $crate::default::default_tree().add_leaf(&format!($($arg)*))
-            }
-        };
-    }
+    (target: $target:expr, $($arg:tt)*) => {
+        if $crate::default::is_default_tree_enabled(module_path!()) {
+            $crate::default::default_tree().add_leaf_target_text(
+                $crate::Level::Info,
+                $target,
+                $crate::__text(format_args!($($arg)*)),
+            )
+        }
+    };
+    ($($arg:tt)*) => {
+        if $crate::default::is_default_tree_enabled(module_path!()) {
+            $crate::default::default_tree().add_leaf_target_text(
+                $crate::Level::Info,
+                module_path!(),
+                $crate::__text(format_args!($($arg)*)),
+            )
+        }
+    };
+}
+
+/// Adds a leaf to the default tree with the given text and formatting arguments,
+/// only if `condition` is true.
+///
+/// The formatting arguments are not evaluated if the condition is false.
+///
+/// # Arguments
+/// * `condition` - A `bool` expression.
+/// * `text...` - Formatted text arguments, as per `format!(...)`.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// use debug_tree::{default_tree, add_leaf_if};
+/// fn main() {
+///     for i in 0..4 {
+///         add_leaf_if!(i % 2 == 0, "{} is even", i);
+///     }
+///     assert_eq!("0 is even\n2 is even", &default_tree().string());
+/// }
+/// ```
+#[macro_export]
+macro_rules! add_leaf_if {
+    ($condition:expr, $($arg:tt)*) => {
+        if $condition {
+            $crate::add_leaf!($($arg)*)
+        }
+    };
+}
 
 /// Adds the value as a leaf to the default tree.
 ///
@@ -62,13 +242,331 @@ macro_rules! add_leaf {
 macro_rules! add_leaf_value {
     ($value:expr) => {{
         let v = $value;
-        if $crate::default::default_tree().is_enabled() {
-            $crate::default::default_tree().add_leaf(&format!("{}", &v));
+        if $crate::default::is_default_tree_enabled(module_path!()) {
+            $crate::default::default_tree().add_leaf_target(module_path!(), &format!("{}", &v));
         }
         v
     }};
 }
 
+/// Adds a byte slice to the default tree as a hex dump, with one leaf for each line of 16 bytes.
+/// See [TreeBuilder::add_hexdump](crate::TreeBuilder::add_hexdump).
+///
+/// # Arguments
+/// * `bytes` - An expression that implements `AsRef<[u8]>`.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// use debug_tree::{default_tree, add_hexdump};
+/// fn main() {
+///     add_hexdump!(b"Hello, world!\n");
+///     assert_eq!(
+///         "00000000  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0a        |Hello, world!.|",
+///         &default_tree().string()
+///     );
+/// }
+/// ```
+#[macro_export]
+macro_rules! add_hexdump {
+    ($bytes:expr) => {
+        if $crate::default::is_default_tree_enabled(module_path!()) {
+            $crate::default::default_tree().add_hexdump(AsRef::<[u8]>::as_ref(&$bytes))
+        }
+    };
+}
+
+/// Adds a leaf to the default tree with a label and a human-readable duration,
+/// such as `Parsed 12 rows: 1.24ms`. See [add_duration](crate::TreeBuilder::add_duration).
+///
+/// # Arguments
+/// * `duration` - A `Duration` expression.
+/// * `label...` - Formatted label arguments, as per `format!(...)`.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// use debug_tree::{default_tree, add_duration};
+/// use std::time::Duration;
+/// fn main() {
+///     add_duration!(Duration::from_millis(4050), "Synced {} files", 3);
+///     assert_eq!("Synced 3 files: 4.05s", &default_tree().string());
+/// }
+/// ```
+#[macro_export]
+macro_rules! add_duration {
+    ($duration:expr, $($arg:tt)*) => {
+        if $crate::default::is_default_tree_enabled(module_path!()) {
+            $crate::default::default_tree().add_duration(&format!($($arg)*), $duration)
+        }
+    };
+}
+
+/// Adds a leaf to the default tree with a label and a human-readable size in bytes,
+/// such as `Read body: 4.2 KiB`. See [add_size](crate::TreeBuilder::add_size).
+///
+/// # Arguments
+/// * `bytes` - A `u64` expression.
+/// * `label...` - Formatted label arguments, as per `format!(...)`.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// use debug_tree::{default_tree, add_size};
+/// fn main() {
+///     add_size!(512, "Header");
+///     assert_eq!("Header: 512 B", &default_tree().string());
+/// }
+/// ```
+#[macro_export]
+macro_rules! add_size {
+    ($bytes:expr, $($arg:tt)*) => {
+        if $crate::default::is_default_tree_enabled(module_path!()) {
+            $crate::default::default_tree().add_size(&format!($($arg)*), $bytes)
+        }
+    };
+}
+
+/// Serializes `value` and adds it to the default tree as a subtree labelled with the
+/// expression. See [add_serde](crate::TreeBuilder::add_serde).
+///
+/// # Arguments
+/// * `value` - An expression that implements `serde::Serialize`.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// use debug_tree::{default_tree, add_serde};
+/// fn main() {
+///     let user = ("ada", Some(36));
+///     add_serde!(user);
+///     assert_eq!("user\n├╼ [0]: \"ada\"\n└╼ [1]: 36", &default_tree().string());
+/// }
+/// ```
+#[cfg(feature = "serde")]
+#[macro_export]
+macro_rules! add_serde {
+    ($value:expr) => {
+        if $crate::default::is_default_tree_enabled(module_path!()) {
+            $crate::add_serde_to!($crate::default::default_tree(), $value)
+        }
+    };
+}
+
+/// Adds a `Result` as a leaf to the default tree, formatted as `Ok(..)` or `Err(..)`.
+/// An `Err` is added at [Level::Error](crate::Level::Error).
+///
+/// Returns the given `result` argument, so it can be used with `?`.
+///
+/// # Arguments
+/// * `result` - A `Result` expression whose values implement the `Debug` trait.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// use debug_tree::{default_tree, add_result};
+/// fn parse(x: &str) -> Result<i32, std::num::ParseIntError> {
+///     let value = add_result!(x.parse::<i32>())?;
+///     Ok(value * 2)
+/// }
+/// fn main() {
+///     assert_eq!(Ok(4), parse("2"));
+///     assert!(parse("two").is_err());
+///     assert_eq!("\
+/// Ok(2)
+/// Err(ParseIntError { kind: InvalidDigit })", &default_tree().string());
+/// }
+/// ```
+#[macro_export]
+macro_rules! add_result {
+    ($result:expr) => {{
+        let v = $result;
+        if $crate::filter::is_module_enabled(module_path!()) {
+            $crate::add_result_to!($crate::default::default_tree(), v)
+        } else {
+            v
+        }
+    }};
+}
+
+/// Adds an `Option` as a leaf to the default tree, formatted as `Some(..)` or `None`.
+/// `None` is added at [Level::Warn](crate::Level::Warn).
+///
+/// Returns the given `option` argument, so it can be used with `?`.
+///
+/// # Arguments
+/// * `option` - An `Option` expression whose value implements the `Debug` trait.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// use debug_tree::{default_tree, add_option};
+/// fn main() {
+///     let values = vec![1, 2, 3];
+///     assert_eq!(Some(&2), add_option!(values.get(1)));
+///     assert_eq!(None, add_option!(values.get(5)));
+///     assert_eq!("Some(2)\nNone", &default_tree().string());
+/// }
+/// ```
+#[macro_export]
+macro_rules! add_option {
+    ($option:expr) => {{
+        let v = $option;
+        if $crate::filter::is_module_enabled(module_path!()) {
+            $crate::add_option_to!($crate::default::default_tree(), v)
+        } else {
+            v
+        }
+    }};
+}
+
+/// Adds an error to the default tree, with the chain of its sources nested beneath it.
+/// See [add_error](crate::TreeBuilder::add_error).
+///
+/// # Arguments
+/// * `error` - A reference to a value that implements the `Error` trait.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// use debug_tree::{default_tree, add_error};
+/// fn main() {
+///     if let Err(err) = "".parse::<u8>() {
+///         add_error!(&err);
+///     }
+///     assert_eq!("cannot parse integer from empty string", &default_tree().string());
+/// }
+/// ```
+#[macro_export]
+macro_rules! add_error {
+    ($error:expr) => {
+        if $crate::filter::is_module_enabled(module_path!()) {
+            $crate::add_error_to!($crate::default::default_tree(), $error)
+        }
+    };
+}
+
+/// Adds a leaf at the `Error` level to the default tree with the given text and formatting
+/// arguments, like the `log` crate's `error!`.
+/// See [tree_error_to](crate::tree_error_to).
+///
+/// # Arguments
+/// * `text...` - Formatted text arguments, as per `format!(...)`.
+///
+/// See [tree_warn](crate::tree_warn) for an example.
+///
+#[macro_export]
+macro_rules! tree_error {
+    ($($arg:tt)*) => {
+        if $crate::filter::is_module_enabled(module_path!()) {
+            $crate::tree_error_to!($crate::default::default_tree(), $($arg)*)
+        }
+    };
+}
+
+/// Adds a leaf at the `Warn` level to the default tree with the given text and formatting
+/// arguments, like the `log` crate's `warn!`.
+/// See [tree_warn_to](crate::tree_warn_to).
+///
+/// # Arguments
+/// * `text...` - Formatted text arguments, as per `format!(...)`.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// use debug_tree::{default_tree, Level, tree_warn, tree_info};
+/// fn main() {
+///     tree_warn!("Disk {}% full", 93);
+///     tree_info!("Rotated logs");
+///     let tree = default_tree().snapshot();
+///     assert_eq!(Level::Warn, tree.children[0].level);
+///     assert_eq!("Disk 93% full\nRotated logs", &default_tree().string());
+/// }
+/// ```
+#[macro_export]
+macro_rules! tree_warn {
+    ($($arg:tt)*) => {
+        if $crate::filter::is_module_enabled(module_path!()) {
+            $crate::tree_warn_to!($crate::default::default_tree(), $($arg)*)
+        }
+    };
+}
+
+/// Adds a leaf at the `Info` level to the default tree with the given text and formatting
+/// arguments, like the `log` crate's `info!`.
+/// See [tree_info_to](crate::tree_info_to).
+///
+/// # Arguments
+/// * `text...` - Formatted text arguments, as per `format!(...)`.
+///
+/// See [tree_warn](crate::tree_warn) for an example.
+///
+#[macro_export]
+macro_rules! tree_info {
+    ($($arg:tt)*) => {
+        if $crate::filter::is_module_enabled(module_path!()) {
+            $crate::tree_info_to!($crate::default::default_tree(), $($arg)*)
+        }
+    };
+}
+
+/// Adds a leaf at the `Debug` level to the default tree with the given text and formatting
+/// arguments, like the `log` crate's `debug!`.
+/// See [tree_debug_to](crate::tree_debug_to).
+///
+/// # Arguments
+/// * `text...` - Formatted text arguments, as per `format!(...)`.
+///
+/// See [tree_warn](crate::tree_warn) for an example.
+///
+#[macro_export]
+macro_rules! tree_debug {
+    ($($arg:tt)*) => {
+        if $crate::filter::is_module_enabled(module_path!()) {
+            $crate::tree_debug_to!($crate::default::default_tree(), $($arg)*)
+        }
+    };
+}
+
+/// Adds a leaf to the default tree with the time elapsed since the previous checkpoint in the
+/// current branch. See [checkpoint](crate::TreeBuilder::checkpoint).
+///
+/// # Arguments
+/// * `label...` - Formatted label arguments, as per `format!(...)`.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// use debug_tree::{default_tree, add_branch, checkpoint};
+/// fn main() {
+///     {
+///         add_branch!("Request");
+///         checkpoint!("Parsed headers");
+///         checkpoint!("Read body");
+///     }
+///     let text = default_tree().string();
+///     assert!(text.starts_with("Request\n├╼ Parsed headers: "));
+///     assert!(text.contains("\n└╼ Read body: "));
+/// }
+/// ```
+#[macro_export]
+macro_rules! checkpoint {
+    ($($arg:tt)*) => {
+        if $crate::filter::is_module_enabled(module_path!()) {
+            $crate::checkpoint_to!($crate::default::default_tree(), $($arg)*)
+        }
+    };
+}
+
 /// Adds a scoped branch to the default tree with the given text and formatting arguments
 /// The branch will be exited at the end of the current block.
 ///
@@ -94,29 +592,114 @@ macro_rules! add_leaf_value {
 /// Sibling of Branch" , &default_tree().string());
 /// }
 /// ```
+///
+/// The branch can be bound to a name, so that it can be exited early with `release()`.
+/// An identifier followed by a comma is always taken as that name, so the text must start
+/// with a string literal: write `add_branch!("{}", text)` rather than `add_branch!(text, ..)`.
+///
+/// ```
+/// #[macro_use]
+/// use debug_tree::{default_tree, add_branch, add_leaf};
+/// fn main() {
+///     add_branch!(branch, "New {}", "Branch");
+///     add_leaf!("Child of {}", "Branch");
+///     branch.release();
+///     add_leaf!("Sibling of {}", "Branch");
+///     assert_eq!("\
+/// New Branch
+/// └╼ Child of Branch
+/// Sibling of Branch" , &default_tree().string());
+/// }
+/// ```
 #[macro_export]
 macro_rules! add_branch {
     () => {
-        let _debug_tree_branch = if $crate::default::default_tree().is_enabled() {
+        let _debug_tree_branch = if $crate::default::is_default_tree_enabled(module_path!()) {
             $crate::default::default_tree().enter_scoped()
         } else {
             $crate::scoped_branch::ScopedBranch::none()
         };
     };
+    (target: $target:expr, $($arg:tt)*) => {
+        let _debug_tree_branch = if $crate::default::is_default_tree_enabled(module_path!()) {
+            $crate::default::default_tree().add_branch_target_text(
+                $target,
+                $crate::__text(format_args!($($arg)*)),
+            )
+        } else {
+            $crate::scoped_branch::ScopedBranch::none()
+        };
+    };
+    ($name:ident, $($arg:tt)*) => {
+        #[allow(unused_mut)]
+        let mut $name = if $crate::default::is_default_tree_enabled(module_path!()) {
+            $crate::default::default_tree().add_branch_target_text(
+                module_path!(),
+                $crate::__text(format_args!($($arg)*)),
+            )
+        } else {
+            $crate::scoped_branch::ScopedBranch::none()
+        };
+    };
     ($($arg:tt)*) => {
-        let _debug_tree_branch = if $crate::default::default_tree().is_enabled() {
-            $crate::default::default_tree().add_branch(&format!($($arg)*))
+        let _debug_tree_branch = if $crate::default::is_default_tree_enabled(module_path!()) {
+            $crate::default::default_tree().add_branch_target_text(
+                module_path!(),
+                $crate::__text(format_args!($($arg)*)),
+            )
         } else {
             $crate::scoped_branch::ScopedBranch::none()
         };
     };
+}
 
+/// Adds a scoped branch to the default tree with the given text and formatting arguments,
+/// only if `condition` is true.
+/// The branch will be exited at the end of the current block.
+///
+/// The formatting arguments are not evaluated and no branch is entered if the condition is false.
+///
+/// # Arguments
+/// * `condition` - A `bool` expression.
+/// * `text...` - Formatted text arguments, as per `format!(...)`.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// use debug_tree::{default_tree, add_branch_if, add_leaf};
+/// fn main() {
+///     {
+///         add_branch_if!(false, "Hidden {}", "Branch");
+///         add_leaf!("Not a child");
+///     }
+///     {
+///         add_branch_if!(true, "Shown {}", "Branch");
+///         add_leaf!("Child");
+///     }
+///     assert_eq!("\
+/// Not a child
+/// Shown Branch
+/// └╼ Child" , &default_tree().string());
+/// }
+/// ```
+#[macro_export]
+macro_rules! add_branch_if {
+    ($condition:expr, $($arg:tt)*) => {
+        let _debug_tree_branch = if $condition && $crate::default::is_default_tree_enabled(module_path!()) {
+            $crate::default::default_tree().add_branch_target_text(
+                module_path!(),
+                $crate::__text(format_args!($($arg)*)),
+            )
+        } else {
+            $crate::scoped_branch::ScopedBranch::none()
+        };
+    };
 }
 
 #[cfg(test)]
 mod test {
     use crate::default_tree;
-    use crate::*;
 
     #[test]
     fn unnamed_branch() {
@@ -157,6 +740,31 @@ mod test {
         );
     }
 
+    #[test]
+    fn named_branch_release() {
+        add_branch!(b, "21");
+        add_leaf!("21.1");
+        {
+            add_branch!(inner, "21.2");
+            add_leaf!("21.2.1");
+            inner.release();
+            add_leaf!("21.3");
+        }
+        b.release();
+        add_leaf!("22");
+        default_tree().peek_print();
+        assert_eq!(
+            "\
+21
+├╼ 21.1
+├╼ 21.2
+│ └╼ 21.2.1
+└╼ 21.3
+22",
+            default_tree().string()
+        );
+    }
+
     #[test]
     fn leaf_with_value() {
         let value = add_leaf_value!(10);
@@ -1,14 +1,14 @@
 use crate::TreeBuilder;
 
 /// A deferred function called with an argument, `TreeBuilder`
-pub struct DeferredFn<F: Fn(TreeBuilder) -> ()> {
+pub struct DeferredFn<F: Fn(TreeBuilder)> {
     tree: Option<TreeBuilder>,
     action: Option<F>,
 }
 
 impl<F> DeferredFn<F>
 where
-    F: Fn(TreeBuilder) -> (),
+    F: Fn(TreeBuilder),
 {
     /// Create a new deferred function based on `tree`
     pub fn new(tree: TreeBuilder, action: F) -> Self {
@@ -36,7 +36,7 @@ where
 
 impl<F> Drop for DeferredFn<F>
 where
-    F: Fn(TreeBuilder) -> (),
+    F: Fn(TreeBuilder),
 {
     fn drop(&mut self) {
         if let (Some(x), Some(action)) = (&self.tree, &self.action) {
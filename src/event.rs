@@ -0,0 +1,137 @@
+/// A change made to a tree, as received from [subscribe](crate::TreeBuilder::subscribe).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum TreeEvent {
+    /// A node was added, at `path` from the top level.
+    LeafAdded { path: Vec<usize>, text: String },
+    /// A branch was entered.
+    Entered,
+    /// A branch was exited.
+    Exited,
+    /// The tree was cleared.
+    Cleared,
+}
+
+impl TreeEvent {
+    /// Encodes this event as a record of an event log.
+    ///
+    /// Each record starts with a line containing its kind. A `leaf` record also has the path of
+    /// the node, with its indices separated by dots, and the length in bytes of the text, which
+    /// follows on its own line.
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            TreeEvent::LeafAdded { path, text } => format!(
+                "leaf {} {}\n{}\n",
+                path.iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<_>>()
+                    .join("."),
+                text.len(),
+                text
+            )
+            .into_bytes(),
+            TreeEvent::Entered => b"enter\n".to_vec(),
+            TreeEvent::Exited => b"exit\n".to_vec(),
+            TreeEvent::Cleared => b"clear\n".to_vec(),
+        }
+    }
+}
+
+/// Decodes the event at the start of `bytes`, returning the event and the length of its record.
+/// Returns `None` if the record is incomplete or invalid.
+fn decode(bytes: &[u8]) -> Option<(TreeEvent, usize)> {
+    let end = bytes.iter().position(|&x| x == b'\n')?;
+    let header = std::str::from_utf8(&bytes[..end]).ok()?;
+    let mut parts = header.split(' ');
+    let event = match parts.next()? {
+        "enter" => TreeEvent::Entered,
+        "exit" => TreeEvent::Exited,
+        "clear" => TreeEvent::Cleared,
+        "leaf" => {
+            let path = parts
+                .next()?
+                .split('.')
+                .map(|x| x.parse().ok())
+                .collect::<Option<Vec<usize>>>()?;
+            let len: usize = parts.next()?.parse().ok()?;
+            let text_end = end.checked_add(1)?.checked_add(len)?;
+            let text = bytes.get(end + 1..text_end)?;
+            if bytes.get(text_end) != Some(&b'\n') {
+                return None;
+            }
+            let text = String::from_utf8(text.to_vec()).ok()?;
+            return Some((TreeEvent::LeafAdded { path, text }, text_end + 1));
+        }
+        _ => return None,
+    };
+    Some((event, end + 1))
+}
+
+/// Decodes the records of an event log, as written by
+/// [log_events_to](crate::TreeBuilder::log_events_to).
+/// Decoding stops at the first incomplete or invalid record,
+/// such as one that was being written when the process crashed.
+pub fn decode_log(mut bytes: &[u8]) -> Vec<TreeEvent> {
+    let mut ret = vec![];
+    while let Some((event, len)) = decode(bytes) {
+        ret.push(event);
+        bytes = &bytes[len..];
+    }
+    ret
+}
+
+/// Reads the events of the event log at `path`. See [decode_log].
+pub fn read_log<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<Vec<TreeEvent>> {
+    Ok(decode_log(&std::fs::read(path)?))
+}
+
+/// Parses events from JSON Lines, with one object per line describing a builder operation:
+///
+/// | Line                                   | Operation                       |
+/// |----------------------------------------|---------------------------------|
+/// | `{"op": "leaf", "text": "Connected"}`  | Add a leaf to the current branch |
+/// | `{"op": "enter"}`                      | Step into the last leaf          |
+/// | `{"op": "exit"}`                       | Step out of the current branch   |
+/// | `{"op": "clear"}`                      | Clear the tree                   |
+///
+/// Other fields are ignored, and so are blank lines.
+/// The `path` of each `LeafAdded` event is the position the leaf would have if the events were
+/// replayed into an empty tree.
+///
+/// # Example
+///
+/// ```
+/// use debug_tree::TreeBuilder;
+/// use debug_tree::event::parse_jsonl;
+/// let events = parse_jsonl(r#"
+/// {"op": "leaf", "text": "Request"}
+/// {"op": "enter"}
+/// {"op": "leaf", "text": "Parsed \"body\""}
+/// {"op": "exit"}
+/// "#).unwrap();
+/// assert_eq!("Request\n└╼ Parsed \"body\"", TreeBuilder::from_events(&events).peek_string());
+/// ```
+pub fn parse_jsonl(s: &str) -> Result<Vec<TreeEvent>, String> {
+    let tree = crate::TreeBuilder::new();
+    let events = tree.subscribe();
+    for (i, line) in s.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let error = |e: String| format!("line {}: {}", i + 1, e);
+        let value = crate::json::parse(line).map_err(error)?;
+        match value.get("op").and_then(|x| x.as_str()) {
+            Some("leaf") => match value.get("text").and_then(|x| x.as_str()) {
+                Some(text) => tree.add_leaf(text),
+                None => return Err(error("expected a `text` string".to_string())),
+            },
+            Some("enter") => tree.enter(),
+            Some("exit") => {
+                tree.exit();
+            }
+            Some("clear") => tree.clear(),
+            Some(op) => return Err(error(format!("unknown op `{}`", op))),
+            None => return Err(error("expected an `op` string".to_string())),
+        }
+    }
+    Ok(events.try_iter().collect())
+}
@@ -0,0 +1,96 @@
+use once_cell::sync::Lazy;
+
+/// Name of the environment variable read by [module_filter].
+pub const FILTER_ENV: &str = "RUST_DEBUG_TREE";
+
+/// Enables or disables recording by module path.
+///
+/// A filter is a comma-separated list of directives, each either `module::path=on|off`,
+/// or a bare `on|off` that applies to modules not matched by any other directive.
+/// The directive with the longest matching module path wins.
+/// Modules that are not matched are disabled, unless a bare `on` is given.
+///
+/// # Example
+///
+/// ```
+/// use debug_tree::filter::ModuleFilter;
+/// let filter = ModuleFilter::parse("my_crate::parser=on,my_crate::parser::lexer=off").unwrap();
+/// assert!(filter.is_enabled("my_crate::parser"));
+/// assert!(filter.is_enabled("my_crate::parser::ast"));
+/// assert!(!filter.is_enabled("my_crate::parser::lexer"));
+/// assert!(!filter.is_enabled("my_crate::parsers"));
+/// assert!(!filter.is_enabled("other"));
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ModuleFilter {
+    directives: Vec<(String, bool)>,
+    default: bool,
+}
+
+fn parse_switch(s: &str) -> Result<bool, String> {
+    match s.trim().to_ascii_lowercase().as_str() {
+        "on" | "true" | "1" => Ok(true),
+        "off" | "false" | "0" => Ok(false),
+        _ => Err(format!("expected `on` or `off`, found `{}`", s.trim())),
+    }
+}
+
+impl ModuleFilter {
+    /// Parses a filter such as `my_crate::parser=on,other=off`.
+    pub fn parse(s: &str) -> Result<ModuleFilter, String> {
+        let mut filter = ModuleFilter {
+            directives: vec![],
+            default: false,
+        };
+        for directive in s.split(',').map(str::trim).filter(|x| !x.is_empty()) {
+            match directive.split_once('=') {
+                Some((module, switch)) => filter
+                    .directives
+                    .push((module.trim().to_string(), parse_switch(switch)?)),
+                None => filter.default = parse_switch(directive)?,
+            }
+        }
+        Ok(filter)
+    }
+
+    /// Returns whether recording from the module at `module_path` is enabled.
+    pub fn is_enabled(&self, module_path: &str) -> bool {
+        self.directives
+            .iter()
+            .filter(|(module, _)| module_matches(module, module_path))
+            .max_by_key(|(module, _)| module.len())
+            .map(|&(_, enabled)| enabled)
+            .unwrap_or(self.default)
+    }
+}
+
+/// Returns whether `path` is the module `prefix` or one of its submodules.
+pub(crate) fn module_matches(prefix: &str, path: &str) -> bool {
+    path.strip_prefix(prefix)
+        .map(|rest| rest.is_empty() || rest.starts_with("::"))
+        .unwrap_or(false)
+}
+
+static MODULE_FILTER: Lazy<Option<ModuleFilter>> = Lazy::new(|| match std::env::var(FILTER_ENV) {
+    Ok(x) => match ModuleFilter::parse(&x) {
+        Ok(filter) => Some(filter),
+        Err(err) => {
+            eprintln!("debug_tree: ignoring invalid {}: {}", FILTER_ENV, err);
+            None
+        }
+    },
+    Err(_) => None,
+});
+
+/// The filter parsed from the `RUST_DEBUG_TREE` environment variable, the first time it is used.
+pub fn module_filter() -> Option<&'static ModuleFilter> {
+    MODULE_FILTER.as_ref()
+}
+
+/// Returns whether the default tree macros record from the module at `module_path`.
+/// Every module is enabled if `RUST_DEBUG_TREE` is not set.
+pub fn is_module_enabled(module_path: &str) -> bool {
+    module_filter()
+        .map(|x| x.is_enabled(module_path))
+        .unwrap_or(true)
+}
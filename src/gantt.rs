@@ -0,0 +1,81 @@
+use crate::internal::Timing;
+use crate::Tree;
+use std::time::Instant;
+
+/// Replaces the characters that Mermaid treats as syntax in task and section names.
+fn name(tree: &Tree) -> String {
+    tree.text
+        .as_deref()
+        .unwrap_or("")
+        .chars()
+        .map(|c| match c {
+            ':' | ';' | '#' | '\n' | '\r' => ' ',
+            c => c,
+        })
+        .collect()
+}
+
+/// The milliseconds from `origin` to `time`.
+fn millis(origin: Instant, time: Instant) -> u128 {
+    time.saturating_duration_since(origin).as_millis()
+}
+
+impl Tree {
+    /// Returns a Mermaid gantt chart of the branches beneath this tree that have been timed with
+    /// [set_timing](crate::TreeBuilder::set_timing), with a section for each top-level node.
+    /// Times are in milliseconds since the first node was added.
+    /// Leaves, and branches that haven't been exited, are left out.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// tree.set_timing(true);
+    /// {
+    ///     let _request = tree.add_branch("Request");
+    ///     let _parse = tree.add_branch("Parse: body");
+    /// }
+    /// let chart = tree.snapshot().to_mermaid_gantt();
+    /// assert!(chart.starts_with("gantt\n    dateFormat x\n    axisFormat %S.%L\n"));
+    /// assert!(chart.contains("\n    section Request\n    Request :0, "));
+    /// assert!(chart.contains("\n    Parse  body :"));
+    /// ```
+    pub fn to_mermaid_gantt(&self) -> String {
+        fn tasks(tree: &Tree, origin: Instant, out: &mut String) {
+            if let Some(Timing {
+                start,
+                end: Some(end),
+            }) = tree.metadata::<Timing>()
+            {
+                out.push_str(&format!(
+                    "    {} :{}, {}\n",
+                    name(tree),
+                    millis(origin, *start),
+                    millis(origin, *end)
+                ));
+            }
+            for x in &tree.children {
+                tasks(x, origin, out);
+            }
+        }
+        let mut origin = None;
+        self.visit(|x, _| {
+            if let Some(timing) = x.metadata::<Timing>() {
+                origin = Some(origin.map_or(timing.start, |x: Instant| x.min(timing.start)));
+            }
+        });
+        let mut out = String::from("gantt\n    dateFormat x\n    axisFormat %S.%L\n");
+        if let Some(origin) = origin {
+            for x in &self.children {
+                let mut section = String::new();
+                tasks(x, origin, &mut section);
+                if !section.is_empty() {
+                    out.push_str(&format!("    section {}\n", name(x)));
+                    out.push_str(&section);
+                }
+            }
+        }
+        out
+    }
+}
@@ -0,0 +1,165 @@
+use crate::human::HumanSize;
+use crate::internal::Tree;
+use crate::markup;
+use crate::tree_config::TreeConfig;
+use crate::Level;
+
+const STYLE: &str = r#"
+body { font-family: sans-serif; margin: 1em; }
+#toolbar { position: sticky; top: 0; background: white; padding: 0.5em 0; display: flex; gap: 0.5em; }
+ul { list-style: none; margin: 0; padding-left: 1.5em; border-left: 1px solid #ccc; }
+body > ul { border-left: none; padding-left: 0; }
+summary, .leaf { font-family: monospace; white-space: pre-wrap; cursor: default; }
+summary { cursor: pointer; }
+.leaf { padding-left: 1.1em; }
+.hidden { display: none; }
+.match { background: #fff3a0; }
+.error > * > .text { color: #c00; }
+.warn > * > .text { color: #b60; }
+.debug > * > .text, .trace > * > .text { color: #777; }
+.annotation { color: #777; float: right; }
+.attachment { padding-left: 1.1em; }
+.attachment summary { color: #777; }
+.attachment pre { margin: 0.25em 0; padding: 0.5em; background: #f5f5f5; overflow: auto; }
+"#;
+
+const SCRIPT: &str = r#"
+const search = document.getElementById('search');
+const level = document.getElementById('level');
+const levels = ['error', 'warn', 'info', 'debug', 'trace'];
+function update() {
+    const query = search.value.toLowerCase();
+    const max = levels.indexOf(level.value);
+    function visit(node) {
+        let visible = false;
+        for (const child of node.querySelectorAll(':scope > details > ul > li, :scope > ul > li')) {
+            visible = visit(child) || visible;
+        }
+        const text = node.querySelector(':scope > details > summary > .text, :scope > .leaf > .text');
+        const matches = query !== '' && text.textContent.toLowerCase().includes(query);
+        text.classList.toggle('match', matches);
+        if (levels.indexOf(node.dataset.level) <= max && (query === '' || matches)) {
+            visible = true;
+        }
+        node.classList.toggle('hidden', !visible);
+        if (visible && query !== '' && node.firstElementChild.tagName === 'DETAILS') {
+            node.firstElementChild.open = true;
+        }
+        return visible;
+    }
+    document.querySelectorAll('body > ul > li').forEach(visit);
+}
+function expand(open) {
+    document.querySelectorAll('details').forEach(x => x.open = open);
+}
+search.addEventListener('input', update);
+level.addEventListener('change', update);
+document.getElementById('expand').addEventListener('click', () => expand(true));
+document.getElementById('collapse').addEventListener('click', () => expand(false));
+"#;
+
+/// Escapes `text` for use in HTML text and attribute values.
+fn escape(text: &str) -> String {
+    let mut ret = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => ret.push_str("&amp;"),
+            '<' => ret.push_str("&lt;"),
+            '>' => ret.push_str("&gt;"),
+            '"' => ret.push_str("&quot;"),
+            '\'' => ret.push_str("&#39;"),
+            c => ret.push(c),
+        }
+    }
+    ret
+}
+
+/// Escapes `text`, and renders its inline markup as tags if `config` enables markup.
+fn text(text: &str, config: &TreeConfig) -> String {
+    let text = escape(text);
+    match config.markup {
+        Some(_) => markup::render(&text, &("<b>", "</b>"), &("<code>", "</code>")),
+        None => text,
+    }
+}
+
+/// Renders the attachments of `tree` as collapsed blocks with their full content.
+fn attachments(tree: &Tree) -> String {
+    tree.attachments
+        .iter()
+        .map(|x| {
+            format!(
+                r#"<details class="attachment"><summary>{} ({})</summary><pre>{}</pre></details>"#,
+                escape(&x.name),
+                HumanSize(x.content.len() as u64),
+                escape(&x.content)
+            )
+        })
+        .collect()
+}
+
+fn node(tree: &Tree, config: &TreeConfig, out: &mut String) {
+    let text = format!(
+        r#"<span class="text">{}</span>{}"#,
+        text(tree.text.as_deref().unwrap_or(""), config),
+        match &tree.annotation {
+            Some(x) => format!(r#"<span class="annotation">{}</span>"#, escape(x)),
+            None => String::new(),
+        }
+    );
+    out.push_str(&format!(
+        r#"<li class="{0}" data-level="{0}">"#,
+        tree.level.as_str()
+    ));
+    if tree.children.is_empty() {
+        out.push_str(&format!(r#"<div class="leaf">{}</div>"#, text));
+        out.push_str(&attachments(tree));
+    } else {
+        out.push_str(&format!("<details open><summary>{}</summary>", text));
+        out.push_str(&attachments(tree));
+        children(tree, config, out);
+        out.push_str("</details>");
+    }
+    out.push_str("</li>\n");
+}
+
+fn children(tree: &Tree, config: &TreeConfig, out: &mut String) {
+    out.push_str("<ul>\n");
+    for x in &tree.children {
+        node(x, config, out);
+    }
+    out.push_str("</ul>");
+}
+
+/// Renders `tree` as a standalone HTML page, with collapsible branches,
+/// text search and filtering by level.
+pub(crate) fn render(tree: &Tree, config: &TreeConfig) -> String {
+    let view = tree.view(config);
+    let title = escape(tree.text.as_deref().unwrap_or("Debug Tree"));
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n",
+        title, STYLE
+    );
+    if tree.text.is_some() {
+        out.push_str(&format!("<h1>{}</h1>\n", title));
+    }
+    out.push_str("<div id=\"toolbar\">\n<input id=\"search\" type=\"search\" placeholder=\"Search\">\n<select id=\"level\">\n");
+    for level in Level::ALL.iter() {
+        out.push_str(&format!(
+            "<option value=\"{0}\"{1}>{0}</option>\n",
+            level,
+            if *level == Level::Trace {
+                " selected"
+            } else {
+                ""
+            }
+        ));
+    }
+    out.push_str("</select>\n<button id=\"expand\">Expand all</button>\n<button id=\"collapse\">Collapse all</button>\n</div>\n");
+    children(&view, config, &mut out);
+    out.push_str(&format!(
+        "\n<script>{}</script>\n</body>\n</html>\n",
+        SCRIPT
+    ));
+    out
+}
@@ -0,0 +1,80 @@
+use std::fmt;
+use std::time::Duration;
+
+/// Displays a [Duration] in a compact, human-readable form, with the unit chosen by magnitude:
+/// `850ns`, `12.50µs`, `1.24ms`, `4.05s`, `3m 02s` or `1h 05m 09s`.
+/// The unit is chosen after rounding, so a duration just under a unit is shown in that unit.
+///
+/// # Example
+///
+/// ```
+/// use debug_tree::human::HumanDuration;
+/// use std::time::Duration;
+/// assert_eq!("1.24ms", HumanDuration(Duration::from_micros(1240)).to_string());
+/// assert_eq!("3m 02s", HumanDuration(Duration::from_secs(182)).to_string());
+/// assert_eq!("999.99µs", HumanDuration(Duration::from_nanos(999_994)).to_string());
+/// assert_eq!("1.00ms", HumanDuration(Duration::from_nanos(999_995)).to_string());
+/// assert_eq!("1.00s", HumanDuration(Duration::from_nanos(999_999_999)).to_string());
+/// assert_eq!("1m 00s", HumanDuration(Duration::from_millis(59_999)).to_string());
+/// assert_eq!("1h 00m 00s", HumanDuration(Duration::from_millis(3_599_500)).to_string());
+/// ```
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct HumanDuration(pub Duration);
+
+impl fmt::Display for HumanDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let nanos = self.0.as_nanos();
+        // Each bound is the smallest value that rounds up to the next unit
+        let secs = (nanos + 500_000_000) / 1_000_000_000;
+        if nanos < 1_000 {
+            write!(f, "{}ns", nanos)
+        } else if nanos < 999_995 {
+            write!(f, "{:.2}µs", nanos as f64 / 1e3)
+        } else if nanos < 999_995_000 {
+            write!(f, "{:.2}ms", nanos as f64 / 1e6)
+        } else if nanos < 59_995_000_000 {
+            write!(f, "{:.2}s", self.0.as_secs_f64())
+        } else if secs < 3600 {
+            write!(f, "{}m {:02}s", secs / 60, secs % 60)
+        } else {
+            write!(
+                f,
+                "{}h {:02}m {:02}s",
+                secs / 3600,
+                secs / 60 % 60,
+                secs % 60
+            )
+        }
+    }
+}
+
+/// Displays a number of bytes with a binary unit: `512 B`, `4.2 KiB` or `4.7 MiB`.
+/// The unit is chosen after rounding, so a size just under a unit is shown in that unit.
+///
+/// # Example
+///
+/// ```
+/// use debug_tree::human::HumanSize;
+/// assert_eq!("512 B", HumanSize(512).to_string());
+/// assert_eq!("4.7 MiB", HumanSize(4_928_307).to_string());
+/// assert_eq!("1023.9 KiB", HumanSize(1_048_524).to_string());
+/// assert_eq!("1.0 MiB", HumanSize(1_048_575).to_string());
+/// ```
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct HumanSize(pub u64);
+
+impl fmt::Display for HumanSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+        if self.0 < 1024 {
+            return write!(f, "{} B", self.0);
+        }
+        let mut size = self.0 as f64 / 1024.0;
+        let mut unit = 0;
+        while (size * 10.0).round() >= 10240.0 && unit + 1 < UNITS.len() {
+            size /= 1024.0;
+            unit += 1;
+        }
+        write!(f, "{:.1} {}", size, UNITS[unit])
+    }
+}
@@ -1,221 +1,2314 @@
-use crate::tree_config::{tree_config, TreeConfig};
+use crate::event::TreeEvent;
+use crate::filter::module_matches;
+use crate::human::HumanSize;
+use crate::level::Level;
+use crate::sync::Lock;
+use crate::theme::{self, Color, Theme};
+use crate::tree_config::{
+    tree_config, ChildCount, EmptyNodePolicy, NewestFirst, NodeTextFn, Overflow, TreeConfig,
+};
+use once_cell::sync::Lazy;
+use std::any::Any;
+use std::borrow::Cow;
 use std::cmp::max;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 /// Tree that holds `text` for the current leaf and a list of `children` that are the branches.
-#[derive(Debug)]
+#[derive(Clone)]
 pub struct Tree {
-    pub text: Option<String>,
+    /// The text of the node. Text from string literals is borrowed rather than copied.
+    pub text: Option<Cow<'static, str>>,
     pub children: Vec<Tree>,
+    /// The subsystem that added this node, usually its module path.
+    pub target: Option<&'static str>,
+    /// Text rendered right-aligned in a gutter at the end of this node's first line.
+    pub annotation: Option<String>,
+    /// The level this node was added at.
+    pub level: Level,
+    /// Payloads that are only included in the JSON and HTML exports.
+    pub attachments: Vec<Attachment>,
+    metadata: Vec<Arc<dyn Any + Send + Sync>>,
+}
+
+impl Drop for Tree {
+    /// Drops the descendants one at a time, so that deeply nested trees can't overflow the stack.
+    fn drop(&mut self) {
+        let mut pending = std::mem::take(&mut self.children);
+        while let Some(mut x) = pending.pop() {
+            pending.append(&mut x.children);
+        }
+    }
+}
+
+/// Compares the text and children of trees, recursively. Levels, targets, annotations,
+/// attachments and metadata are not compared.
+///
+/// # Example
+///
+/// ```
+/// use debug_tree::Tree;
+/// let a = Tree::from_depths(vec![(0, "a"), (1, "b")]).unwrap();
+/// let b = Tree::from_depths(vec![(0, "a"), (1, "b")]).unwrap();
+/// assert_eq!(a, b);
+/// assert_ne!(a, Tree::from_depths(vec![(0, "a")]).unwrap());
+/// ```
+impl PartialEq for Tree {
+    fn eq(&self, other: &Self) -> bool {
+        self.eq_by(other, |a, b| a.text == b.text)
+    }
+}
+
+impl Eq for Tree {}
+
+impl fmt::Debug for Tree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tree")
+            .field("text", &self.text)
+            .field("children", &self.children)
+            .field("target", &self.target)
+            .field("annotation", &self.annotation)
+            .field("level", &self.level)
+            .field("attachments", &self.attachments)
+            .field("metadata", &self.metadata.len())
+            .finish()
+    }
 }
 
+/// A named payload attached to a node, such as a request body or a large dump.
+/// Terminal output only shows its size; the JSON and HTML exports include its content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    pub name: String,
+    pub content: String,
+}
+
+/// Formats `n` with commas between groups of three digits, e.g. `8,401`.
+pub(crate) fn thousands(n: usize) -> String {
+    let digits = n.to_string();
+    let mut ret = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            ret.push(',');
+        }
+        ret.push(c);
+    }
+    ret
+}
+
+/// When, and on which thread, a top-level node was added. Attached to the node as metadata.
+#[derive(Debug, Clone)]
+pub(crate) struct Origin {
+    time: Instant,
+    thread: std::thread::Thread,
+}
+
+impl Origin {
+    fn label(&self) -> String {
+        match self.thread.name() {
+            Some(x) => x.to_string(),
+            None => format!("{:?}", self.thread.id()),
+        }
+    }
+}
+
+/// When a node was added and, for a branch, when it was exited.
+/// Recorded as metadata on each node added while [timing](crate::TreeBuilder::set_timing) is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timing {
+    pub start: Instant,
+    /// When the branch was exited, or `None` for a leaf or a branch that is still current.
+    pub end: Option<Instant>,
+}
+
+impl Timing {
+    /// The time from `start` to `end`, if the branch has been exited.
+    pub fn duration(&self) -> Option<Duration> {
+        self.end.map(|x| x.saturating_duration_since(self.start))
+    }
+}
+
+/// The time spent inside debug-tree calls while the cursor was directly inside a branch.
+/// Recorded as metadata on each branch while
+/// [overhead tracking](crate::TreeBuilder::set_overhead_tracking) is on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Overhead(pub Duration);
+
 /// Position of the element relative to its siblings
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
-pub enum Position {
+pub(crate) enum Position {
     Inside,
     First,
     Last,
     Only,
 }
 
+/// Apply the text normalization options of `config` to the text of a node.
+fn sanitize(text: &str, config: &TreeConfig) -> String {
+    let mut text = match config.normalize_newlines {
+        true => text.replace("\r\n", "\n"),
+        false => text.to_string(),
+    };
+    #[cfg(feature = "regex")]
+    for (pattern, replacement) in &config.redactions {
+        text = pattern
+            .replace_all(&text, replacement.as_str())
+            .into_owned();
+    }
+    if config.trim_trailing_whitespace {
+        text = text
+            .split('\n')
+            .map(str::trim_end)
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+    if let Some(x) = config.control_chars {
+        text = x.apply(&text);
+    }
+    if config.flatten_newlines {
+        text = text.replace('\n', "⏎");
+    }
+    text
+}
+
 impl Tree {
     /// Create a new tree with some optional text.
     pub fn new(text: Option<&str>) -> Tree {
         Tree {
-            text: text.map(|x| x.to_string()),
+            text: text.map(|x| Cow::Owned(x.to_string())),
             children: Vec::new(),
+            target: None,
+            annotation: None,
+            level: Level::Info,
+            attachments: Vec::new(),
+            metadata: Vec::new(),
         }
     }
 
+    /// Create a tree from the text of each node and its depth, in depth-first order.
+    /// Top-level nodes have a depth of 0.
+    ///
+    /// Returns an error if a node is more than one level deeper than the node before it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::{Tree, TreeBuilder};
+    /// let tree = Tree::from_depths(vec![(0, "1"), (1, "1.1"), (2, "1.1.1"), (0, "2")]).unwrap();
+    /// assert_eq!("\
+    /// 1
+    /// └╼ 1.1
+    ///   └╼ 1.1.1
+    /// 2", TreeBuilder::from(tree).peek_string());
+    /// assert!(Tree::from_depths(vec![(0, "1"), (2, "1.1.1")]).is_err());
+    /// ```
+    pub fn from_depths<I, S>(nodes: I) -> Result<Tree, String>
+    where
+        I: IntoIterator<Item = (usize, S)>,
+        S: Into<String>,
+    {
+        let mut tree = Tree::new(None);
+        let mut path: Vec<usize> = vec![];
+        for (i, (depth, text)) in nodes.into_iter().enumerate() {
+            if depth > path.len() {
+                return Err(match path.len() {
+                    0 => format!(
+                        "node {}: depth {} is not a top-level depth of 0",
+                        i + 1,
+                        depth
+                    ),
+                    n => format!(
+                        "node {}: depth {} is more than one level deeper than {}",
+                        i + 1,
+                        depth,
+                        n - 1
+                    ),
+                });
+            }
+            path.truncate(depth);
+            let parent = tree.at_mut(&path).unwrap();
+            parent.children.push(Tree::new(Some(&text.into())));
+            path.push(parent.children.len() - 1);
+        }
+        Ok(tree)
+    }
+
+    /// Create a tree from text indented by `spaces_per_level` spaces for each level,
+    /// such as an outline or a traceback. A tab counts as one level, and blank lines are skipped.
+    /// Indentation that is not a whole number of levels is rounded down.
+    ///
+    /// Returns an error if a line is more than one level deeper than the line before it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::{Tree, TreeBuilder};
+    /// let text = "\
+    /// Traceback (most recent call last):
+    ///   File \"main.py\", line 3
+    ///     run()
+    /// ValueError: bad input";
+    /// let tree = Tree::from_indented_str(text, 2).unwrap();
+    /// assert_eq!("\
+    /// Traceback (most recent call last):
+    /// └╼ File \"main.py\", line 3
+    ///   └╼ run()
+    /// ValueError: bad input", TreeBuilder::from(tree).peek_string());
+    /// ```
+    pub fn from_indented_str(s: &str, spaces_per_level: usize) -> Result<Tree, String> {
+        let spaces_per_level = max(spaces_per_level, 1);
+        let mut nodes = vec![];
+        let mut previous: Option<usize> = None;
+        for (i, line) in s.lines().enumerate() {
+            let text = line.trim_start();
+            if text.is_empty() {
+                continue;
+            }
+            let indent = &line[..line.len() - text.len()];
+            let depth = indent.chars().filter(|&x| x == '\t').count()
+                + indent.chars().filter(|&x| x == ' ').count() / spaces_per_level;
+            if depth > previous.map(|x| x + 1).unwrap_or(0) {
+                return Err(format!(
+                    "line {}: indented more than one level deeper than the line before it",
+                    i + 1
+                ));
+            }
+            previous = Some(depth);
+            nodes.push((depth, text.trim_end()));
+        }
+        Tree::from_depths(nodes)
+    }
+
+    /// Returns whether this tree has the same text and children as `other`,
+    /// regardless of the order of siblings.
+    /// Only the text of the nodes is compared.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::Tree;
+    /// let a = Tree::from_indented_str("1\n  1.1\n  1.2\n2", 2).unwrap();
+    /// let b = Tree::from_indented_str("2\n1\n  1.2\n  1.1", 2).unwrap();
+    /// assert!(a.eq_unordered(&b));
+    /// ```
+    pub fn eq_unordered(&self, other: &Tree) -> bool {
+        self.canonical() == other.canonical()
+    }
+
+    /// Returns whether the nodes beneath this tree match the nodes beneath `pattern`, in order.
+    ///
+    /// A `*` in the text of a pattern node matches any text, so a node with the text `*` matches
+    /// a node with any text. A pattern node with the text `**` matches any number of sibling
+    /// nodes, including none, along with their descendants.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::Tree;
+    /// let tree = Tree::from_indented_str("Load\n  Took 12ms\n  Opened 0x7ff3\n    Read\nDone", 2).unwrap();
+    /// let pattern = Tree::from_indented_str("Load\n  Took *ms\n  **\n*", 2).unwrap();
+    /// assert!(tree.matches(&pattern));
+    /// ```
+    pub fn matches(&self, pattern: &Tree) -> bool {
+        matches_in_order(&self.children, &pattern.children)
+    }
+
+    /// Returns whether the nodes beneath this tree match the nodes beneath `pattern`,
+    /// regardless of the order of siblings. See [matches](Tree::matches) for the wildcards.
+    pub fn matches_unordered(&self, pattern: &Tree) -> bool {
+        matches_any_order(&self.children, &pattern.children)
+    }
+
+    /// Returns the path and node of each node beneath this tree that matches `pattern`,
+    /// in depth-first order.
+    ///
+    /// The pattern is a list of segments separated by `/`, each matching the text of one level
+    /// of nodes starting from the children of this tree. A `*` in a segment matches any text,
+    /// and a segment of `**` matches any number of levels, including none.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::Tree;
+    /// let tree = Tree::from_indented_str(
+    ///     "main\n  parse_expr\n    syntax error\n    ok\n  eval\n    parse_expr\n      type error",
+    ///     2,
+    /// )
+    /// .unwrap();
+    /// let errors: Vec<_> = tree.query("**/parse_expr/*error*").into_iter().map(|(path, _)| path).collect();
+    /// assert_eq!(vec![vec![0, 0, 0], vec![0, 1, 0, 0]], errors);
+    /// assert_eq!(Some("eval"), tree.query("main/e*")[0].1.text.as_deref());
+    /// ```
+    pub fn query(&self, pattern: &str) -> Vec<(Vec<usize>, &Tree)> {
+        fn visit<'t>(
+            tree: &'t Tree,
+            segments: &[&str],
+            path: &mut Vec<usize>,
+            out: &mut Vec<(Vec<usize>, &'t Tree)>,
+        ) {
+            let (segment, rest) = match segments.split_first() {
+                Some(x) => x,
+                None => return,
+            };
+            for (i, x) in tree.children.iter().enumerate() {
+                path.push(i);
+                if *segment == "**" {
+                    if rest.is_empty() {
+                        out.push((path.clone(), x));
+                    }
+                    visit(x, segments, path, out);
+                } else if glob(segment, x.text.as_deref().unwrap_or("")) {
+                    if rest.is_empty() {
+                        out.push((path.clone(), x));
+                    }
+                    visit(x, rest, path, out);
+                }
+                path.pop();
+            }
+            if *segment == "**" {
+                visit(tree, rest, path, out);
+            }
+        }
+        let segments: Vec<&str> = pattern.split('/').collect();
+        let mut out = vec![];
+        visit(self, &segments, &mut vec![], &mut out);
+        out.sort_by(|a, b| a.0.cmp(&b.0));
+        out.dedup_by(|a, b| a.0 == b.0);
+        out
+    }
+
+    /// A representation of the text and children of this tree, with siblings in sorted order.
+    fn canonical(&self) -> String {
+        let mut children: Vec<String> = self.children.iter().map(Tree::canonical).collect();
+        children.sort();
+        format!("{:?}[{}]", self.text, children.join(","))
+    }
+
+    /// Returns the metadata of type `T` attached to this tree, if any.
+    pub fn metadata<T: Any + Send + Sync>(&self) -> Option<&T> {
+        self.metadata.iter().find_map(|x| x.downcast_ref::<T>())
+    }
+
+    /// Attaches `value` to this tree, replacing any existing metadata of the same type.
+    /// Metadata is not rendered, but is available when visiting the tree.
+    pub fn set_metadata<T: Any + Send + Sync>(&mut self, value: T) {
+        self.metadata.retain(|x| !x.is::<T>());
+        self.metadata.push(Arc::new(value));
+    }
+
+    /// Calls `f` with each node beneath this tree and its depth, in depth-first order.
+    /// The children of this tree have a depth of 0.
+    pub fn visit<F: FnMut(&Tree, usize)>(&self, mut f: F) {
+        fn visit_inner<F: FnMut(&Tree, usize)>(tree: &Tree, depth: usize, f: &mut F) {
+            for x in &tree.children {
+                f(x, depth);
+                visit_inner(x, depth + 1, f);
+            }
+        }
+        visit_inner(self, 0, &mut f);
+    }
+
+    /// Replaces the text of this tree and each of its descendants with the result of `f`,
+    /// for bulk rewriting such as stripping prefixes or translating labels.
+    /// Nodes without text are left unchanged. To keep the original, map a clone.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::{Tree, TreeBuilder};
+    /// let mut tree = Tree::from_depths(vec![(0, "app: Start"), (1, "app: Load")]).unwrap();
+    /// tree.map_text(|x| x.trim_start_matches("app: ").to_string());
+    /// assert_eq!("Start\n└╼ Load", TreeBuilder::from(tree).peek_string());
+    /// ```
+    pub fn map_text<F: FnMut(&str) -> String>(&mut self, mut f: F) {
+        fn map_inner<F: FnMut(&str) -> String>(tree: &mut Tree, f: &mut F) {
+            if let Some(text) = &mut tree.text {
+                *text = Cow::Owned(f(text));
+            }
+            for x in tree.children.iter_mut() {
+                map_inner(x, f);
+            }
+        }
+        map_inner(self, &mut f);
+    }
+
+    /// Calls `f` with each node beneath this tree and its ancestors, in depth-first order.
+    /// The ancestors start with a child of this tree and end with the parent of the node,
+    /// so the children of this tree have no ancestors.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::Tree;
+    /// let tree = Tree::from_depths(vec![(0, "main"), (1, "load"), (2, "error")]).unwrap();
+    /// let mut chains = vec![];
+    /// tree.visit_with_ancestors(|x, ancestors| {
+    ///     if x.text.as_deref() == Some("error") {
+    ///         chains.push(ancestors.iter().filter_map(|x| x.text.as_deref()).collect::<Vec<_>>());
+    ///     }
+    /// });
+    /// assert_eq!(vec![vec!["main", "load"]], chains);
+    /// ```
+    pub fn visit_with_ancestors<'t, F: FnMut(&'t Tree, &[&'t Tree])>(&'t self, mut f: F) {
+        fn visit_inner<'t, F: FnMut(&'t Tree, &[&'t Tree])>(
+            tree: &'t Tree,
+            ancestors: &mut Vec<&'t Tree>,
+            f: &mut F,
+        ) {
+            for x in &tree.children {
+                f(x, ancestors);
+                ancestors.push(x);
+                visit_inner(x, ancestors, f);
+                ancestors.pop();
+            }
+        }
+        visit_inner(self, &mut vec![], &mut f);
+    }
+
+    /// Iterates over each node beneath this tree and its depth, in breadth-first order:
+    /// all the nodes at one depth before any at the next.
+    /// The children of this tree have a depth of 0. See [visit](Tree::visit) for depth-first order.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::Tree;
+    /// let tree = Tree::from_depths(vec![(0, "a"), (1, "a.a"), (0, "b"), (1, "b.a")]).unwrap();
+    /// let level_1: Vec<_> = tree
+    ///     .breadth_first()
+    ///     .filter(|(_, depth)| *depth == 1)
+    ///     .filter_map(|(x, _)| x.text.as_deref())
+    ///     .collect();
+    /// assert_eq!(vec!["a.a", "b.a"], level_1);
+    /// ```
+    pub fn breadth_first(&self) -> impl Iterator<Item = (&Tree, usize)> {
+        let mut queue: VecDeque<(&Tree, usize)> = self.children.iter().map(|x| (x, 0)).collect();
+        std::iter::from_fn(move || {
+            let (node, depth) = queue.pop_front()?;
+            queue.extend(node.children.iter().map(|x| (x, depth + 1)));
+            Some((node, depth))
+        })
+    }
+
     /// Navigate to the branch at the given `path` relative to this tree.
     /// If a valid branch is found by following the path, it is returned.
-    pub fn at_mut(&mut self, path: &[usize]) -> Option<&mut Tree> {
-        match path.first() {
-            Some(&i) => match self.children.get_mut(i) {
-                Some(x) => x.at_mut(&path[1..]),
-                _ => None,
-            },
-            _ => Some(self),
+    /// The path is a slice of child indices, or a string of indices separated by `/`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::{Tree, TreeBuilder};
+    /// let tree = Tree::from_depths(vec![(0, "a"), (1, "a.a"), (1, "a.b"), (2, "a.b.a")]).unwrap();
+    /// assert_eq!(Some("a.b.a"), tree.at("0/1/0").and_then(|x| x.text.as_deref()));
+    /// assert_eq!(Some("a.b"), tree.at(&[0, 1]).and_then(|x| x.text.as_deref()));
+    /// assert!(tree.at("0/x").is_none());
+    ///
+    /// let builder = TreeBuilder::new();
+    /// let _a = builder.add_branch("a");
+    /// let id = builder.add_leaf_id("a.a").unwrap();
+    /// assert_eq!("0/0", id.to_string());
+    /// assert_eq!(Some("a.a"), builder.snapshot().at(&id.to_string()).and_then(|x| x.text.as_deref()));
+    /// ```
+    pub fn at<P: TreePath + ?Sized>(&self, path: &P) -> Option<&Tree> {
+        fn at_inner<'t>(tree: &'t Tree, path: &[usize]) -> Option<&'t Tree> {
+            match path.first() {
+                Some(&i) => tree.children.get(i).and_then(|x| at_inner(x, &path[1..])),
+                _ => Some(tree),
+            }
         }
+        at_inner(self, &path.indices()?)
     }
 
-    /// "Render" this tree as a list of `String`s.
-    /// Each string represents a line in the tree.
-    /// `does_continue` is a bool for each column indicating whether the tree continues.
-    pub fn lines(
-        &self,
-        does_continue: &Vec<bool>,
-        index: usize,
-        pool_size: usize,
-        config: &TreeConfig,
-    ) -> Vec<String> {
-        let does_continue = if config.show_first_level && does_continue.is_empty() {
-            vec![true]
-        } else {
-            does_continue.clone()
-        };
-        let position = match index {
-            _ if pool_size == 1 => Position::Only,
-            _ if (index + 1) == pool_size => Position::Last,
-            0 => Position::First,
-            _ => Position::Inside,
+    /// Navigate to the branch at the given `path` relative to this tree.
+    /// If a valid branch is found by following the path, it is returned.
+    /// See [at](Tree::at).
+    pub fn at_mut<P: TreePath + ?Sized>(&mut self, path: &P) -> Option<&mut Tree> {
+        fn at_inner<'t>(tree: &'t mut Tree, path: &[usize]) -> Option<&'t mut Tree> {
+            match path.first() {
+                Some(&i) => match tree.children.get_mut(i) {
+                    Some(x) => at_inner(x, &path[1..]),
+                    _ => None,
+                },
+                _ => Some(tree),
+            }
+        }
+        at_inner(self, &path.indices()?)
+    }
+
+    /// Returns the parent of the node at `path`, or `None` if there is no such node or it is a
+    /// child of this tree.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::Tree;
+    /// let tree = Tree::from_depths(vec![(0, "a"), (1, "a.a"), (2, "a.a.a")]).unwrap();
+    /// assert_eq!(Some("a.a"), tree.parent("0/0/0").and_then(|x| x.text.as_deref()));
+    /// assert!(tree.parent("0").is_none());
+    /// ```
+    pub fn parent<P: TreePath + ?Sized>(&self, path: &P) -> Option<&Tree> {
+        let path = path.indices()?;
+        match path.split_last() {
+            Some((_, parent)) if !parent.is_empty() && self.at(&path[..]).is_some() => {
+                self.at(parent)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the ancestors of the node at `path`, from a child of this tree to the parent of the
+    /// node, or an empty list if there is no such node.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::Tree;
+    /// let tree = Tree::from_depths(vec![(0, "a"), (1, "a.a"), (2, "a.a.a")]).unwrap();
+    /// let chain: Vec<_> = tree.ancestors("0/0/0").iter().filter_map(|x| x.text.as_deref()).collect();
+    /// assert_eq!(vec!["a", "a.a"], chain);
+    /// ```
+    pub fn ancestors<P: TreePath + ?Sized>(&self, path: &P) -> Vec<&Tree> {
+        let path = match path.indices() {
+            Some(x) if self.at(&x[..]).is_some() => x,
+            _ => return vec![],
         };
-        let mut next_continue = does_continue.clone();
-        next_continue.push(match position {
-            Position::Inside | Position::First => true,
-            Position::Last | Position::Only => false,
-        });
+        let mut ret = Vec::with_capacity(path.len().saturating_sub(1));
+        let mut node = self;
+        for &i in path.iter().take(path.len().saturating_sub(1)) {
+            node = &node.children[i];
+            ret.push(node);
+        }
+        ret
+    }
 
-        let mut txt = String::new();
-        let pad: String;
-        if does_continue.len() > 1 {
-            for &i in &does_continue[2..] {
-                txt.push_str(&format!(
-                    "{}{:indent$}",
-                    if i { config.symbols.continued } else { " " },
-                    "",
-                    indent = max(config.indent, 1) - 1
-                ));
+    /// Returns a copy of this tree with only the nodes for which `keep` returns true,
+    /// including their descendants, and the ancestors of those nodes.
+    pub fn filtered<F: Fn(&Tree) -> bool>(&self, keep: &F) -> Tree {
+        let mut ret = self.without_children();
+        ret.children = self
+            .children
+            .iter()
+            .filter_map(|x| {
+                if keep(x) {
+                    Some(x.clone())
+                } else {
+                    let x = x.filtered(keep);
+                    if x.children.is_empty() {
+                        None
+                    } else {
+                        Some(x)
+                    }
+                }
+            })
+            .collect();
+        ret
+    }
+
+    /// Permanently removes the nodes beneath this tree for which `keep` returns false, along with
+    /// their descendants, so that their memory is freed.
+    /// Unlike [filtered](Tree::filtered), nodes are visited from the top down, and the descendants
+    /// of a removed node are not visited.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::{Tree, TreeBuilder};
+    /// let mut tree = Tree::from_depths(vec![(0, "a"), (1, "cache"), (2, "hit"), (0, "b")]).unwrap();
+    /// tree.retain(|x| x.text.as_deref() != Some("cache"));
+    /// assert_eq!("a\nb", TreeBuilder::from(tree).peek_string());
+    /// ```
+    pub fn retain<F: FnMut(&Tree) -> bool>(&mut self, mut keep: F) {
+        self.retain_except_path(&mut [], false, &mut keep);
+    }
+
+    /// Permanently removes the nodes beneath this tree for which `keep` returns false,
+    /// moving their children up to take their place.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::{Tree, TreeBuilder};
+    /// let mut tree = Tree::from_depths(vec![(0, "a"), (1, "cache"), (2, "hit"), (0, "b")]).unwrap();
+    /// tree.retain_nodes(|x| x.text.as_deref() != Some("cache"));
+    /// assert_eq!("a\n└╼ hit\nb", TreeBuilder::from(tree).peek_string());
+    /// ```
+    pub fn retain_nodes<F: FnMut(&Tree) -> bool>(&mut self, mut keep: F) {
+        self.retain_except_path(&mut [], true, &mut keep);
+    }
+
+    /// Removes the nodes beneath this tree for which `keep` returns false. If `hoist` is true,
+    /// their children take their place, otherwise they are removed with their descendants.
+    /// Nodes on `path` are always kept, and `path` is updated to their new indices.
+    fn retain_except_path<F: FnMut(&Tree) -> bool>(
+        &mut self,
+        path: &mut [usize],
+        hoist: bool,
+        keep: &mut F,
+    ) {
+        let cursor = path.first().copied();
+        let children = std::mem::take(&mut self.children);
+        for (i, mut x) in children.into_iter().enumerate() {
+            if Some(i) == cursor {
+                path[0] = self.children.len();
+                x.retain_except_path(&mut path[1..], hoist, keep);
+            } else if keep(&x) {
+                x.retain_except_path(&mut [], hoist, keep);
+            } else if hoist {
+                x.retain_except_path(&mut [], hoist, keep);
+                self.children.append(&mut x.children);
+                continue;
+            } else {
+                continue;
             }
-            pad = txt.clone();
-            let branch_size = max(config.indent, 2usize) - 2;
-            let branch = match config.symbols.branch.len() {
-                0 => "-".repeat(branch_size),
-                1 => config.symbols.branch.repeat(branch_size),
-                _n => config
-                    .symbols
-                    .branch
-                    .repeat(branch_size)
-                    .chars()
-                    .take(branch_size)
-                    .collect::<String>(),
-            };
+            self.children.push(x);
+        }
+        self.children.shrink_to_fit();
+    }
+
+    /// A copy of this tree without its children.
+    fn without_children(&self) -> Tree {
+        Tree {
+            text: self.text.clone(),
+            children: Vec::new(),
+            target: self.target,
+            annotation: self.annotation.clone(),
+            level: self.level,
+            attachments: self.attachments.clone(),
+            metadata: self.metadata.clone(),
+        }
+    }
+
+    /// The tree as it should be displayed according to `config`.
+    pub(crate) fn view(&self, config: &TreeConfig) -> Cow<'_, Tree> {
+        let mut view = Cow::Borrowed(self);
+        if let Some(node_text) = &config.node_text {
+            view.to_mut().format_nodes(&*node_text.0);
+        }
+        if config.thread_timeline {
+            view.to_mut().interleave_threads();
+        }
+        match config.empty_nodes {
+            EmptyNodePolicy::Blank => {}
+            EmptyNodePolicy::Placeholder(placeholder) => view.to_mut().fill_empty(placeholder),
+            EmptyNodePolicy::Skip => view.to_mut().retain_nodes(|x| x.text.is_some()),
+        }
+        if config.outline_numbers {
+            view.to_mut().number_outline("");
+        }
+        match config.newest_first {
+            Some(NewestFirst::TopLevel) => view.to_mut().children.reverse(),
+            Some(NewestFirst::All) => view.to_mut().reverse_all(),
+            None => {}
+        }
+        if !config.only_targets.is_empty() {
+            view = Cow::Owned(view.filtered(&|x: &Tree| {
+                match x.target {
+                    Some(target) => config
+                        .only_targets
+                        .iter()
+                        .any(|prefix| module_matches(prefix, target)),
+                    None => false,
+                }
+            }));
+        }
+        if config.errors_only {
+            view = Cow::Owned(view.filtered(&|x: &Tree| x.level == Level::Error));
+        }
+        if let Some(separator) = config.collapse_chains {
+            for x in view.to_mut().children.iter_mut() {
+                x.collapse_chains(separator);
+            }
+        }
+        if config.align_columns {
+            view.to_mut().align_columns();
+        }
+        #[cfg(feature = "regex")]
+        if !config.stable_ids.is_empty() {
+            view.to_mut()
+                .remap_ids(&config.stable_ids, &mut std::collections::HashMap::new());
+        }
+        view
+    }
+
+    /// Reverses the order of the children of this tree and of each of its descendants.
+    fn reverse_all(&mut self) {
+        self.children.reverse();
+        for x in self.children.iter_mut() {
+            x.reverse_all();
+        }
+    }
+
+    /// Sets the text of each descendant of this tree to the result of `f` for it,
+    /// before its children are formatted.
+    fn format_nodes(&mut self, f: &NodeTextFn) {
+        for x in self.children.iter_mut() {
+            x.text = Some(Cow::Owned(f(x)));
+            x.format_nodes(f);
+        }
+    }
+
+    /// Sets the text of each descendant of this tree that has no text to `placeholder`.
+    fn fill_empty(&mut self, placeholder: &'static str) {
+        for x in self.children.iter_mut() {
+            x.text.get_or_insert(Cow::Borrowed(placeholder));
+            x.fill_empty(placeholder);
+        }
+    }
+
+    /// Prefixes the text of each descendant of this tree with its hierarchical index,
+    /// after `prefix`, the index of this tree.
+    fn number_outline(&mut self, prefix: &str) {
+        for (i, x) in self.children.iter_mut().enumerate() {
+            let number = format!("{}{}.", prefix, i + 1);
+            x.text = Some(match x.text.as_deref() {
+                Some(text) => format!("{} {}", number, text).into(),
+                None => number.clone().into(),
+            });
+            x.number_outline(&number);
+        }
+    }
+
+    /// Sorts the children of this tree by the time they were added, if they all have an [Origin],
+    /// and prefixes the text of each child that has one with the name of its thread.
+    fn interleave_threads(&mut self) {
+        if self
+            .children
+            .iter()
+            .all(|x| x.metadata::<Origin>().is_some())
+        {
+            self.children
+                .sort_by_key(|x| x.metadata::<Origin>().map(|x| x.time));
+        }
+        for x in self.children.iter_mut() {
+            if let Some(label) = x.metadata::<Origin>().map(Origin::label) {
+                x.text = Some(format!("[{}] {}", label, x.text.as_deref().unwrap_or("")).into());
+            }
+        }
+    }
+
+    /// Replaces each distinct match of `patterns` in the text of this tree and its descendants
+    /// with `#1`, `#2`, ..., in depth-first order. `ids` holds the ids of the values seen so far.
+    #[cfg(feature = "regex")]
+    fn remap_ids(
+        &mut self,
+        patterns: &[regex::Regex],
+        ids: &mut std::collections::HashMap<String, usize>,
+    ) {
+        if let Some(text) = &mut self.text {
+            for pattern in patterns {
+                *text = Cow::Owned(
+                    pattern
+                        .replace_all(text, |x: &regex::Captures| {
+                            let n = ids.len() + 1;
+                            format!("#{}", ids.entry(x[0].to_string()).or_insert(n))
+                        })
+                        .into_owned(),
+                );
+            }
+        }
+        for x in self.children.iter_mut() {
+            x.remap_ids(patterns, ids);
+        }
+    }
+
+    /// Merges each chain of nodes with a single child, starting from this node, into its first
+    /// node, joining their text with `separator`.
+    fn collapse_chains(&mut self, separator: &str) {
+        while self.children.len() == 1 {
+            let mut child = self.children.pop().unwrap();
+            self.text = Some(
+                format!(
+                    "{}{}{}",
+                    self.text.as_deref().unwrap_or(""),
+                    separator,
+                    child.text.as_deref().unwrap_or("")
+                )
+                .into(),
+            );
+            self.annotation = child.annotation.take().or_else(|| self.annotation.take());
+            self.level = self.level.min(child.level);
+            self.children = std::mem::take(&mut child.children);
+        }
+        for x in self.children.iter_mut() {
+            x.collapse_chains(separator);
+        }
+    }
 
-            let is_multiline = self
-                .text
+    /// Replaces the tabs in the text of each group of siblings with padding,
+    /// so that the tab-separated columns line up.
+    /// Text without tabs, or with multiple lines, is left unchanged.
+    fn align_columns(&mut self) {
+        let is_row = |x: &Tree| {
+            x.text
                 .as_ref()
-                .map(|x| x.contains("\n"))
-                .unwrap_or(false);
+                .map(|x| x.contains('\t') && !x.contains('\n'))
+                .unwrap_or(false)
+        };
+        let mut widths: Vec<usize> = vec![];
+        for x in self.children.iter().filter(|x| is_row(x)) {
+            let cells: Vec<&str> = x.text.as_ref().unwrap().split('\t').collect();
+            for (i, cell) in cells[..cells.len() - 1].iter().enumerate() {
+                let width = cell.chars().count();
+                match widths.get_mut(i) {
+                    Some(x) => *x = max(*x, width),
+                    None => widths.push(width),
+                }
+            }
+        }
+        for x in self.children.iter_mut() {
+            if is_row(x) {
+                let text = x.text.take().unwrap();
+                let cells: Vec<&str> = text.split('\t').collect();
+                let mut row = String::new();
+                for (cell, width) in cells[..cells.len() - 1].iter().zip(&widths) {
+                    row.push_str(&format!("{:<width$}  ", cell, width = width));
+                }
+                row.push_str(cells[cells.len() - 1]);
+                x.text = Some(row.into());
+            }
+            x.align_columns();
+        }
+    }
 
-            let first_leaf = match (is_multiline, config.symbols.multiline_first) {
-                (true, Some(x)) => x,
-                _ => config.symbols.leaf,
+    /// Compares the structure of this tree with `other` like `==`, but compares the text of the
+    /// nodes after applying `normalize` to it, to ignore details such as timings or addresses.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::Tree;
+    /// let a = Tree::from_depths(vec![(0, "Request"), (1, "took 12ms")]).unwrap();
+    /// let b = Tree::from_depths(vec![(0, "Request"), (1, "took 15ms")]).unwrap();
+    /// let digits = |x: &str| x.replace(|c: char| c.is_ascii_digit(), "#");
+    /// assert_ne!(a, b);
+    /// assert!(a.eq_ignoring(&b, digits));
+    /// ```
+    pub fn eq_ignoring<F: FnMut(&str) -> String>(&self, other: &Tree, mut normalize: F) -> bool {
+        self.eq_by(other, |a, b| match (a.text.as_deref(), b.text.as_deref()) {
+            (Some(a), Some(b)) => normalize(a) == normalize(b),
+            (a, b) => a == b,
+        })
+    }
+
+    /// Returns whether this tree and `other` have the same shape, and `eq` returns true for each
+    /// pair of corresponding nodes. Nodes are compared one at a time, so that deeply nested trees
+    /// can't overflow the stack.
+    fn eq_by<F: FnMut(&Tree, &Tree) -> bool>(&self, other: &Tree, mut eq: F) -> bool {
+        let mut pending = vec![(self, other)];
+        while let Some((a, b)) = pending.pop() {
+            if a.children.len() != b.children.len() || !eq(a, b) {
+                return false;
+            }
+            pending.extend(a.children.iter().zip(&b.children));
+        }
+        true
+    }
+
+    /// Returns a hash of the text and structure of this tree and its descendants, which is
+    /// stable across runs, platforms and versions of this crate, so it can be stored to detect
+    /// whether a trace changed, or to deduplicate identical reports.
+    /// Trees that are equal with `==` have the same fingerprint.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::Tree;
+    /// let a = Tree::from_depths(vec![(0, "a"), (1, "b")]).unwrap();
+    /// let b = Tree::from_depths(vec![(0, "a"), (0, "b")]).unwrap();
+    /// assert_eq!(a.fingerprint(), a.clone().fingerprint());
+    /// assert_ne!(a.fingerprint(), b.fingerprint());
+    /// ```
+    pub fn fingerprint(&self) -> u64 {
+        // 64-bit FNV-1a, which unlike `DefaultHasher` is specified and won't change.
+        const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+        const PRIME: u64 = 0x0000_0100_0000_01b3;
+        let mut hash = OFFSET;
+        let mut write = |bytes: &[u8]| {
+            for &x in bytes {
+                hash = (hash ^ x as u64).wrapping_mul(PRIME);
+            }
+        };
+        let mut pending = vec![self];
+        while let Some(x) = pending.pop() {
+            match x.text.as_deref() {
+                Some(text) => {
+                    write(&[1]);
+                    write(&(text.len() as u64).to_le_bytes());
+                    write(text.as_bytes());
+                }
+                None => write(&[0]),
+            }
+            write(&(x.children.len() as u64).to_le_bytes());
+            pending.extend(x.children.iter().rev());
+        }
+        hash
+    }
+
+    /// Returns an estimate of the memory used by this tree and its descendants, in bytes,
+    /// counting the nodes, their text and their attachments.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::Tree;
+    /// let empty = Tree::new(None).estimated_bytes();
+    /// let tree = Tree::from_depths(vec![(0, "abc")]).unwrap();
+    /// assert_eq!(2 * empty + 3, tree.estimated_bytes());
+    /// ```
+    pub fn estimated_bytes(&self) -> usize {
+        std::mem::size_of::<Tree>()
+            + self.text.as_deref().map_or(0, str::len)
+            + self
+                .attachments
+                .iter()
+                .map(|x| x.name.len() + x.content.len())
+                .sum::<usize>()
+            + self
+                .children
+                .iter()
+                .map(Tree::estimated_bytes)
+                .sum::<usize>()
+    }
+
+    /// Returns the total number of nodes beneath this tree.
+    pub fn descendant_count(&self) -> usize {
+        self.children.iter().map(|x| 1 + x.descendant_count()).sum()
+    }
+
+    /// The text of this tree as it should be displayed, according to `config`.
+    fn display_text(&self, config: &TreeConfig) -> Option<String> {
+        let mut text = self.text.as_ref().map(|x| sanitize(x, config));
+        if let Some(text) = &mut text {
+            text.push_str(&self.metadata_suffix(config));
+        }
+        text
+    }
+
+    /// The child count and attachment sizes displayed after the text of this tree.
+    fn metadata_suffix(&self, config: &TreeConfig) -> String {
+        let mut suffix = String::new();
+        if let (Some(count), false) = (config.child_count, self.children.is_empty()) {
+            let (n, noun) = match count {
+                ChildCount::Children => (self.children.len(), "child"),
+                ChildCount::Descendants => (self.descendant_count(), "descendant"),
             };
-            txt.push_str(&format!(
-                "{}{}{}",
-                match position {
-                    Position::Only => config.symbols.join_only,
-                    Position::First => config.symbols.join_first,
-                    Position::Last => config.symbols.join_last,
-                    Position::Inside => config.symbols.join_inner,
-                },
-                branch,
-                first_leaf,
+            suffix.push_str(&match (n, count) {
+                (1, _) => format!(" (1 {})", noun),
+                (n, ChildCount::Children) => format!(" ({} children)", n),
+                (n, ChildCount::Descendants) => format!(" ({} descendants)", n),
+            });
+        }
+        for x in &self.attachments {
+            suffix.push_str(&format!(
+                " [attachment: {}]",
+                HumanSize(x.content.len() as u64)
             ));
+        }
+        suffix
+    }
 
-            let s = match &self.text {
-                Some(x) => match is_multiline {
-                    true => format!(
-                        "{}",
-                        x.replace(
-                            "\n",
-                            &format!(
-                                "\n{}{}{}{}",
-                                &pad,
-                                match position {
-                                    Position::Only | Position::Last =>
-                                        " ".repeat(config.symbols.continued.chars().count()),
-                                    _ => config.symbols.continued.to_string(),
-                                },
-                                " ".repeat(branch_size),
-                                match &config.symbols.multiline_continued {
-                                    Some(multi) => multi.to_string(),
-                                    _ => " ".repeat(first_leaf.chars().count()),
-                                }
-                            ),
-                        )
-                    ),
-                    false => x.clone(),
+    /// Colors the displayed `text` of this tree at `depth` with `theme`, line by line.
+    /// The metadata suffix is colored separately, unless it was cut short to fit the width.
+    fn paint_text(&self, text: &str, theme: &Theme, depth: usize, config: &TreeConfig) -> String {
+        let suffix = self.metadata_suffix(config);
+        let (body, suffix) = match text.strip_suffix(suffix.as_str()) {
+            Some(body) if !suffix.is_empty() => (body, suffix.as_str()),
+            _ => (text, ""),
+        };
+        let color = theme.text_color(self.level, depth);
+        let mut ret = body
+            .split('\n')
+            .map(|x| theme::paint(color, x))
+            .collect::<Vec<_>>()
+            .join("\n");
+        ret.push_str(&theme::paint(theme.metadata, suffix));
+        ret
+    }
+
+    /// "Render" this tree as the root of the output.
+    /// The root itself is hidden unless it has text, in which case it is rendered as a title line
+    /// and the top level is connected beneath it.
+    /// Only the newest `tail` top-level entries are rendered.
+    pub(crate) fn render_root(&self, config: &TreeConfig, tail: usize) -> String {
+        let view = self.view(config);
+        let mut config = config.clone();
+        // The newest entries are first if they're listed newest-first
+        let children = match config.newest_first {
+            Some(_) => &view.children[..tail.min(view.children.len())],
+            None => &view.children[view.children.len().saturating_sub(tail)..],
+        };
+        let size_hint = children
+            .iter()
+            .map(|x| x.size_hint(config.indent, 1))
+            .sum::<usize>();
+        if view.text.is_some() {
+            config.show_first_level = true;
+        }
+        let mut w = LineWriter::new(&config, size_hint);
+        let mut annotations: Vec<Option<&str>> = vec![];
+        // The node of each entry, for line prefixes
+        let mut nodes: Vec<Option<&Tree>> = vec![];
+        if let Some(text) = &view.text {
+            w.begin_line();
+            w.out
+                .push_str(&unconnected_text(&sanitize(text, &config), &config));
+            annotations.push(self.annotation.as_deref());
+            nodes.push(Some(&view));
+        }
+        for (index, x) in children.iter().enumerate() {
+            if index > 0 {
+                if let Some(separator) = config.separator {
+                    w.begin_line();
+                    if config.show_first_level {
+                        w.out.push_str(
+                            format!("{} {}", config.symbols.continued, separator).trim_end(),
+                        );
+                    } else {
+                        w.out.push_str(separator);
+                    }
+                    annotations.push(None);
+                    nodes.push(None);
+                }
+            }
+            x.write_lines(
+                &mut w,
+                config.show_first_level,
+                Position::of(index, children.len()),
+            );
+            x.collect_annotations(&mut annotations);
+            x.collect_nodes(&mut nodes);
+        }
+        // The node of each physical line, as entries may span several lines
+        let line_nodes: Vec<Option<&Tree>> = match config.line_prefix {
+            Some(_) => w
+                .entries()
+                .zip(&nodes)
+                .flat_map(|(entry, &node)| std::iter::repeat(node).take(entry.split('\n').count()))
+                .collect(),
+            None => vec![],
+        };
+        let is_annotated = annotations.iter().any(Option::is_some);
+        let mut out = if !is_annotated && !config.line_numbers {
+            w.out
+        } else {
+            let mut lines: Vec<String> = w.entries().map(String::from).collect();
+            if is_annotated {
+                lines = annotate_lines(&lines, &annotations, &config);
+            }
+            if config.line_numbers {
+                lines = number_lines(&lines, config.theme.as_ref().and_then(|x| x.metadata));
+            }
+            lines.join("\n")
+        };
+        if config.severity_summary && !view.children.is_empty() {
+            out.push('\n');
+            out.push_str(&view.severity_summary());
+        }
+        if let Some(prefix) = &config.line_prefix {
+            if !out.is_empty() {
+                let nodes = line_nodes.into_iter().chain(std::iter::repeat(None));
+                out = out
+                    .split('\n')
+                    .zip(nodes)
+                    .map(|(line, node)| format!("{}{}", (prefix.0)(node), line))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+            }
+        }
+        out
+    }
+
+    /// A summary of the number of error-level and warning-level nodes beneath this tree,
+    /// and of the total number of nodes, such as `3 errors, 12 warnings, 8,401 nodes`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::{TreeBuilder, Level};
+    /// let tree = TreeBuilder::new();
+    /// tree.add_leaf_at(Level::Error, "Timed out");
+    /// tree.add_leaf_at(Level::Warn, "Retrying");
+    /// tree.add_leaf("Done");
+    /// assert_eq!("1 error, 1 warning, 3 nodes", tree.snapshot().severity_summary());
+    /// ```
+    pub fn severity_summary(&self) -> String {
+        fn count(tree: &Tree, counts: &mut [usize; 3]) {
+            for x in &tree.children {
+                match x.level {
+                    Level::Error => counts[0] += 1,
+                    Level::Warn => counts[1] += 1,
+                    _ => {}
+                }
+                counts[2] += 1;
+                count(x, counts);
+            }
+        }
+        let mut counts = [0; 3];
+        count(self, &mut counts);
+        counts
+            .iter()
+            .zip(["error", "warning", "node"])
+            .map(|(&n, noun)| format!("{} {}{}", thousands(n), noun, if n == 1 { "" } else { "s" }))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Appends the annotation of this tree and each of its descendants, in depth-first order.
+    fn collect_annotations<'t>(&'t self, annotations: &mut Vec<Option<&'t str>>) {
+        annotations.push(self.annotation.as_deref());
+        for x in &self.children {
+            x.collect_annotations(annotations);
+        }
+    }
+
+    /// Appends this tree and each of its descendants, in the order they are rendered.
+    fn collect_nodes<'t>(&'t self, nodes: &mut Vec<Option<&'t Tree>>) {
+        nodes.push(Some(self));
+        for x in &self.children {
+            x.collect_nodes(nodes);
+        }
+    }
+
+    /// An estimate of the number of bytes needed to render this tree at `depth`.
+    fn size_hint(&self, indent: usize, depth: usize) -> usize {
+        // Box-drawing symbols take up to 3 bytes each.
+        let line = self.text.as_deref().map_or(0, str::len) + 3 * indent * depth + 1;
+        line + self
+            .children
+            .iter()
+            .map(|x| x.size_hint(indent, depth + 1))
+            .sum::<usize>()
+    }
+
+    /// The prefix of the continuation lines of multi-line text, after the padding of the parents.
+    /// By default the text is aligned under the first line, which is `first_width` columns after
+    /// the padding, and vertical lines are continued for the following siblings and the children.
+    fn continuation(
+        &self,
+        position: Position,
+        first_width: usize,
+        branch_size: usize,
+        config: &TreeConfig,
+    ) -> String {
+        let continued = config.symbols.continued;
+        let continued_width = continued.chars().count();
+        let has_siblings = matches!(position, Position::First | Position::Inside);
+        match (config.symbols.multiline_continued, config.multiline_indent) {
+            (Some(multi), None) => format!(
+                "{}{}{}",
+                if has_siblings {
+                    continued.to_string()
+                } else {
+                    " ".repeat(continued_width)
                 },
-                _ => String::new(),
+                " ".repeat(branch_size),
+                multi
+            ),
+            (multi, indent) => {
+                let multi = multi.unwrap_or("");
+                let width = indent.unwrap_or(first_width);
+                let mut prefix = vec![' '; width];
+                let mut overlay = |column: usize, s: &str| {
+                    if column + s.chars().count() <= width {
+                        for (i, c) in s.chars().enumerate() {
+                            prefix[column + i] = c;
+                        }
+                    }
+                };
+                if has_siblings {
+                    overlay(0, continued);
+                }
+                let child_column = continued_width + max(config.indent, 1) - 1;
+                if !self.children.is_empty() && child_column + continued_width < width {
+                    overlay(child_column, continued);
+                }
+                overlay(width.saturating_sub(multi.chars().count()), multi);
+                prefix.into_iter().collect()
+            }
+        }
+    }
+
+    /// "Render" this tree into `w`, adding a line for this node and for each of its descendants.
+    /// If `connected` is false, this node is rendered without a connector or padding.
+    fn write_lines(&self, w: &mut LineWriter<'_>, connected: bool, position: Position) {
+        let config = w.config;
+        let mut text = self.display_text(config);
+        w.begin_line();
+        if connected {
+            let join = match position {
+                Position::Only => config.symbols.join_only,
+                Position::First => config.symbols.join_first,
+                Position::Last => config.symbols.join_last,
+                Position::Inside => config.symbols.join_inner,
             };
-            txt.push_str(&s);
-        } else {
-            if let Some(x) = &self.text {
-                txt.push_str(&x);
+            let first_leaf = |is_multiline| match (is_multiline, config.symbols.multiline_first) {
+                (true, Some(x)) => x,
+                _ => config.symbols.leaf,
+            };
+            if let (Some(width), Some(x)) = (config.max_width, &mut text) {
+                let pad_width = w.pad.chars().count();
+                let first_width = join.chars().count()
+                    + w.branch.chars().count()
+                    + first_leaf(x.contains('\n') || config.overflow == Overflow::Wrap)
+                        .chars()
+                        .count();
+                let continuation_width = self
+                    .continuation(position, first_width, w.branch_size, config)
+                    .chars()
+                    .count();
+                *x = fit(
+                    x,
+                    width.saturating_sub(pad_width + first_width),
+                    width.saturating_sub(pad_width + continuation_width),
+                    config.overflow,
+                );
+            }
+            if let (Some(markup), Some(x)) = (config.markup, &mut text) {
+                *x = markup.apply(x);
             }
+            let is_multiline = text.as_ref().map(|x| x.contains('\n')).unwrap_or(false);
+            let first_leaf = first_leaf(is_multiline);
+            let connector = config.theme.as_ref().and_then(|x| x.connector);
+            if let (Some(theme), Some(x)) = (&config.theme, &mut text) {
+                *x = self.paint_text(x, theme, w.depth, config);
+            }
+            let prefix = format!("{}{}{}{}", w.pad, join, w.branch, first_leaf);
+            w.out.push_str(&theme::paint(connector, &prefix));
+            match &text {
+                Some(x) if is_multiline => {
+                    let first_width = join.chars().count()
+                        + w.branch.chars().count()
+                        + first_leaf.chars().count();
+                    let continuation =
+                        self.continuation(position, first_width, w.branch_size, config);
+                    let continuation = format!("{}{}", w.pad, continuation);
+                    for (i, x) in x.split('\n').enumerate() {
+                        if i > 0 {
+                            w.out.push('\n');
+                            w.out.push_str(&theme::paint(connector, &continuation));
+                        }
+                        w.out.push_str(x);
+                    }
+                }
+                Some(x) => w.out.push_str(x),
+                None => {}
+            }
+        } else if let Some(x) = &text {
+            let x = unconnected_text(x, config);
+            match &config.theme {
+                Some(theme) => w.out.push_str(&self.paint_text(&x, theme, w.depth, config)),
+                None => w.out.push_str(&x),
+            }
+        }
+
+        let pad_len = w.pad.len();
+        if connected {
+            w.pad.push_str(match position {
+                Position::Inside | Position::First => config.symbols.continued,
+                Position::Last | Position::Only => " ",
+            });
+            w.pad
+                .extend(std::iter::repeat(' ').take(max(config.indent, 1) - 1));
         }
-        let mut ret = vec![txt];
+        w.depth += 1;
         for (index, x) in self.children.iter().enumerate() {
-            for line in x.lines(&next_continue, index, self.children.len(), config) {
-                ret.push(line);
+            x.write_lines(w, true, Position::of(index, self.children.len()));
+        }
+        w.depth -= 1;
+        w.pad.truncate(pad_len);
+    }
+}
+
+impl Position {
+    /// The position of the element at `index` among `pool_size` siblings.
+    fn of(index: usize, pool_size: usize) -> Position {
+        match index {
+            _ if pool_size == 1 => Position::Only,
+            _ if (index + 1) == pool_size => Position::Last,
+            0 => Position::First,
+            _ => Position::Inside,
+        }
+    }
+}
+
+/// Truncates or wraps each line of `text`, so that the first line fits within `first` columns
+/// and the following lines fit within `rest` columns.
+fn fit(text: &str, first: usize, rest: usize, overflow: Overflow) -> String {
+    let mut ret = String::with_capacity(text.len());
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            ret.push('\n');
+        }
+        let width = max(if i == 0 { first } else { rest }, 1);
+        match overflow {
+            Overflow::Truncate if line.chars().count() > width => {
+                ret.extend(line.chars().take(width - 1));
+                ret.push('…');
+            }
+            Overflow::Truncate => ret.push_str(line),
+            Overflow::Wrap => {
+                let (mut width, mut n) = (width, 0);
+                for c in line.chars() {
+                    if n == width {
+                        ret.push('\n');
+                        width = max(rest, 1);
+                        n = 0;
+                    }
+                    ret.push(c);
+                    n += 1;
+                }
             }
         }
-        ret
+    }
+    ret
+}
+
+/// Applies the maximum width and markup of `config` to text that is rendered without a connector.
+fn unconnected_text<'t>(text: &'t str, config: &TreeConfig) -> Cow<'t, str> {
+    let mut text = Cow::Borrowed(text);
+    if let Some(width) = config.max_width {
+        text = Cow::Owned(fit(&text, width, width, config.overflow));
+    }
+    if let Some(markup) = config.markup {
+        text = Cow::Owned(markup.apply(&text));
+    }
+    text
+}
+
+/// State shared while rendering the lines of a tree, reused for every node.
+struct LineWriter<'a> {
+    config: &'a TreeConfig,
+    /// The branch between the connector and the leaf symbol of each node, e.g. `─`.
+    branch: String,
+    branch_size: usize,
+    /// The padding of the ancestors of the node being rendered.
+    pad: String,
+    /// The depth of the node being rendered, where top-level nodes are at depth 0.
+    depth: usize,
+    /// The rendered entries, separated by newlines. An entry may span several lines.
+    out: String,
+    /// The offset in `out` at which each entry starts.
+    starts: Vec<usize>,
+}
+
+impl<'a> LineWriter<'a> {
+    fn new(config: &'a TreeConfig, capacity: usize) -> LineWriter<'a> {
+        let branch_size = max(config.indent, 2usize) - 2;
+        let branch = match config.symbols.branch.len() {
+            0 => "-".repeat(branch_size),
+            1 => config.symbols.branch.repeat(branch_size),
+            _n => config
+                .symbols
+                .branch
+                .repeat(branch_size)
+                .chars()
+                .take(branch_size)
+                .collect::<String>(),
+        };
+        LineWriter {
+            config,
+            branch,
+            branch_size,
+            pad: String::new(),
+            depth: 0,
+            out: String::with_capacity(capacity),
+            starts: vec![],
+        }
+    }
+
+    /// Starts a new entry.
+    fn begin_line(&mut self) {
+        if !self.starts.is_empty() {
+            self.out.push('\n');
+        }
+        self.starts.push(self.out.len());
+    }
+
+    /// The text of each entry.
+    fn entries(&self) -> impl Iterator<Item = &str> {
+        let ends = self.starts.iter().skip(1).map(|x| x - 1);
+        self.starts
+            .iter()
+            .zip(ends.chain(std::iter::once(self.out.len())))
+            .map(move |(&start, end)| &self.out[start..end])
+    }
+}
+
+/// Returns whether `text` matches `pattern`, in which `*` matches any text.
+fn glob(pattern: &str, text: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == text,
+        Some((prefix, rest)) => match text.strip_prefix(prefix) {
+            Some(text) => text
+                .char_indices()
+                .map(|(i, _)| i)
+                .chain(std::iter::once(text.len()))
+                .any(|i| glob(rest, &text[i..])),
+            None => false,
+        },
+    }
+}
+
+fn is_any_nodes(pattern: &Tree) -> bool {
+    pattern.text.as_deref() == Some("**")
+}
+
+fn node_matches(tree: &Tree, pattern: &Tree, ordered: bool) -> bool {
+    let text = match (&tree.text, &pattern.text) {
+        (Some(text), Some(pattern)) => glob(pattern, text),
+        (text, pattern) => text == pattern,
+    };
+    text && match ordered {
+        true => matches_in_order(&tree.children, &pattern.children),
+        false => matches_any_order(&tree.children, &pattern.children),
+    }
+}
+
+fn matches_in_order(trees: &[Tree], patterns: &[Tree]) -> bool {
+    match patterns.split_first() {
+        None => trees.is_empty(),
+        Some((pattern, rest)) if is_any_nodes(pattern) => {
+            (0..=trees.len()).any(|i| matches_in_order(&trees[i..], rest))
+        }
+        Some((pattern, rest)) => match trees.split_first() {
+            Some((tree, trees)) => {
+                node_matches(tree, pattern, true) && matches_in_order(trees, rest)
+            }
+            None => false,
+        },
+    }
+}
+
+fn matches_any_order(trees: &[Tree], patterns: &[Tree]) -> bool {
+    /// Assigns each pattern to a different unused tree, backtracking on failure.
+    fn assign(trees: &[Tree], patterns: &[&Tree], used: &mut Vec<bool>) -> bool {
+        match patterns.split_first() {
+            None => true,
+            Some((pattern, rest)) => (0..trees.len()).any(|i| {
+                if used[i] || !node_matches(&trees[i], pattern, false) {
+                    return false;
+                }
+                used[i] = true;
+                let ok = assign(trees, rest, used);
+                used[i] = false;
+                ok
+            }),
+        }
+    }
+    let any_nodes = patterns.iter().any(is_any_nodes);
+    let patterns: Vec<&Tree> = patterns.iter().filter(|x| !is_any_nodes(x)).collect();
+    if patterns.len() > trees.len() || (!any_nodes && patterns.len() != trees.len()) {
+        return false;
+    }
+    assign(trees, &patterns, &mut vec![false; trees.len()])
+}
+
+/// Appends each annotation to the first line of its entry, right-aligned in a gutter that starts
+/// after the longest line, so that annotations form a single column.
+fn annotate_lines(
+    lines: &[String],
+    annotations: &[Option<&str>],
+    config: &TreeConfig,
+) -> Vec<String> {
+    let body = lines
+        .iter()
+        .flat_map(|x| x.split('\n'))
+        .map(theme::visible_width)
+        .max()
+        .unwrap_or(0);
+    let gutter = annotations
+        .iter()
+        .flatten()
+        .map(|x| x.chars().count())
+        .fold(config.gutter_width, max);
+    lines
+        .iter()
+        .zip(annotations)
+        .map(|(line, annotation)| match annotation {
+            Some(annotation) => {
+                let (first, rest) = match line.split_once('\n') {
+                    Some((first, rest)) => (first, format!("\n{}", rest)),
+                    None => (line.as_str(), String::new()),
+                };
+                let metadata = config.theme.as_ref().and_then(|x| x.metadata);
+                format!(
+                    "{}{} {}{}{}",
+                    first,
+                    " ".repeat(body - theme::visible_width(first)),
+                    " ".repeat(gutter - annotation.chars().count()),
+                    theme::paint(metadata, annotation),
+                    rest,
+                )
+            }
+            None => line.clone(),
+        })
+        .collect()
+}
+
+/// Prefixes each physical line (splitting multi-line entries) with its right-aligned line number.
+fn number_lines(lines: &[String], color: Option<Color>) -> Vec<String> {
+    let lines: Vec<&str> = lines.iter().flat_map(|x| x.split('\n')).collect();
+    let width = lines.len().to_string().len();
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, x)| {
+            let number = format!("{:>width$}", i + 1, width = width);
+            format!("{} {}", theme::paint(color, &number), x)
+        })
+        .collect()
+}
+
+/// Identifies a node that was added to a tree.
+///
+/// An identifier is only valid for the tree that returned it,
+/// and is invalidated when that tree is cleared.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NodeId {
+    path: Vec<usize>,
+    generation: usize,
+}
+
+impl NodeId {
+    /// The indices of the node and its ancestors, starting from the top level.
+    pub fn path(&self) -> &[usize] {
+        &self.path
+    }
+
+    /// The identifier of the parent of the node, or `None` if the node is at the top level.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// let _branch = tree.add_branch("Request");
+    /// let id = tree.add_leaf_id("Response").unwrap();
+    /// let parent = id.parent().unwrap();
+    /// assert_eq!(Some("Request".to_string()), tree.node_text(&parent));
+    /// assert!(parent.parent().is_none());
+    /// ```
+    pub fn parent(&self) -> Option<NodeId> {
+        match self.path.split_last() {
+            Some((_, path)) if !path.is_empty() => Some(NodeId {
+                path: path.to_vec(),
+                generation: self.generation,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Formats the path of the node as its indices separated by `/`, such as `0/2/1`,
+/// which can be passed back to [Tree::at].
+impl fmt::Display for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, x) in self.path.iter().enumerate() {
+            if i > 0 {
+                f.write_str("/")?;
+            }
+            write!(f, "{}", x)?;
+        }
+        Ok(())
+    }
+}
+
+/// The path of a node, as the indices of the node and its ancestors, starting from the top level.
+/// Paths are either slices of indices, such as `[0, 2, 1]`, or strings of indices separated by
+/// `/`, such as `"0/2/1"`.
+pub trait TreePath {
+    /// The indices of the path, or `None` if it isn't a valid path.
+    fn indices(&self) -> Option<Cow<'_, [usize]>>;
+}
+
+impl TreePath for [usize] {
+    fn indices(&self) -> Option<Cow<'_, [usize]>> {
+        Some(Cow::Borrowed(self))
+    }
+}
+
+impl<const N: usize> TreePath for [usize; N] {
+    fn indices(&self) -> Option<Cow<'_, [usize]>> {
+        Some(Cow::Borrowed(self))
+    }
+}
+
+impl TreePath for Vec<usize> {
+    fn indices(&self) -> Option<Cow<'_, [usize]>> {
+        Some(Cow::Borrowed(self))
+    }
+}
+
+impl TreePath for str {
+    fn indices(&self) -> Option<Cow<'_, [usize]>> {
+        if self.is_empty() {
+            return Some(Cow::Borrowed(&[]));
+        }
+        self.split('/')
+            .map(|x| x.parse().ok())
+            .collect::<Option<Vec<_>>>()
+            .map(Cow::Owned)
+    }
+}
+
+impl TreePath for String {
+    fn indices(&self) -> Option<Cow<'_, [usize]>> {
+        self.as_str().indices()
+    }
+}
+
+impl TreePath for NodeId {
+    fn indices(&self) -> Option<Cow<'_, [usize]>> {
+        Some(Cow::Borrowed(&self.path))
+    }
+}
+
+type NodeCallback = Arc<dyn Fn(&NodeId, &str) + Send + Sync>;
+type DepthCallback = Arc<dyn Fn(usize) + Send + Sync>;
+
+/// Callbacks that are notified of changes to a tree.
+#[derive(Clone, Default)]
+pub(crate) struct Observers {
+    pub node_added: Vec<NodeCallback>,
+    pub entered: Vec<DepthCallback>,
+    pub exited: Vec<DepthCallback>,
+    pub subscribers: Vec<Sender<TreeEvent>>,
+    pub recording: Option<Vec<TreeEvent>>,
+    pub logs: Vec<Arc<Mutex<std::fs::File>>>,
+}
+
+impl Observers {
+    /// Sends the event made by `event` to the subscribers, dropping those that have unsubscribed,
+    /// records it if recording, and appends it to the event logs.
+    fn send<F: Fn() -> TreeEvent>(&mut self, event: F) {
+        if !self.subscribers.is_empty() {
+            self.subscribers.retain(|x| x.send(event()).is_ok());
+        }
+        if let Some(x) = &mut self.recording {
+            x.push(event());
+        }
+        if !self.logs.is_empty() {
+            let record = event().encode();
+            self.logs
+                .retain(|x| match x.lock().unwrap().write_all(&record) {
+                    Ok(()) => true,
+                    Err(err) => {
+                        eprintln!("debug_tree: stopped writing event log: {}", err);
+                        false
+                    }
+                });
+        }
+    }
+}
+
+impl fmt::Debug for Observers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Observers")
+            .field("node_added", &self.node_added.len())
+            .field("entered", &self.entered.len())
+            .field("exited", &self.exited.len())
+            .field("subscribers", &self.subscribers.len())
+            .field("recording", &self.recording.as_ref().map(Vec::len))
+            .field("logs", &self.logs.len())
+            .finish()
+    }
+}
+
+/// Renders and clears a tree once its estimated size exceeds `limit` bytes.
+pub(crate) struct AutoFlush {
+    limit: usize,
+    /// The estimated size of the tree, which is recalculated when it passes `limit`.
+    estimate: usize,
+    /// Where the tree is written.
+    sink: Arc<Mutex<dyn Write + Send>>,
+    /// The error of the last flush that failed, until it is taken.
+    error: Option<std::io::Error>,
+}
+
+impl fmt::Debug for AutoFlush {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AutoFlush")
+            .field("limit", &self.limit)
+            .field("estimate", &self.estimate)
+            .field("error", &self.error)
+            .finish()
     }
 }
 
-/// Holds the current state of the tree, including the path to the branch.
-/// Multiple trees may point to the same data.
-#[derive(Debug, Clone)]
+/// Holds the current state of the tree, including its data and the path to the branch.
+#[derive(Debug)]
 pub(crate) struct TreeBuilderBase {
-    data: Arc<Mutex<Tree>>,
+    data: Tree,
+    /// Identifies this state while it is alive. See [ForkPoint].
+    id: usize,
     path: Vec<usize>,
     dive_count: usize,
     config: Option<TreeConfig>,
-    is_enabled: bool,
+    /// Shared with each [TreeBuilder](crate::TreeBuilder) handle, so it can be read without locking.
+    is_enabled: Arc<AtomicBool>,
+    level: Level,
+    generation: usize,
+    /// Time of the last checkpoint in each entered scope, innermost last.
+    checkpoints: Vec<Instant>,
+    /// Depth of the muted branch, if any. Nothing is added at or beneath it.
+    mute_depth: Option<usize>,
+    pub observers: Observers,
+    /// Where this state was forked from, if it was. See [TreeBuilderBase::fork].
+    fork_point: Option<ForkPoint>,
+    /// If true, unbalanced calls to `enter` and `exit` add a warning leaf.
+    cursor_checks: bool,
+    /// The most recently added node.
+    last_added: Option<NodeId>,
+    /// If true, each added node records its [Timing].
+    timing: bool,
+    /// If true, each top-level node records its [Origin], for [TreeConfig::thread_timeline].
+    origins: bool,
+    /// The total time spent adding nodes, entering and exiting, if overhead tracking is on.
+    overhead: Option<Duration>,
+    /// When to render and clear the tree, if it's set to flush automatically.
+    auto_flush: Option<AutoFlush>,
+    /// Trees rendered as top-level branches after this one's nodes, each with its name.
+    mounts: Vec<(String, Arc<Lock<TreeBuilderBase>>)>,
+}
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Held while mounting, so that concurrent mounts can't both pass the cycle check.
+static MOUNT_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// The cursor of a tree, saved so that it can be returned to after moving elsewhere.
+#[derive(Debug, Clone)]
+pub(crate) struct Cursor {
+    generation: usize,
+    path: Vec<usize>,
+    dive_count: usize,
+    mute_depth: Option<usize>,
+}
+
+/// The cursor of a tree at the time it was forked.
+#[derive(Debug, Clone)]
+pub(crate) struct ForkPoint {
+    /// The id of the forked state.
+    id: usize,
+    generation: usize,
+    path: Vec<usize>,
+    dive_count: usize,
+}
+
+/// The number of top-level nodes of a tree and of each tree mounted beneath it, at the time it
+/// was rendered, so that only the nodes that were rendered are removed afterwards.
+#[derive(Debug, Clone)]
+pub(crate) struct Rendered {
+    count: usize,
+    mounts: Vec<Rendered>,
 }
 
 impl TreeBuilderBase {
     /// Create a new state
     pub fn new() -> TreeBuilderBase {
         TreeBuilderBase {
-            data: Arc::new(Mutex::new(Tree::new(None))),
+            data: Tree::new(None),
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
             path: vec![],
             dive_count: 1,
             config: None,
-            is_enabled: true,
+            is_enabled: Arc::new(AtomicBool::new(true)),
+            level: Level::Trace,
+            generation: 0,
+            checkpoints: vec![Instant::now()],
+            mute_depth: None,
+            observers: Observers::default(),
+            fork_point: None,
+            cursor_checks: false,
+            last_added: None,
+            timing: false,
+            origins: false,
+            overhead: None,
+            auto_flush: None,
+            mounts: vec![],
+        }
+    }
+
+    /// Create a new state holding `tree`, with the cursor after its last top-level node.
+    pub fn from_tree(tree: Tree) -> TreeBuilderBase {
+        TreeBuilderBase {
+            data: tree,
+            ..Self::new()
+        }
+    }
+
+    /// Create an empty state with the same enabled state, level and configuration override,
+    /// which remembers the current cursor so it can be joined back to it.
+    pub fn fork(&self) -> TreeBuilderBase {
+        TreeBuilderBase {
+            config: self.config.clone(),
+            is_enabled: Arc::new(AtomicBool::new(self.is_enabled())),
+            level: self.level,
+            cursor_checks: self.cursor_checks,
+            timing: self.timing,
+            origins: self.origins,
+            overhead: self.overhead.map(|_| Duration::default()),
+            fork_point: Some(ForkPoint {
+                id: self.id,
+                generation: self.generation,
+                path: self.path.clone(),
+                dive_count: self.dive_count,
+            }),
+            ..Self::new()
+        }
+    }
+
+    /// The cursor that `fork_point` refers to, if it was forked from this state
+    /// and this state hasn't been cleared since.
+    fn fork_cursor(&self, fork_point: &ForkPoint) -> Option<(Vec<usize>, usize)> {
+        if fork_point.id != self.id || fork_point.generation != self.generation {
+            return None;
+        }
+        let parent = &fork_point.path[..max(1, fork_point.path.len()) - 1];
+        let exists = match self.data.at(parent) {
+            Some(x) => match fork_point.path.last() {
+                Some(&i) => i < x.children.len(),
+                None => true,
+            },
+            None => false,
+        };
+        if exists {
+            Some((fork_point.path.clone(), fork_point.dive_count))
+        } else {
+            None
         }
     }
 
+    /// Adds the nodes of `child` beneath its root to the branch where `child` was forked,
+    /// without moving the cursor. They are added to the current branch instead
+    /// if `child` wasn't forked from this state, or if it has been cleared since.
+    pub fn join(&mut self, child: Tree, fork_point: Option<ForkPoint>) {
+        let cursor = fork_point.and_then(|x| self.fork_cursor(&x));
+        let saved = match cursor {
+            Some((path, dive_count)) => Some((
+                std::mem::replace(&mut self.path, path),
+                std::mem::replace(&mut self.dive_count, dive_count),
+            )),
+            None => None,
+        };
+        for x in &child.children {
+            self.add_node(x);
+        }
+        if let Some((path, dive_count)) = saved {
+            self.path = path;
+            self.dive_count = dive_count;
+        }
+    }
+
+    /// Where this state was forked from, if it was.
+    pub fn fork_point(&self) -> Option<ForkPoint> {
+        self.fork_point.clone()
+    }
+
     pub fn set_enabled(&mut self, enabled: bool) {
-        self.is_enabled = enabled;
+        self.is_enabled.store(enabled, Ordering::Relaxed);
     }
     pub fn is_enabled(&self) -> bool {
-        self.is_enabled
+        self.is_enabled.load(Ordering::Relaxed)
+    }
+    /// The enabled flag, which stays the same when the tree is cleared.
+    pub fn enabled_flag(&self) -> Arc<AtomicBool> {
+        self.is_enabled.clone()
+    }
+
+    pub fn set_level(&mut self, level: Level) {
+        self.level = level;
+    }
+    pub fn level(&self) -> Level {
+        self.level
+    }
+    pub fn is_level_enabled(&self, level: Level) -> bool {
+        self.is_enabled() && level <= self.level
+    }
+
+    /// Adds `node` and its descendants to the current branch,
+    /// keeping their levels, targets, annotations and metadata.
+    /// A node whose level isn't enabled is left out, and its children are added in its place.
+    pub fn add_node(&mut self, node: &Tree) {
+        if !self.is_level_enabled(node.level) {
+            for x in &node.children {
+                self.add_node(x);
+            }
+            return;
+        }
+        if let Some(id) = self.add_leaf(node.text.as_deref().unwrap_or("")) {
+            if let Some(x) = self.data.at_mut(&id.path) {
+                x.level = node.level;
+                x.target = node.target;
+                x.annotation = node.annotation.clone();
+                x.attachments = node.attachments.clone();
+                x.metadata = node.metadata.clone();
+            }
+            if !node.children.is_empty() {
+                self.enter();
+                for x in &node.children {
+                    self.add_node(x);
+                }
+                self.exit();
+            }
+        }
     }
 
-    pub fn add_leaf(&mut self, text: &str) {
+    /// Adds a leaf, unless the current branch is muted.
+    pub fn add_leaf(&mut self, text: &str) -> Option<NodeId> {
+        self.add_leaf_text(Cow::Owned(text.to_string()))
+    }
+
+    /// Adds a leaf, unless the current branch is muted. Borrowed text is stored without copying.
+    pub fn add_leaf_text(&mut self, text: Cow<'static, str>) -> Option<NodeId> {
+        if self.is_muted() {
+            return None;
+        }
+        let started = self.overhead.map(|_| Instant::now());
+        self.check_auto_flush(std::mem::size_of::<Tree>() + text.len());
+        let mut text = Some(text);
         let &dive_count = &self.dive_count;
         if dive_count > 0 {
             for i in 0..dive_count {
                 let mut n = 0;
-                if let Some(x) = self.data.lock().unwrap().at_mut(&self.path) {
-                    x.children.push(Tree::new(if i == max(1, dive_count) - 1 {
-                        Some(&text)
-                    } else {
-                        None
-                    }));
+                if let Some(x) = self.data.at_mut(&self.path) {
+                    let mut node = Tree::new(None);
+                    if i == max(1, dive_count) - 1 {
+                        node.text = text.take();
+                    }
+                    x.children.push(node);
                     n = x.children.len() - 1;
                 }
                 self.path.push(n);
             }
             self.dive_count = 0;
         } else {
-            if let Some(x) = self
-                .data
-                .lock()
-                .unwrap()
-                .at_mut(&self.path[..max(1, self.path.len()) - 1])
-            {
-                x.children.push(Tree::new(Some(&text)));
-                let n = match self.path.last() {
-                    Some(&x) => x + 1,
-                    _ => 0,
-                };
-                self.path.last_mut().map(|x| *x = n);
+            if let Some(x) = self.data.at_mut(&self.path[..max(1, self.path.len()) - 1]) {
+                let mut node = Tree::new(None);
+                node.text = text.take();
+                x.children.push(node);
+                let n = x.children.len() - 1;
+                if let Some(x) = self.path.last_mut() {
+                    *x = n;
+                }
+            }
+        }
+        let origins = self.origins || self.config.as_ref().is_some_and(|x| x.thread_timeline);
+        if let (true, Some(&top)) = (origins, self.path.first()) {
+            if let Some(x) = self.data.children.get_mut(top) {
+                if x.metadata::<Origin>().is_none() {
+                    x.set_metadata(Origin {
+                        time: Instant::now(),
+                        thread: std::thread::current(),
+                    });
+                }
+            }
+        }
+        if self.timing {
+            if let Some(x) = self.data.at_mut(&self.path) {
+                x.set_metadata(Timing {
+                    start: Instant::now(),
+                    end: None,
+                });
+            }
+        }
+        let id = NodeId {
+            path: self.path.clone(),
+            generation: self.generation,
+        };
+        let data = &self.data;
+        let text = data
+            .at(&id.path)
+            .and_then(|x| x.text.as_deref())
+            .unwrap_or("");
+        for f in &self.observers.node_added {
+            f(&id, text);
+        }
+        self.last_added = Some(id.clone());
+        self.observers.send(|| TreeEvent::LeafAdded {
+            path: id.path.clone(),
+            text: text.to_string(),
+        });
+        self.record_overhead(started);
+        Some(id)
+    }
+
+    /// Adds a top-level leaf, without moving the cursor.
+    pub fn add_root_leaf_text(&mut self, text: Cow<'static, str>) -> Option<NodeId> {
+        let cursor = self.save_cursor();
+        self.path.clear();
+        self.dive_count = 1;
+        self.mute_depth = None;
+        let id = self.add_leaf_text(text);
+        self.restore_cursor(cursor);
+        id
+    }
+
+    fn save_cursor(&self) -> Cursor {
+        Cursor {
+            generation: self.generation,
+            path: self.path.clone(),
+            dive_count: self.dive_count,
+            mute_depth: self.mute_depth,
+        }
+    }
+
+    /// Moves the cursor back to `cursor`, or to the top level if the tree has been cleared or
+    /// pruned since it was saved.
+    fn restore_cursor(&mut self, cursor: Cursor) {
+        if cursor.generation == self.generation {
+            self.path = cursor.path;
+            self.dive_count = cursor.dive_count;
+            self.mute_depth = cursor.mute_depth;
+        } else {
+            self.path.clear();
+            self.dive_count = 1;
+            self.mute_depth = None;
+        }
+    }
+
+    /// Enters the node identified by `id`, so that nodes are added after its children, and
+    /// returns the cursor to go back to with [leave](TreeBuilderBase::leave).
+    /// Returns `None` if the node no longer exists.
+    pub fn enter_node(&mut self, id: &NodeId) -> Option<Cursor> {
+        match self.is_current(id) {
+            true => self.enter_path(&id.path),
+            false => None,
+        }
+    }
+
+    /// Enters the node at `path`. See [enter_node](TreeBuilderBase::enter_node).
+    pub fn enter_path(&mut self, path: &[usize]) -> Option<Cursor> {
+        if path.is_empty() || self.data.at(path).is_none() {
+            return None;
+        }
+        let cursor = self.save_cursor();
+        self.path = path.to_vec();
+        self.dive_count = 1;
+        self.mute_depth = None;
+        self.checkpoints.push(Instant::now());
+        Some(cursor)
+    }
+
+    /// Moves the cursor back to where it was before [enter_node](TreeBuilderBase::enter_node).
+    pub fn leave(&mut self, cursor: Cursor) {
+        if self.checkpoints.len() > 1 {
+            self.checkpoints.pop();
+        }
+        self.restore_cursor(cursor);
+    }
+
+    /// Discards everything added to the current branch, until it is exited.
+    pub fn mute_current(&mut self) {
+        if self.mute_depth.is_none() {
+            self.mute_depth = Some(self.depth());
+        }
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.mute_depth.is_some()
+    }
+
+    fn is_current(&self, id: &NodeId) -> bool {
+        id.generation == self.generation
+    }
+
+    pub fn node_text(&self, id: &NodeId) -> Option<String> {
+        if !self.is_current(id) {
+            return None;
+        }
+        self.data
+            .at(&id.path)
+            .and_then(|x| x.text.as_deref().map(String::from))
+    }
+
+    pub fn last_leaf_text(&self) -> Option<String> {
+        self.last_added.as_ref().and_then(|id| self.node_text(id))
+    }
+
+    /// The text of the branch that nodes are currently added to,
+    /// or `None` at the top level or if the branch has no text yet.
+    pub fn current_branch_text(&self) -> Option<String> {
+        let branch = match self.dive_count {
+            0 => &self.path[..max(1, self.path.len()) - 1],
+            1 => &self.path[..],
+            _ => return None,
+        };
+        if branch.is_empty() {
+            return None;
+        }
+        self.data
+            .at(branch)
+            .and_then(|x| x.text.as_deref().map(String::from))
+    }
+
+    pub fn set_node_text(&mut self, id: &NodeId, text: &str) -> bool {
+        if !self.is_current(id) {
+            return false;
+        }
+        match self.data.at_mut(&id.path) {
+            Some(x) => {
+                x.text = Some(Cow::Owned(text.to_string()));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn set_metadata<T: Any + Send + Sync>(&mut self, id: &NodeId, value: T) -> bool {
+        if !self.is_current(id) {
+            return false;
+        }
+        match self.data.at_mut(&id.path) {
+            Some(x) => {
+                x.set_metadata(value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn set_annotation(&mut self, id: &NodeId, annotation: &str) -> bool {
+        if !self.is_current(id) {
+            return false;
+        }
+        match self.data.at_mut(&id.path) {
+            Some(x) => {
+                x.annotation = Some(annotation.to_string());
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn append_to_last(&mut self, text: &str) {
+        if self.is_enabled() && !self.is_muted() && !self.path.is_empty() {
+            if let Some(x) = self.data.at_mut(&self.path) {
+                x.text
+                    .get_or_insert(Cow::Borrowed(""))
+                    .to_mut()
+                    .push_str(text);
+            }
+        }
+    }
+
+    pub fn annotate(&mut self, annotation: &str) {
+        if self.is_enabled() && !self.path.is_empty() {
+            if let Some(x) = self.data.at_mut(&self.path) {
+                x.annotation = Some(annotation.to_string());
+            }
+        }
+    }
+
+    pub fn attach(&mut self, name: &str, content: &str) {
+        if self.is_enabled() && !self.is_muted() && !self.path.is_empty() {
+            self.check_auto_flush(name.len() + content.len());
+            if let Some(x) = self.data.at_mut(&self.path) {
+                x.attachments.push(Attachment {
+                    name: name.to_string(),
+                    content: content.to_string(),
+                });
+            }
+        }
+    }
+
+    pub fn set_node_level(&mut self, id: &NodeId, level: Level) {
+        if self.is_current(id) {
+            if let Some(x) = self.data.at_mut(&id.path) {
+                x.level = level;
+            }
+        }
+    }
+
+    pub fn set_target(&mut self, id: &NodeId, target: &'static str) {
+        if self.is_current(id) {
+            if let Some(x) = self.data.at_mut(&id.path) {
+                x.target = Some(target);
+            }
+        }
+    }
+
+    /// Removes the nodes for which `keep` returns false, along with their descendants unless
+    /// `hoist` is true, in which case their children take their place.
+    /// The current branch, its ancestors and the most recently added node are always kept.
+    /// Existing `NodeId`s are invalidated.
+    pub fn retain<F: FnMut(&Tree) -> bool>(&mut self, hoist: bool, mut keep: F) {
+        self.data
+            .retain_except_path(&mut self.path, hoist, &mut keep);
+        self.generation += 1;
+    }
+
+    pub fn map_text<F: FnMut(&str) -> String>(&mut self, f: F) {
+        self.data.map_text(f);
+    }
+
+    pub fn query(&self, pattern: &str) -> Vec<NodeId> {
+        self.data
+            .query(pattern)
+            .into_iter()
+            .map(|(path, _)| NodeId {
+                path,
+                generation: self.generation,
+            })
+            .collect()
+    }
+
+    /// Mounts `tree` as a branch named `name`. Returns false without mounting it if `tree` is
+    /// this state, or already mounts this state directly or indirectly.
+    pub fn mount(this: &Arc<Lock<Self>>, name: &str, tree: &Arc<Lock<Self>>) -> bool {
+        let _lock = MOUNT_LOCK.lock().unwrap_or_else(|x| x.into_inner());
+        if Arc::ptr_eq(this, tree) || tree.lock().mounts_reach(this) {
+            return false;
+        }
+        this.lock().mounts.push((name.to_string(), tree.clone()));
+        true
+    }
+
+    /// Returns whether `tree` is mounted beneath this state.
+    fn mounts_reach(&self, tree: &Arc<Lock<Self>>) -> bool {
+        self.mounts
+            .iter()
+            .any(|(_, x)| Arc::ptr_eq(x, tree) || x.lock().mounts_reach(tree))
+    }
+
+    /// The data of this state, followed by a branch for each mounted tree that isn't empty.
+    fn combined(&self) -> Cow<'_, Tree> {
+        if self.mounts.is_empty() {
+            return Cow::Borrowed(&self.data);
+        }
+        let mut tree = self.data.clone();
+        for (name, x) in &self.mounts {
+            let x = x.lock();
+            if x.data.children.is_empty() && x.mounts.is_empty() {
+                continue;
             }
+            let mut branch = Tree::new(Some(name));
+            branch.children = x.combined().children.clone();
+            tree.children.push(branch);
         }
+        Cow::Owned(tree)
+    }
+
+    /// Returns whether this state or any tree mounted beneath it has nodes.
+    pub fn has_content(&self) -> bool {
+        !self.data.children.is_empty() || self.mounts.iter().any(|(_, x)| x.lock().has_content())
+    }
+
+    pub fn with_data<R, F: FnOnce(&Tree) -> R>(&self, f: F) -> R {
+        f(&self.data)
+    }
+
+    pub fn set_root_text(&mut self, text: Option<&str>) {
+        self.data.text = text.map(|x| Cow::Owned(x.to_string()));
+    }
+
+    pub fn root_text(&self) -> Option<String> {
+        self.data.text.as_deref().map(String::from)
     }
 
     pub fn set_config_override(&mut self, config: Option<TreeConfig>) {
@@ -225,18 +2318,187 @@ impl TreeBuilderBase {
     pub fn config_override(&self) -> &Option<TreeConfig> {
         &self.config
     }
+
+    /// The configuration used to render this tree: its override, or else the default config.
+    pub fn resolved_config(&self) -> TreeConfig {
+        self.config.clone().unwrap_or_else(tree_config)
+    }
     pub fn config_override_mut(&mut self) -> &mut Option<TreeConfig> {
         &mut self.config
     }
 
     pub fn enter(&mut self) {
+        let started = self.overhead.map(|_| Instant::now());
         self.dive_count += 1;
+        self.checkpoints.push(Instant::now());
+        let depth = self.depth();
+        for f in &self.observers.entered {
+            f(depth);
+        }
+        self.observers.send(|| TreeEvent::Entered);
+        self.record_overhead(started);
+    }
+
+    pub fn set_timing(&mut self, enabled: bool) {
+        self.timing = enabled;
+    }
+
+    pub fn set_thread_timeline(&mut self, enabled: bool) {
+        self.origins = enabled;
+    }
+
+    pub fn set_overhead_tracking(&mut self, enabled: bool) {
+        self.overhead = match enabled {
+            true => Some(self.overhead.unwrap_or_default()),
+            false => None,
+        };
+    }
+
+    pub fn overhead(&self) -> Option<Duration> {
+        self.overhead
+    }
+
+    pub fn set_auto_flush(&mut self, limit: usize, sink: Arc<Mutex<dyn Write + Send>>) {
+        self.auto_flush = Some(AutoFlush {
+            limit,
+            estimate: self.data.estimated_bytes(),
+            sink,
+            error: None,
+        });
+    }
+
+    pub fn disable_auto_flush(&mut self) {
+        self.auto_flush = None;
+    }
+
+    pub fn take_auto_flush_error(&mut self) -> Option<std::io::Error> {
+        self.auto_flush.as_mut().and_then(|x| x.error.take())
+    }
+
+    /// Adds `bytes` to the estimated size of the tree, and flushes the tree if it is over the
+    /// auto-flush limit and has more than the current branch and its ancestors.
+    fn check_auto_flush(&mut self, bytes: usize) {
+        let Some(x) = &mut self.auto_flush else {
+            return;
+        };
+        x.estimate += bytes;
+        if x.estimate <= x.limit {
+            return;
+        }
+        x.estimate = self.data.estimated_bytes();
+        if x.estimate > x.limit && self.data.descendant_count() > self.branch_depth() {
+            self.flush_keeping_cursor();
+        }
+    }
+
+    /// The number of nodes in the path to the current branch.
+    fn branch_depth(&self) -> usize {
+        match self.dive_count {
+            0 => self.path.len().saturating_sub(1),
+            _ => self.path.len(),
+        }
+    }
+
+    /// Writes the tree to the auto-flush sink, then removes everything except the current branch
+    /// and its ancestors. Existing `NodeId`s are invalidated.
+    /// If writing fails, the error is kept and the tree is left as it is, to be written again
+    /// once `limit` more bytes are added.
+    fn flush_keeping_cursor(&mut self) {
+        let text = self.peek_string();
+        let Some(auto_flush) = &mut self.auto_flush else {
+            return;
+        };
+        let result = {
+            let mut sink = auto_flush.sink.lock().unwrap_or_else(|x| x.into_inner());
+            writeln!(sink, "{}", text).and_then(|_| sink.flush())
+        };
+        if let Err(err) = result {
+            auto_flush.error = Some(err);
+            auto_flush.estimate = 0;
+            return;
+        }
+        let depth = self.branch_depth();
+        let mut chain = Vec::with_capacity(depth);
+        let mut siblings = std::mem::take(&mut self.data.children);
+        for &i in &self.path[..depth] {
+            let Some(x) = siblings.get_mut(i) else {
+                break;
+            };
+            let mut node = std::mem::replace(x, Tree::new(None));
+            siblings = std::mem::take(&mut node.children);
+            chain.push(node);
+        }
+        let mut child = None;
+        for mut node in chain.into_iter().rev() {
+            node.children.extend(child.take());
+            child = Some(node);
+        }
+        self.data.children.extend(child);
+        self.path.iter_mut().for_each(|x| *x = 0);
+        self.generation += 1;
+        self.last_added = None;
+        let estimate = self.data.estimated_bytes();
+        if let Some(x) = &mut self.auto_flush {
+            x.estimate = estimate;
+        }
+    }
+
+    /// Adds the time since `started` to the total overhead, and to the [Overhead] of the
+    /// current branch.
+    fn record_overhead(&mut self, started: Option<Instant>) {
+        let (Some(started), Some(total)) = (started, self.overhead.as_mut()) else {
+            return;
+        };
+        let elapsed = started.elapsed();
+        *total += elapsed;
+        let branch = match self.dive_count {
+            0 => &self.path[..self.path.len().saturating_sub(1)],
+            _ => &self.path[..],
+        };
+        if let Some(x) = self.data.at_mut(branch) {
+            let overhead = x.metadata::<Overhead>().copied().unwrap_or_default();
+            x.set_metadata(Overhead(overhead.0 + elapsed));
+        }
+    }
+
+    pub fn set_cursor_checks(&mut self, enabled: bool) {
+        self.cursor_checks = enabled;
+    }
+
+    /// Adds a warning leaf if cursor checks are enabled.
+    fn cursor_warning(&mut self, text: &str) {
+        if self.cursor_checks && self.is_level_enabled(Level::Warn) {
+            if let Some(id) = self.add_leaf(&format!("debug_tree: {}", text)) {
+                self.set_node_level(&id, Level::Warn);
+            }
+        }
+    }
+
+    /// Adds a warning leaf if cursor checks are enabled and any branches haven't been exited.
+    fn check_balanced(&mut self) {
+        match self.depth() {
+            0 => {}
+            1 => self.cursor_warning("1 branch was entered but not exited"),
+            n => self.cursor_warning(&format!("{} branches were entered but not exited", n)),
+        }
+    }
+
+    /// Moves the cursor back to the top level, exiting every branch.
+    pub fn reset_cursor(&mut self) {
+        while self.depth() > 0 {
+            self.exit();
+        }
     }
 
     /// Try stepping up to the parent tree branch.
     /// Returns false if already at the top branch.
     pub fn exit(&mut self) -> bool {
-        if self.dive_count > 0 {
+        if self.depth() == 0 {
+            self.cursor_warning("exit() was called at the top level");
+            return false;
+        }
+        let started = self.overhead.map(|_| Instant::now());
+        let exited = if self.dive_count > 0 {
             self.dive_count -= 1;
             true
         } else {
@@ -246,7 +2508,44 @@ impl TreeBuilderBase {
             } else {
                 false
             }
+        };
+        if exited && self.checkpoints.len() > 1 {
+            self.checkpoints.pop();
+        }
+        if exited && self.timing {
+            if let Some(x) = self.data.at_mut(&self.path) {
+                if let Some(&timing) = x.metadata::<Timing>() {
+                    x.set_metadata(Timing {
+                        end: Some(Instant::now()),
+                        ..timing
+                    });
+                }
+            }
+        }
+        if exited {
+            let depth = self.depth();
+            if self.mute_depth.map(|x| depth < x).unwrap_or(false) {
+                self.mute_depth = None;
+            }
+            for f in &self.observers.exited {
+                f(depth);
+            }
+            self.observers.send(|| TreeEvent::Exited);
         }
+        self.record_overhead(started);
+        exited
+    }
+
+    /// Adds a leaf with the time elapsed since the previous checkpoint in the current scope,
+    /// or since the scope was entered.
+    pub fn checkpoint(&mut self, label: &str) -> Duration {
+        let now = Instant::now();
+        let elapsed = match self.checkpoints.last_mut() {
+            Some(x) => now.duration_since(std::mem::replace(x, now)),
+            None => Duration::default(),
+        };
+        self.add_leaf(&format!("{}: {:?}", label, elapsed));
+        elapsed
     }
 
     pub fn depth(&self) -> usize {
@@ -254,28 +2553,128 @@ impl TreeBuilderBase {
     }
 
     pub fn peek_print(&self) {
-        println!("{}", self.peek_string());
+        let mut config = self.resolved_config();
+        if config.max_width.is_none() && config.terminal_width {
+            config.max_width = crate::terminal::stdout_width();
+        }
+        println!("{}", self.combined().render_root(&config, usize::MAX));
     }
 
     pub fn print(&mut self) {
+        self.check_balanced();
         self.peek_print();
         self.clear();
     }
+    /// Clears the data and cursor, keeping the settings and root text.
     pub fn clear(&mut self) {
-        *self = Self::new();
+        self.clear_data();
+        for (_, x) in &self.mounts {
+            x.lock().clear();
+        }
+    }
+
+    /// Clears the data and cursor of this state, but not of the trees mounted beneath it.
+    fn clear_data(&mut self) {
+        let root_text = self.root_text();
+        *self = TreeBuilderBase {
+            config: self.config.take(),
+            is_enabled: self.is_enabled.clone(),
+            level: self.level,
+            generation: self.generation + 1,
+            observers: std::mem::take(&mut self.observers),
+            fork_point: self.fork_point.take(),
+            cursor_checks: self.cursor_checks,
+            timing: self.timing,
+            origins: self.origins,
+            overhead: self.overhead.map(|_| Duration::default()),
+            auto_flush: self
+                .auto_flush
+                .take()
+                .map(|x| AutoFlush { estimate: 0, ..x }),
+            mounts: std::mem::take(&mut self.mounts),
+            ..Self::new()
+        };
+        self.set_root_text(root_text.as_deref());
+        self.observers.send(|| TreeEvent::Cleared);
+    }
+
+    /// The number of top-level nodes of this state and of the trees mounted beneath it.
+    pub fn rendered(&self) -> Rendered {
+        Rendered {
+            count: self.data.children.len(),
+            mounts: self
+                .mounts
+                .iter()
+                .map(|(_, x)| x.lock().rendered())
+                .collect(),
+        }
+    }
+
+    /// Removes the top-level nodes counted by `rendered`, keeping any added since.
+    /// The state is cleared if no nodes were added since. Otherwise, existing `NodeId`s are
+    /// invalidated, and the cursor is reset if it was in a removed node.
+    pub fn remove_rendered(&mut self, rendered: &Rendered) {
+        for ((_, x), rendered) in self.mounts.iter().zip(&rendered.mounts) {
+            x.lock().remove_rendered(rendered);
+        }
+        let removed = rendered.count.min(self.data.children.len());
+        if removed == self.data.children.len() {
+            self.clear_data();
+            return;
+        }
+        self.data.children.drain(..removed);
+        match self.path.first_mut() {
+            Some(x) if *x >= removed => *x -= removed,
+            Some(_) => {
+                self.path.clear();
+                self.dive_count = 1;
+                self.mute_depth = None;
+            }
+            None => {}
+        }
+        self.generation += 1;
+        self.last_added = None;
+        let estimate = self.data.estimated_bytes();
+        if let Some(x) = &mut self.auto_flush {
+            x.estimate = estimate;
+        }
     }
 
     pub fn string(&mut self) -> String {
+        self.check_balanced();
         let s = self.peek_string();
         self.clear();
         s
     }
 
     pub fn peek_string(&self) -> String {
-        let config = self
-            .config_override()
-            .clone()
-            .unwrap_or_else(|| tree_config().clone());
-        (&self.data.lock().unwrap().lines(&vec![], 0, 1, &config)[1..]).join("\n")
+        self.peek_string_tail(usize::MAX)
+    }
+
+    pub fn peek_html(&self) -> String {
+        crate::html::render(&self.combined(), &self.resolved_config())
+    }
+
+    pub fn peek_json(&self) -> String {
+        self.combined().view(&self.resolved_config()).to_json()
+    }
+
+    pub fn peek_spans(&self) -> String {
+        self.combined().view(&self.resolved_config()).to_spans()
+    }
+
+    pub fn peek_csv_stats(&self) -> String {
+        self.combined().view(&self.resolved_config()).to_csv_stats()
+    }
+
+    pub fn html(&mut self) -> String {
+        self.check_balanced();
+        let s = self.peek_html();
+        self.clear();
+        s
+    }
+
+    pub fn peek_string_tail(&self, n: usize) -> String {
+        self.combined().render_root(&self.resolved_config(), n)
     }
 }
@@ -0,0 +1,356 @@
+use crate::{Attachment, Tree};
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// A parsed JSON value.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// The value of `key`, if this is an object that contains it.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(x) => x.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(x) => Some(x),
+            _ => None,
+        }
+    }
+}
+
+/// The deepest nesting of arrays and objects that is parsed, so that the recursive parser
+/// can't overflow the stack.
+const MAX_DEPTH: usize = 512;
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    /// The number of arrays and objects that contain the current value.
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_whitespace(&mut self) {
+        while let Some(' ' | '\t' | '\n' | '\r') = self.chars.peek() {
+            self.chars.next();
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        match self.chars.next() {
+            Some(x) if x == c => Ok(()),
+            Some(x) => Err(format!("expected `{}`, found `{}`", c, x)),
+            None => Err(format!("expected `{}`, found the end", c)),
+        }
+    }
+
+    fn keyword(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        for c in word.chars() {
+            self.expect(c)?;
+        }
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        let value = match self.chars.peek() {
+            Some('n') => self.keyword("null", Json::Null),
+            Some('t') => self.keyword("true", Json::Bool(true)),
+            Some('f') => self.keyword("false", Json::Bool(false)),
+            Some('"') => self.string().map(Json::String),
+            Some('[' | '{') if self.depth >= MAX_DEPTH => Err("nested too deeply".to_string()),
+            Some('[') => self.nested(Self::array),
+            Some('{') => self.nested(Self::object),
+            Some(&c) if c == '-' || c.is_ascii_digit() => self.number(),
+            Some(&c) => Err(format!("unexpected `{}`", c)),
+            None => Err("unexpected end".to_string()),
+        };
+        self.skip_whitespace();
+        value
+    }
+
+    fn nested(&mut self, f: fn(&mut Self) -> Result<Json, String>) -> Result<Json, String> {
+        self.depth += 1;
+        let value = f(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let mut s = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if c.is_ascii_digit() || "+-.eE".contains(c) {
+                s.push(c);
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+        s.parse()
+            .map(Json::Number)
+            .map_err(|_| format!("invalid number `{}`", s))
+    }
+
+    fn hex(&mut self) -> Result<u32, String> {
+        let mut x = 0;
+        for _ in 0..4 {
+            let digit = self
+                .chars
+                .next()
+                .and_then(|c| c.to_digit(16))
+                .ok_or("invalid `\\u` escape")?;
+            x = x * 16 + digit;
+        }
+        Ok(x)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            match self.chars.next().ok_or("unterminated string")? {
+                '"' => return Ok(s),
+                '\\' => s.push(match self.chars.next().ok_or("unterminated string")? {
+                    'n' => '\n',
+                    't' => '\t',
+                    'r' => '\r',
+                    'b' => '\u{8}',
+                    'f' => '\u{c}',
+                    'u' => {
+                        let mut x = self.hex()?;
+                        if (0xd800..0xdc00).contains(&x) {
+                            self.expect('\\')?;
+                            self.expect('u')?;
+                            let low = self.hex()?;
+                            if !(0xdc00..0xe000).contains(&low) {
+                                return Err("invalid `\\u` escape".to_string());
+                            }
+                            x = 0x10000 + ((x - 0xd800) << 10) + (low - 0xdc00);
+                        }
+                        char::from_u32(x).ok_or("invalid `\\u` escape")?
+                    }
+                    c => c,
+                }),
+                c => s.push(c),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        self.expect('[')?;
+        let mut ret = vec![];
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&']') {
+            self.chars.next();
+            return Ok(Json::Array(ret));
+        }
+        loop {
+            ret.push(self.value()?);
+            match self.chars.next() {
+                Some(',') => {}
+                Some(']') => return Ok(Json::Array(ret)),
+                _ => return Err("expected `,` or `]`".to_string()),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, String> {
+        self.expect('{')?;
+        let mut ret = vec![];
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&'}') {
+            self.chars.next();
+            return Ok(Json::Object(ret));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            ret.push((key, self.value()?));
+            match self.chars.next() {
+                Some(',') => {}
+                Some('}') => return Ok(Json::Object(ret)),
+                _ => return Err("expected `,` or `}`".to_string()),
+            }
+        }
+    }
+}
+
+/// Parses a JSON document.
+pub(crate) fn parse(s: &str) -> Result<Json, String> {
+    let mut parser = Parser {
+        chars: s.chars().peekable(),
+        depth: 0,
+    };
+    let value = parser.value()?;
+    match parser.chars.next() {
+        None => Ok(value),
+        Some(c) => Err(format!("unexpected `{}` after the value", c)),
+    }
+}
+
+/// Appends `s` to `out` as a JSON string literal.
+fn write_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn write_node(tree: &Tree, out: &mut String) {
+    out.push('{');
+    let mut fields = 0;
+    let mut field = |name: &str, out: &mut String| {
+        if fields > 0 {
+            out.push(',');
+        }
+        fields += 1;
+        write_string(name, out);
+        out.push(':');
+    };
+    if let Some(text) = &tree.text {
+        field("text", out);
+        write_string(text, out);
+    }
+    if !tree.children.is_empty() {
+        field("children", out);
+        write_nodes(&tree.children, out);
+    }
+    if !tree.attachments.is_empty() {
+        field("attachments", out);
+        out.push('[');
+        for (i, x) in tree.attachments.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push_str("{\"name\":");
+            write_string(&x.name, out);
+            out.push_str(",\"content\":");
+            write_string(&x.content, out);
+            out.push('}');
+        }
+        out.push(']');
+    }
+    out.push('}');
+}
+
+fn write_nodes(nodes: &[Tree], out: &mut String) {
+    out.push('[');
+    for (i, x) in nodes.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_node(x, out);
+    }
+    out.push(']');
+}
+
+impl Tree {
+    /// Returns the tree as JSON in the format read by [from_json](Tree::from_json):
+    /// the root node if it has text, otherwise the array of top-level nodes.
+    /// Each node's attachments are included as an `attachments` array of `name` and `content`
+    /// objects.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::Tree;
+    /// let tree = Tree::from_json(r#"[{"text": "1", "children": ["1.1"]}, "2"]"#).unwrap();
+    /// assert_eq!(
+    ///     r#"[{"text":"1","children":[{"text":"1.1"}]},{"text":"2"}]"#,
+    ///     tree.to_json()
+    /// );
+    /// assert_eq!(tree.to_json(), Tree::from_json(&tree.to_json()).unwrap().to_json());
+    /// ```
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        match self.text {
+            Some(_) => write_node(self, &mut out),
+            None => write_nodes(&self.children, &mut out),
+        }
+        out
+    }
+
+    /// Create a tree from JSON, where each node is an object with an optional `text` string and
+    /// an optional `children` array of nodes, or is a string for a node without children.
+    /// The document is either the root node, or an array of top-level nodes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::{Tree, TreeBuilder};
+    /// let tree = Tree::from_json(r#"[{"text": "1", "children": ["1.1", {"text": "1.2"}]}, "2"]"#).unwrap();
+    /// assert_eq!("1\n├╼ 1.1\n└╼ 1.2\n2", TreeBuilder::from(tree).peek_string());
+    /// ```
+    pub fn from_json(s: &str) -> Result<Tree, String> {
+        fn node(value: &Json) -> Result<Tree, String> {
+            match value {
+                Json::String(x) => Ok(Tree::new(Some(x))),
+                Json::Object(_) => {
+                    let mut tree = Tree::new(match value.get("text") {
+                        Some(Json::String(x)) => Some(x),
+                        None | Some(Json::Null) => None,
+                        Some(_) => return Err("expected `text` to be a string".to_string()),
+                    });
+                    match value.get("children") {
+                        Some(Json::Array(x)) => {
+                            tree.children = x.iter().map(node).collect::<Result<_, _>>()?
+                        }
+                        None | Some(Json::Null) => {}
+                        Some(_) => return Err("expected `children` to be an array".to_string()),
+                    }
+                    match value.get("attachments") {
+                        Some(Json::Array(x)) => {
+                            tree.attachments = x.iter().map(attachment).collect::<Result<_, _>>()?
+                        }
+                        None | Some(Json::Null) => {}
+                        Some(_) => return Err("expected `attachments` to be an array".to_string()),
+                    }
+                    Ok(tree)
+                }
+                _ => Err("expected a node to be an object or a string".to_string()),
+            }
+        }
+        fn attachment(value: &Json) -> Result<Attachment, String> {
+            match (
+                value.get("name").and_then(Json::as_str),
+                value.get("content").and_then(Json::as_str),
+            ) {
+                (Some(name), Some(content)) => Ok(Attachment {
+                    name: name.to_string(),
+                    content: content.to_string(),
+                }),
+                _ => Err("expected an attachment to have a `name` and a `content`".to_string()),
+            }
+        }
+        match parse(s)? {
+            Json::Array(x) => {
+                let mut tree = Tree::new(None);
+                tree.children = x.iter().map(node).collect::<Result<_, _>>()?;
+                Ok(tree)
+            }
+            x => node(&x),
+        }
+    }
+}
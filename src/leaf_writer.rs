@@ -0,0 +1,31 @@
+use crate::TreeBuilder;
+use std::fmt;
+
+/// Accumulates formatted text, and adds it as a leaf when dropped.
+/// Returned by [leaf_writer](TreeBuilder::leaf_writer).
+pub struct LeafWriter {
+    tree: TreeBuilder,
+    text: String,
+}
+
+impl LeafWriter {
+    pub fn new(tree: TreeBuilder) -> LeafWriter {
+        LeafWriter {
+            tree,
+            text: String::new(),
+        }
+    }
+}
+
+impl fmt::Write for LeafWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.text.push_str(s);
+        Ok(())
+    }
+}
+
+impl Drop for LeafWriter {
+    fn drop(&mut self) {
+        self.tree.add_leaf(&self.text);
+    }
+}
@@ -0,0 +1,57 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Verbosity of an addition to a tree, from most to least severe.
+///
+/// Additions made without a level, such as with [add_leaf](crate::TreeBuilder::add_leaf),
+/// are treated as `Level::Info`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Level {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    /// All levels, from most to least severe.
+    pub const ALL: [Level; 5] = [
+        Level::Error,
+        Level::Warn,
+        Level::Info,
+        Level::Debug,
+        Level::Trace,
+    ];
+
+    /// The lowercase name of the level.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+            Level::Trace => "trace",
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    /// Parses a level name, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Level::ALL
+            .iter()
+            .find(|x| x.as_str().eq_ignore_ascii_case(s.trim()))
+            .copied()
+            .ok_or_else(|| format!("unknown level: {}", s))
+    }
+}
@@ -1,28 +1,83 @@
+use crate::sync::Lock;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
+pub mod builder;
+mod capture;
+mod csv;
 #[macro_use]
 pub mod default;
+pub mod filter;
+mod gantt;
+mod html;
+pub mod human;
 mod internal;
+mod json;
+pub mod leaf_writer;
+pub mod level;
+pub mod live;
+mod markup;
+#[cfg(feature = "rayon")]
+pub mod par;
+mod rust_code;
 pub mod scoped_branch;
+pub mod scoped_config;
+#[cfg(feature = "serde")]
+mod serde_tree;
+mod side_by_side;
+#[cfg(unix)]
+pub mod signal;
+mod spans;
+mod sync;
+mod terminal;
+pub mod thread;
+#[cfg(feature = "tracing")]
+pub mod trace_capture;
 
 pub mod defer;
+pub mod event;
 mod test;
+pub mod theme;
 pub mod tree_config;
 
-pub use default::default_tree;
+pub use default::{configure_default, default_tree, set_default_scoped};
 use once_cell::sync::Lazy;
-use scoped_branch::ScopedBranch;
+use scoped_branch::{ScopedBranch, ScopedCursor};
+pub use side_by_side::side_by_side;
+use std::borrow::Cow;
 use std::collections::BTreeMap;
+use std::fmt;
 use std::fs::File;
-use std::io::Write;
+use std::io::{BufRead, Write};
+pub use thread::{spawn, spawn_scoped};
 
+pub use crate::event::TreeEvent;
+pub use crate::internal::{Attachment, NodeId, Overhead, Timing, Tree, TreePath};
+pub use crate::level::Level;
+pub use crate::markup::Markup;
 pub use crate::tree_config::*;
+#[cfg(feature = "regex")]
+pub use regex;
 
 /// Reference wrapper for `TreeBuilderBase`
+///
+/// The enabled flag is shared outside the lock, so checking it doesn't contend with additions.
 #[derive(Debug, Clone)]
-pub struct TreeBuilder(Arc<Mutex<internal::TreeBuilderBase>>);
+pub struct TreeBuilder(Arc<Lock<internal::TreeBuilderBase>>, Arc<AtomicBool>);
 
 impl TreeBuilder {
+    /// Wraps `base` in a new handle.
+    fn from_base(base: internal::TreeBuilderBase) -> TreeBuilder {
+        let enabled = base.enabled_flag();
+        TreeBuilder(Arc::new(Lock::new(base)), enabled)
+    }
+
+    /// Returns another handle to the shared state `base`.
+    pub(crate) fn from_shared(base: Arc<Lock<internal::TreeBuilderBase>>) -> TreeBuilder {
+        let enabled = base.lock().enabled_flag();
+        TreeBuilder(base, enabled)
+    }
+
     /// Returns a new `TreeBuilder` with an empty `Tree`.
     ///
     /// # Example
@@ -32,9 +87,38 @@ impl TreeBuilder {
     /// let tree = TreeBuilder::new();
     /// ```
     pub fn new() -> TreeBuilder {
-        TreeBuilder {
-            0: Arc::new(Mutex::new(internal::TreeBuilderBase::new())),
-        }
+        TreeBuilder::from_base(internal::TreeBuilderBase::new())
+    }
+
+    /// Returns a `Builder` for configuring a new `TreeBuilder` in one place.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::builder().indent(4).enabled(false).build();
+    /// tree.add_leaf("Leaf");
+    /// assert_eq!(Some(4), tree.get_config_override().map(|x| x.indent));
+    /// assert_eq!("", &tree.peek_string());
+    /// ```
+    pub fn builder() -> builder::Builder {
+        builder::Builder::new()
+    }
+
+    /// Returns a new `TreeBuilder` with an empty `Tree` and the given configuration override.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::{TreeBuilder, TreeConfig};
+    /// let tree = TreeBuilder::with_config(TreeConfig::new().show_first_level());
+    /// tree.add_leaf("Leaf");
+    /// assert_eq!("└╼ Leaf", &tree.peek_string());
+    /// ```
+    pub fn with_config(config: TreeConfig) -> TreeBuilder {
+        let tree = TreeBuilder::new();
+        tree.set_config_override(config);
+        tree
     }
 
     /// Set the configuration override for displaying trees
@@ -67,14 +151,14 @@ impl TreeBuilder {
     /// ╰╼ 2" , &tree.string());
     /// ```
     pub fn set_config_override(&self, config: TreeConfig) {
-        let mut lock = self.0.lock().unwrap();
+        let mut lock = self.0.lock();
         lock.set_config_override(Some(config))
     }
 
     /// Remove the configuration override
     /// The default configuration will be used instead
     pub fn remove_config_override(&self) {
-        self.0.lock().unwrap().set_config_override(None);
+        self.0.lock().set_config_override(None);
     }
 
     /// Update the configuration override for displaying trees
@@ -110,7 +194,7 @@ impl TreeBuilder {
     /// ╰─╼ 2" , &tree.string());
     /// ```
     pub fn update_config_override<F: Fn(&mut TreeConfig)>(&self, update: F) {
-        let mut lock = self.0.lock().unwrap();
+        let mut lock = self.0.lock();
         match lock.config_override_mut() {
             Some(x) => update(x),
             None => {
@@ -121,18 +205,100 @@ impl TreeBuilder {
         }
     }
 
+    /// Set the symbols of the configuration override to a built-in style.
+    /// If an override doesn't yet exist, it is created.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::{TreeBuilder, Style};
+    /// let tree = TreeBuilder::new();
+    /// {
+    ///     let _branch = tree.add_branch("1");
+    ///     tree.add_leaf("1.1");
+    ///     tree.add_leaf("1.2");
+    /// }
+    /// tree.set_style(Style::Double);
+    /// assert_eq!("\
+    /// 1
+    /// ╠╼ 1.1
+    /// ╚╼ 1.2" , &tree.string());
+    /// ```
+    pub fn set_style(&self, style: Style) {
+        self.update_config_override(|x| x.symbols = style.symbols());
+    }
+
+    /// Renders this tree with an indentation of `indent` until the returned guard is dropped,
+    /// after which the previous configuration is restored.
+    /// This affects the output rendered within the scope, such as sections printed as they finish.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// tree.add_leaf("1");
+    /// tree.enter();
+    /// tree.add_leaf("1.1");
+    /// {
+    ///     let _indent = tree.with_indentation(4);
+    ///     assert_eq!("1\n└──╼ 1.1", tree.peek_string());
+    /// }
+    /// assert_eq!("1\n└╼ 1.1", tree.peek_string());
+    /// ```
+    pub fn with_indentation(&self, indent: usize) -> scoped_config::ScopedConfig {
+        let config = self.0.lock().resolved_config().indent(indent);
+        scoped_config::ScopedConfig::new(self.clone(), config)
+    }
+
     /// Returns the optional configuration override.
     pub fn get_config_override(&self) -> Option<TreeConfig> {
-        let lock = self.0.lock().unwrap();
+        let lock = self.0.lock();
         lock.config_override().clone()
     }
 
     /// Returns whether a configuration override is set.
     pub fn has_config_override(&self) -> bool {
-        let lock = self.0.lock().unwrap();
+        let lock = self.0.lock();
         lock.config_override().is_some()
     }
 
+    /// Sets the text of the root node.
+    ///
+    /// The root node is normally hidden. When it has text, it is rendered as a title line
+    /// and all top-level entries are connected beneath it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// tree.set_root_text("Report");
+    /// {
+    ///     let _branch = tree.add_branch("1");
+    ///     tree.add_leaf("1.1");
+    /// }
+    /// tree.add_leaf("2");
+    /// assert_eq!("\
+    /// Report
+    /// ├╼ 1
+    /// │ └╼ 1.1
+    /// └╼ 2" , &tree.peek_string());
+    /// ```
+    pub fn set_root_text(&self, text: &str) {
+        self.0.lock().set_root_text(Some(text));
+    }
+
+    /// Removes the text of the root node, hiding it again.
+    pub fn remove_root_text(&self) {
+        self.0.lock().set_root_text(None);
+    }
+
+    /// Returns the text of the root node, if any.
+    pub fn root_text(&self) -> Option<String> {
+        self.0.lock().root_text()
+    }
+
     /// Adds a new branch with text, `text` and returns a `ScopedBranch`.
     /// When the returned `ScopedBranch` goes out of scope, (likely the end of the current block),
     /// or if its `release()` method is called, the tree will step back out of the added branch.
@@ -177,8 +343,46 @@ impl TreeBuilder {
     /// Sibling of Branch", &tree.string());
     /// ```
     pub fn add_branch(&self, text: &str) -> ScopedBranch {
-        self.add_leaf(text);
-        ScopedBranch::new(self.clone())
+        self.add_branch_at(Level::Info, text)
+    }
+
+    /// Calls `f` inside a new branch with text, `text`, and returns its result.
+    ///
+    /// If `f` panics, the panic message is added as a leaf inside the branch
+    /// and the panic then continues to unwind.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// let result = std::panic::catch_unwind(|| {
+    ///     tree.catch("Parse", || {
+    ///         tree.add_leaf("Reading input");
+    ///         panic!("Unexpected end of input");
+    ///     })
+    /// });
+    /// assert!(result.is_err());
+    /// assert_eq!("\
+    /// Parse
+    /// ├╼ Reading input
+    /// └╼ panicked: Unexpected end of input", &tree.string());
+    /// ```
+    pub fn catch<R, F: FnOnce() -> R>(&self, text: &str, f: F) -> R {
+        let _branch = self.add_branch(text);
+        // The panic is always resumed, so no broken state can be observed after unwinding.
+        match std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)) {
+            Ok(x) => x,
+            Err(payload) => {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|x| x.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "Box<dyn Any>".to_string());
+                self.add_leaf(&format!("panicked: {}", message));
+                std::panic::resume_unwind(payload)
+            }
+        }
     }
 
     /// Adds a new branch with text, `text` and returns a `ScopedBranch`.
@@ -247,253 +451,1976 @@ impl TreeBuilder {
     /// tree.add_leaf("New leaf");
     /// ```
     pub fn add_leaf(&self, text: &str) {
-        let mut x = self.0.lock().unwrap();
-        if x.is_enabled() {
-            x.add_leaf(&text);
-        }
+        self.add_leaf_at(Level::Info, text)
     }
 
-    /// Steps into a new child branch.
-    /// Stepping out of the branch requires calling `exit()`.
+    /// Adds `value` as a leaf to the current branch, and returns it.
+    /// This is the method form of [add_leaf_value_to!], so a value can be logged in passing.
     ///
     /// # Example
+    ///
     /// ```
     /// use debug_tree::TreeBuilder;
     /// let tree = TreeBuilder::new();
-    /// tree.add_leaf("Branch");
-    /// tree.enter();
-    /// tree.add_leaf("Child of Branch");
-    /// assert_eq!("\
-    /// Branch
-    /// └╼ Child of Branch", &tree.string());
+    /// let total = tree.add_leaf_value(2 + 3) * 2;
+    /// assert_eq!(10, total);
+    /// assert_eq!("5", tree.peek_string());
     /// ```
-    pub fn enter(&self) {
-        let mut x = self.0.lock().unwrap();
-        if x.is_enabled() {
-            x.enter();
-        }
+    pub fn add_leaf_value<T: std::fmt::Display>(&self, value: T) -> T {
+        self.add_leaf(&value.to_string());
+        value
     }
 
-    /// Exits the current branch, to the parent branch.
-    /// If no parent branch exists, no action is taken
+    /// Adds a leaf to the current branch with `label` and a human-readable `duration`,
+    /// such as `Parsed: 1.24ms`. See [HumanDuration](crate::human::HumanDuration).
+    /// This is the method form of [add_duration_to!].
     ///
     /// # Example
     ///
     /// ```
     /// use debug_tree::TreeBuilder;
+    /// use std::time::Duration;
     /// let tree = TreeBuilder::new();
-    /// tree.add_leaf("Branch");
-    /// tree.enter();
-    /// tree.add_leaf("Child of Branch");
-    /// tree.exit();
-    /// tree.add_leaf("Sibling of Branch");
-    /// assert_eq!("\
-    /// Branch
-    /// └╼ Child of Branch
-    /// Sibling of Branch", &tree.string());
+    /// tree.add_duration("Parsed", Duration::from_micros(1240));
+    /// tree.add_duration("Uploaded", Duration::from_secs(182));
+    /// assert_eq!("Parsed: 1.24ms\nUploaded: 3m 02s", tree.peek_string());
     /// ```
-    pub fn exit(&self) -> bool {
-        let mut x = self.0.lock().unwrap();
-        if x.is_enabled() {
-            x.exit()
-        } else {
-            false
-        }
+    pub fn add_duration(&self, label: &str, duration: std::time::Duration) {
+        self.add_leaf(&format!("{}: {}", label, human::HumanDuration(duration)));
     }
 
-    /// Returns the depth of the current branch
-    /// The initial depth when no branches have been adeed is 0.
+    /// Adds a leaf to the current branch with `label` and a human-readable size of `bytes`,
+    /// such as `Downloaded: 4.7 MiB`. See [HumanSize](crate::human::HumanSize).
+    /// This is the method form of [add_size_to!].
     ///
     /// # Example
     ///
     /// ```
     /// use debug_tree::TreeBuilder;
     /// let tree = TreeBuilder::new();
-    /// assert_eq!(0, tree.depth());
-    /// let _b = tree.add_branch("Branch");
-    /// assert_eq!(1, tree.depth());
-    /// let _b = tree.add_branch("Child branch");
-    /// assert_eq!(2, tree.depth());
+    /// tree.add_size("Downloaded", 4_928_307);
+    /// assert_eq!("Downloaded: 4.7 MiB", tree.peek_string());
     /// ```
-    pub fn depth(&self) -> usize {
-        self.0.lock().unwrap().depth()
+    pub fn add_size(&self, label: &str, bytes: u64) {
+        self.add_leaf(&format!("{}: {}", label, human::HumanSize(bytes)));
     }
 
-    /// Prints the tree without clearing.
+    /// Adds `bytes` to the current branch as a hex dump, with one leaf for each line of
+    /// 16 bytes. Each line shows the offset, the bytes in hex, and the printable ASCII bytes.
+    /// This is the method form of [add_hexdump_to!].
     ///
     /// # Example
     ///
     /// ```
     /// use debug_tree::TreeBuilder;
     /// let tree = TreeBuilder::new();
-    /// tree.add_leaf("Leaf");
-    /// tree.peek_print();
-    /// // Leaf
-    /// tree.peek_print();
-    /// // Leaf
-    /// // Leaf 2
+    /// tree.add_hexdump(b"GET /index.html HTTP/1.1\r\n");
+    /// assert_eq!("\
+    /// 00000000  47 45 54 20 2f 69 6e 64  65 78 2e 68 74 6d 6c 20  |GET /index.html |
+    /// 00000010  48 54 54 50 2f 31 2e 31  0d 0a                    |HTTP/1.1..|", tree.peek_string());
     /// ```
-    pub fn peek_print(&self) {
-        self.0.lock().unwrap().peek_print();
+    pub fn add_hexdump(&self, bytes: &[u8]) {
+        for (i, chunk) in bytes.chunks(16).enumerate() {
+            let mut line = format!("{:08x} ", i * 16);
+            for j in 0..16 {
+                if j % 8 == 0 {
+                    line.push(' ');
+                }
+                match chunk.get(j) {
+                    Some(x) => line.push_str(&format!("{:02x} ", x)),
+                    None => line.push_str("   "),
+                }
+            }
+            line.push_str(" |");
+            line.extend(chunk.iter().map(|&x| match x {
+                0x20..=0x7e => x as char,
+                _ => '.',
+            }));
+            line.push('|');
+            self.add_leaf(&line);
+        }
     }
 
-    /// Prints the tree and then clears it.
+    /// Serializes `value` and adds it to the current branch as a node labelled `label`.
+    /// Maps, structs, sequences and enum variants with data become branches of their entries,
+    /// keyed by field name, map key or `[index]`, and scalars become `key: value` leaves.
+    /// If `value` can't be serialized, an error-level leaf with the error is added instead.
+    /// This is the method form of [add_serde_to!].
     ///
     /// # Example
     ///
     /// ```
     /// use debug_tree::TreeBuilder;
+    /// use std::collections::BTreeMap;
+    /// let mut hosts = BTreeMap::new();
+    /// hosts.insert("primary", (Some("10.0.0.1"), 5432));
+    /// hosts.insert("replica", (None, 5433));
     /// let tree = TreeBuilder::new();
-    /// tree.add_leaf("Leaf");
-    /// tree.print();
-    /// // Leaf
-    /// tree.add_leaf("Leaf 2");
-    /// tree.print();
-    /// // Leaf 2
+    /// tree.add_serde("hosts", &hosts);
+    /// tree.add_serde("retries", &Vec::<u8>::new());
+    /// assert_eq!("\
+    /// hosts
+    /// ├╼ primary
+    /// │ ├╼ [0]: \"10.0.0.1\"
+    /// │ └╼ [1]: 5432
+    /// └╼ replica
+    ///   ├╼ [0]: None
+    ///   └╼ [1]: 5433
+    /// retries: {}", tree.peek_string());
     /// ```
-    pub fn print(&self) {
-        self.0.lock().unwrap().print();
+    #[cfg(feature = "serde")]
+    pub fn add_serde<T: ?Sized + serde::Serialize>(&self, label: &str, value: &T) {
+        if self.is_enabled() {
+            let mut tree = Tree::new(None);
+            tree.children.push(serde_tree::to_tree(label, value));
+            self.add_tree(&tree);
+        }
     }
 
-    /// Returns the tree as a string without clearing the tree.
+    /// Adds the nodes of `tree` beneath it to the current branch, if this tree is enabled.
+    fn add_tree(&self, tree: &Tree) {
+        let mut x = self.0.lock();
+        if !x.is_enabled() {
+            return;
+        }
+        for node in &tree.children {
+            x.add_node(node);
+        }
+    }
+
+    /// Creates an empty tree that can be moved to another thread, and later joined back to the
+    /// current branch of this tree with [join](TreeBuilder::join).
+    /// The new tree starts with the same enabled state, level and configuration override.
     ///
     /// # Example
     ///
     /// ```
     /// use debug_tree::TreeBuilder;
     /// let tree = TreeBuilder::new();
-    /// tree.add_leaf("Leaf");
-    /// assert_eq!("Leaf", tree.peek_string());
-    /// tree.add_leaf("Leaf 2");
-    /// assert_eq!("Leaf\nLeaf 2", tree.peek_string());
+    /// let _branch = tree.add_branch("Download");
+    /// let children: Vec<_> = (1..=2).map(|_| tree.fork()).collect();
+    /// tree.add_leaf("Waiting");
+    /// let handles: Vec<_> = children
+    ///     .into_iter()
+    ///     .enumerate()
+    ///     .map(|(i, child)| {
+    ///         std::thread::spawn(move || {
+    ///             child.add_leaf(&format!("Part {}", i + 1));
+    ///             child
+    ///         })
+    ///     })
+    ///     .collect();
+    /// for handle in handles {
+    ///     tree.join(handle.join().unwrap());
+    /// }
+    /// tree.add_leaf("Done");
+    /// assert_eq!(
+    ///     "Download\n├╼ Waiting\n├╼ Part 1\n├╼ Part 2\n└╼ Done",
+    ///     tree.peek_string()
+    /// );
     /// ```
-    pub fn peek_string(&self) -> String {
-        self.0.lock().unwrap().peek_string()
+    pub fn fork(&self) -> TreeBuilder {
+        let child = self.0.lock().fork();
+        TreeBuilder::from_base(child)
     }
 
-    /// Returns the tree as a string and clears the tree.
+    /// Adds the nodes of `child` to the branch that was current when it was created with
+    /// [fork](TreeBuilder::fork), after any nodes added there since, without moving the cursor.
+    ///
+    /// The nodes are added to the current branch instead if `child` wasn't forked from this tree,
+    /// or if this tree has been cleared since.
+    /// Nothing is added if this tree is disabled, and nodes whose level isn't enabled
+    /// by this tree are left out.
+    pub fn join(&self, child: TreeBuilder) {
+        if !self.is_enabled() {
+            return;
+        }
+        let (tree, fork_point) = {
+            let x = child.0.lock();
+            (x.with_data(|x| x.clone()), x.fork_point())
+        };
+        self.0.lock().join(tree, fork_point);
+    }
+
+    /// Adds nodes to the current branch from the text of each node and its depth,
+    /// relative to the current branch, in depth-first order.
+    /// Nothing is added if the depths are invalid, or if the tree is disabled.
+    /// See [Tree::from_depths].
     ///
     /// # Example
     ///
     /// ```
     /// use debug_tree::TreeBuilder;
     /// let tree = TreeBuilder::new();
-    /// tree.add_leaf("Leaf");
-    /// assert_eq!("Leaf", tree.string());
-    /// tree.add_leaf("Leaf 2");
-    /// assert_eq!("Leaf 2", tree.string());
+    /// tree.add_leaf("Log");
+    /// let _branch = tree.enter_scoped();
+    /// let lines = "main\n  parse\n  run\n    step";
+    /// tree.extend_depths(lines.lines().map(|x| {
+    ///     let text = x.trim_start();
+    ///     ((x.len() - text.len()) / 2, text)
+    /// })).unwrap();
+    /// assert_eq!("\
+    /// Log
+    /// └╼ main
+    ///   ├╼ parse
+    ///   └╼ run
+    ///     └╼ step", tree.peek_string());
     /// ```
-    pub fn string(&self) -> String {
-        self.0.lock().unwrap().string()
+    pub fn extend_depths<I, S>(&self, nodes: I) -> Result<(), String>
+    where
+        I: IntoIterator<Item = (usize, S)>,
+        S: Into<String>,
+    {
+        self.add_tree(&Tree::from_depths(nodes)?);
+        Ok(())
     }
 
-    /// Writes the tree to file without clearing.
+    /// Adds a leaf to the current branch whose text is `cells` separated by tabs.
+    /// With [TreeConfig::align_columns], the cells of sibling rows are aligned into columns.
     ///
     /// # Example
     ///
     /// ```
-    /// use debug_tree::TreeBuilder;
-    /// use std::fs::{read_to_string, create_dir};
-    /// use std::io::Read;
+    /// use debug_tree::{TreeBuilder, TreeConfig};
     /// let tree = TreeBuilder::new();
-    /// create_dir("test_out").ok();
-    /// tree.add_leaf("Leaf");
-    /// assert_eq!(tree.peek_string(), "Leaf");
-    /// tree.peek_write("test_out/peek_write.txt");
-    /// assert_eq!(read_to_string("test_out/peek_write.txt").unwrap(), "Leaf");
-    /// assert_eq!(tree.peek_string(), "Leaf");
+    /// tree.set_config_override(TreeConfig::new().align_columns());
+    /// tree.add_row(&["id", "name", "size"]);
+    /// tree.add_row(&["1", "config.toml", "2 KiB"]);
+    /// tree.add_row(&["22", "data.bin", "512 KiB"]);
+    /// assert_eq!("\
+    /// id  name         size
+    /// 1   config.toml  2 KiB
+    /// 22  data.bin     512 KiB", &tree.peek_string());
     /// ```
-    pub fn peek_write(&self, path: &str) -> std::io::Result<()> {
-        let mut file = File::create(path)?;
-        file.write_all(self.peek_string().as_bytes())
+    pub fn add_row(&self, cells: &[&str]) {
+        self.add_leaf(&cells.join("\t"));
     }
 
-    /// Writes the tree to file without clearing.
+    /// Adds a leaf to current branch with the given text, `text`,
+    /// if `level` is enabled by [set_level](TreeBuilder::set_level).
     ///
     /// # Example
     ///
     /// ```
-    /// use debug_tree::TreeBuilder;
-    /// use std::io::Read;
-    /// use std::fs::{read_to_string, create_dir};
+    /// use debug_tree::{TreeBuilder, Level};
     /// let tree = TreeBuilder::new();
-    /// create_dir("test_out").ok();
-    /// tree.add_leaf("Leaf");
-    /// assert_eq!(tree.peek_string(), "Leaf");
-    /// tree.write("test_out/write.txt");
-    /// assert_eq!(read_to_string("test_out/write.txt").unwrap(), "Leaf");
-    /// assert_eq!(tree.peek_string(), "");
+    /// tree.set_level(Level::Info);
+    /// tree.add_leaf_at(Level::Warn, "Disk almost full");
+    /// tree.add_leaf_at(Level::Debug, "Checked 12 disks");
+    /// assert_eq!("Disk almost full", tree.peek_string());
     /// ```
-    pub fn write(&self, path: &str) -> std::io::Result<()> {
-        let mut file = File::create(path)?;
-        file.write_all(self.string().as_bytes())
+    pub fn add_leaf_at(&self, level: Level, text: &str) {
+        let mut x = self.0.lock();
+        if x.is_level_enabled(level) {
+            if let Some(id) = x.add_leaf(text) {
+                x.set_node_level(&id, level);
+            }
+        }
     }
 
-    /// Clears the tree.
+    /// Adds a new branch with text, `text`, if `level` is enabled by
+    /// [set_level](TreeBuilder::set_level), and returns a `ScopedBranch`.
+    /// If the level is not enabled, no branch is entered.
     ///
     /// # Example
     ///
     /// ```
-    /// use debug_tree::TreeBuilder;
+    /// use debug_tree::{TreeBuilder, Level};
     /// let tree = TreeBuilder::new();
-    /// tree.add_leaf("Leaf");
-    /// assert_eq!("Leaf", tree.peek_string());
-    /// tree.clear();
-    /// assert_eq!("", tree.peek_string());
+    /// tree.set_level(Level::Debug);
+    /// {
+    ///     let _branch = tree.add_branch_at(Level::Trace, "Hidden");
+    ///     tree.add_leaf_at(Level::Debug, "Shown");
+    /// }
+    /// assert_eq!("Shown", tree.peek_string());
     /// ```
-    pub fn clear(&self) {
-        self.0.lock().unwrap().clear()
+    pub fn add_branch_at(&self, level: Level, text: &str) -> ScopedBranch {
+        if self.is_level_enabled(level) {
+            self.add_leaf_at(level, text);
+            ScopedBranch::new(self.clone())
+        } else {
+            ScopedBranch::none()
+        }
     }
 
-    /// Sets the enabled state of the tree.
+    /// Adds a leaf to current branch with the given text, `text`,
+    /// recording `target` as the subsystem that added it.
+    /// The macros use the module path of the caller as the target.
     ///
-    /// If not enabled, the tree will not be modified by adding leaves or branches.
-    /// Additionally, if called using the `add_`... macros, arguments will not be processed.
-    /// This is particularly useful for suppressing output in production, with very little overhead.
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::{TreeBuilder, TreeConfig};
+    /// let tree = TreeBuilder::new();
+    /// tree.add_leaf_target("app::net", "Connected");
+    /// tree.add_leaf_target("app::db", "Queried");
+    /// tree.set_config_override(TreeConfig::new().only_target("app::net"));
+    /// assert_eq!("Connected", tree.peek_string());
+    /// ```
+    pub fn add_leaf_target(&self, target: &'static str, text: &str) {
+        self.add_leaf_target_at(Level::Info, target, text)
+    }
+
+    /// Adds a leaf to current branch with the given text, `text`, recording `target` as the
+    /// subsystem that added it, if `level` is enabled by [set_level](TreeBuilder::set_level).
+    /// This is what the severity macros, such as [tree_warn](crate::tree_warn), use.
     ///
     /// # Example
+    ///
     /// ```
-    /// #[macro_use]
-    /// use debug_tree::{TreeBuilder, add_leaf_to};
-    /// let mut tree = TreeBuilder::new();
-    /// tree.add_leaf("Leaf 1");
-    /// tree.set_enabled(false);
-    /// add_leaf_to!(tree, "Leaf 2");
-    /// tree.set_enabled(true);
-    /// add_leaf_to!(tree, "Leaf 3");
-    /// assert_eq!("Leaf 1\nLeaf 3", tree.peek_string());
+    /// use debug_tree::{TreeBuilder, Level};
+    /// let tree = TreeBuilder::new();
+    /// tree.add_leaf_target_at(Level::Warn, "app::net", "Retrying");
+    /// assert_eq!(Level::Warn, tree.snapshot().children[0].level);
+    /// assert_eq!(Some("app::net"), tree.snapshot().children[0].target);
     /// ```
-    pub fn set_enabled(&self, enabled: bool) {
-        self.0.lock().unwrap().set_enabled(enabled);
+    pub fn add_leaf_target_at(&self, level: Level, target: &'static str, text: &str) {
+        self.add_leaf_target_text(level, target, Cow::Owned(text.to_string()))
     }
 
-    /// Returns the enabled state of the tree.
+    /// Adds a leaf at `level` with `target`, storing borrowed text without copying it.
+    /// Used by the macros, which pass string literals as borrowed text.
+    #[doc(hidden)]
+    pub fn add_leaf_target_text(
+        &self,
+        level: Level,
+        target: &'static str,
+        text: Cow<'static, str>,
+    ) {
+        let mut x = self.0.lock();
+        if x.is_level_enabled(level) {
+            if let Some(id) = x.add_leaf_text(text) {
+                x.set_node_level(&id, level);
+                x.set_target(&id, target);
+            }
+        }
+    }
+
+    /// Adds a leaf to current branch with the given text, `text`, which is stored without
+    /// copying if it is a `&'static str`, or moved into the tree if it is a `String`.
     ///
     /// # Example
+    ///
     /// ```
     /// use debug_tree::TreeBuilder;
-    /// let mut tree = TreeBuilder::new();
-    /// assert_eq!(true, tree.is_enabled());
-    /// tree.set_enabled(false);
-    /// assert_eq!(false, tree.is_enabled());
+    /// let tree = TreeBuilder::new();
+    /// tree.add_leaf_text("Started");
+    /// tree.add_leaf_text(format!("Loaded {} rows", 12));
+    /// assert_eq!("Started\nLoaded 12 rows", tree.peek_string());
     /// ```
-    pub fn is_enabled(&self) -> bool {
-        self.0.lock().unwrap().is_enabled()
+    pub fn add_leaf_text<T: Into<Cow<'static, str>>>(&self, text: T) {
+        let mut x = self.0.lock();
+        if x.is_level_enabled(Level::Info) {
+            x.add_leaf_text(text.into());
+        }
     }
-}
 
-pub trait AsTree {
-    fn as_tree(&self) -> TreeBuilder;
-    fn is_tree_enabled(&self) -> bool {
-        self.as_tree().is_enabled()
+    /// Adds a new branch with text, `text`, recording `target` as the subsystem that added it,
+    /// and returns a `ScopedBranch`.
+    pub fn add_branch_target(&self, target: &'static str, text: &str) -> ScopedBranch {
+        self.add_branch_target_text(target, Cow::Owned(text.to_string()))
     }
-}
 
-impl AsTree for TreeBuilder {
-    fn as_tree(&self) -> TreeBuilder {
-        self.clone()
+    /// Adds a new branch with `target`, storing borrowed text without copying it.
+    /// Used by the macros, which pass string literals as borrowed text.
+    #[doc(hidden)]
+    pub fn add_branch_target_text(
+        &self,
+        target: &'static str,
+        text: Cow<'static, str>,
+    ) -> ScopedBranch {
+        if self.is_level_enabled(Level::Info) {
+            self.add_leaf_target_text(Level::Info, target, text);
+            ScopedBranch::new(self.clone())
+        } else {
+            ScopedBranch::none()
+        }
     }
-}
+
+    /// Replaces the text of every node in the tree, including the root text, with the result of
+    /// `f`. See [Tree::map_text].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// {
+    ///     let _branch = tree.add_branch("Request");
+    ///     tree.add_leaf("Response");
+    /// }
+    /// tree.transform(|x| x.to_uppercase());
+    /// assert_eq!("REQUEST\n└╼ RESPONSE", tree.peek_string());
+    /// ```
+    pub fn transform<F: FnMut(&str) -> String>(&self, f: F) {
+        self.0.lock().map_text(f)
+    }
+
+    /// Permanently removes the nodes for which `keep` returns false, along with their descendants,
+    /// so that the memory of large irrelevant sections is freed. See [Tree::retain].
+    /// The current branch, its ancestors and the most recently added node are kept.
+    /// Existing `NodeId`s are invalidated.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// for i in 0..3 {
+    ///     let _branch = tree.add_branch(&format!("Poll {}", i));
+    ///     tree.add_leaf(if i == 1 { "Changed" } else { "Unchanged" });
+    /// }
+    /// tree.retain(|x| x.text.as_deref() != Some("Unchanged"));
+    /// assert_eq!("Poll 0\nPoll 1\n└╼ Changed\nPoll 2", tree.peek_string());
+    /// ```
+    pub fn retain<F: FnMut(&Tree) -> bool>(&self, keep: F) {
+        self.0.lock().retain(false, keep)
+    }
+
+    /// Permanently removes the nodes for which `keep` returns false, moving their children up to
+    /// take their place. See [Tree::retain_nodes].
+    /// The current branch, its ancestors and the most recently added node are kept.
+    /// Existing `NodeId`s are invalidated.
+    pub fn retain_nodes<F: FnMut(&Tree) -> bool>(&self, keep: F) {
+        self.0.lock().retain(true, keep)
+    }
+
+    /// Returns the identifiers of the nodes that match `pattern`, in depth-first order.
+    /// See [Tree::query] for the pattern syntax.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// {
+    ///     let _a = tree.add_branch("parse_expr");
+    ///     tree.add_leaf("unexpected token error");
+    /// }
+    /// let ids = tree.query("**/*error");
+    /// assert_eq!(1, ids.len());
+    /// assert_eq!("0/0", ids[0].to_string());
+    /// assert_eq!(Some("unexpected token error".to_string()), tree.node_text(&ids[0]));
+    /// ```
+    pub fn query(&self, pattern: &str) -> Vec<NodeId> {
+        self.0.lock().query(pattern)
+    }
+
+    /// Removes the nodes added by `target` or its submodules, along with their descendants.
+    /// The current branch, its ancestors and the most recently added node are kept.
+    /// Existing `NodeId`s are invalidated.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// {
+    ///     let _branch = tree.add_branch_target("app::cache", "Cache lookup");
+    ///     tree.add_leaf_target("app", "Miss");
+    /// }
+    /// tree.add_leaf_target("app", "Loaded");
+    /// tree.prune_target("app::cache");
+    /// assert_eq!("Loaded", tree.peek_string());
+    /// ```
+    pub fn prune_target(&self, target: &str) {
+        self.0.lock().retain(false, |x| match x.target {
+            Some(x) => !filter::module_matches(target, x),
+            None => true,
+        });
+    }
+
+    /// Adds a leaf to current branch with the given text, `text`, and returns its identifier.
+    /// Returns `None` if the tree is not enabled.
+    ///
+    /// # Arguments
+    /// * `text` - A string slice to use as the newly added leaf's text.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// let id = tree.add_leaf_id("Connecting...").unwrap();
+    /// tree.add_leaf("Sending request");
+    /// tree.set_node_text(&id, "Connected");
+    /// assert_eq!(Some("Connected".to_string()), tree.node_text(&id));
+    /// assert_eq!("Connected\nSending request", tree.string());
+    /// assert_eq!(None, tree.node_text(&id));
+    /// ```
+    pub fn add_leaf_id(&self, text: &str) -> Option<NodeId> {
+        let mut x = self.0.lock();
+        if x.is_level_enabled(Level::Info) {
+            x.add_leaf(text)
+        } else {
+            None
+        }
+    }
+
+    /// Adds a top-level leaf, wherever the cursor currently is, and returns its identifier.
+    /// The cursor is left where it was, so this records a milestone without leaving the current
+    /// branch. Returns `None` if the tree is not enabled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// {
+    ///     let _request = tree.add_branch("Request");
+    ///     let _query = tree.add_branch("Query");
+    ///     tree.add_root_leaf("Cache warmed");
+    ///     tree.add_leaf("Rows: 3");
+    /// }
+    /// assert_eq!("Request\n└╼ Query\n  └╼ Rows: 3\nCache warmed", tree.peek_string());
+    /// ```
+    pub fn add_root_leaf(&self, text: &str) -> Option<NodeId> {
+        let mut x = self.0.lock();
+        if x.is_level_enabled(Level::Info) {
+            x.add_root_leaf_text(Cow::Owned(text.to_string()))
+        } else {
+            None
+        }
+    }
+
+    /// Adds a top-level branch, wherever the cursor currently is, and moves the cursor into it
+    /// until the returned `ScopedCursor` is dropped, when the cursor moves back to where it was.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// let _request = tree.add_branch("Request");
+    /// {
+    ///     let _config = tree.add_root_branch("Config reloaded");
+    ///     tree.add_leaf("timeout = 30s");
+    /// }
+    /// tree.add_leaf("Response");
+    /// assert_eq!(
+    ///     "Request\n└╼ Response\nConfig reloaded\n└╼ timeout = 30s",
+    ///     tree.peek_string()
+    /// );
+    /// ```
+    pub fn add_root_branch(&self, text: &str) -> ScopedCursor {
+        let mut x = self.0.lock();
+        if !x.is_level_enabled(Level::Info) {
+            return ScopedCursor::none();
+        }
+        let cursor = x
+            .add_root_leaf_text(Cow::Owned(text.to_string()))
+            .and_then(|id| x.enter_node(&id));
+        ScopedCursor::new(self.clone(), cursor)
+    }
+
+    /// Moves the cursor into the node identified by `id`, so that nodes are added after its
+    /// existing children, until the returned `ScopedCursor` is dropped, when the cursor moves
+    /// back to where it was. This lets late results, such as those of async tasks, be added to
+    /// a branch that was already left.
+    /// Returns `None` if the node no longer exists.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// let id = tree.add_leaf_id("Fetch").unwrap();
+    /// tree.add_leaf("Render");
+    /// {
+    ///     let _fetch = tree.enter_node(&id).unwrap();
+    ///     tree.add_leaf("200 OK");
+    /// }
+    /// tree.add_leaf("Done");
+    /// assert_eq!("Fetch\n└╼ 200 OK\nRender\nDone", tree.peek_string());
+    /// ```
+    pub fn enter_node(&self, id: &NodeId) -> Option<ScopedCursor> {
+        let cursor = self.0.lock().enter_node(id)?;
+        Some(ScopedCursor::new(self.clone(), Some(cursor)))
+    }
+
+    /// Moves the cursor into the node at `path`, like [enter_node](TreeBuilder::enter_node).
+    /// Returns `None` if there is no node at `path`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// tree.add_leaf("a");
+    /// tree.add_leaf("b");
+    /// {
+    ///     let _a = tree.enter_path("0").unwrap();
+    ///     tree.add_leaf("a.1");
+    /// }
+    /// assert!(tree.enter_path("5").is_none());
+    /// assert_eq!("a\n└╼ a.1\nb", tree.peek_string());
+    /// ```
+    pub fn enter_path<P: TreePath + ?Sized>(&self, path: &P) -> Option<ScopedCursor> {
+        let path = path.indices()?;
+        let cursor = self.0.lock().enter_path(&path)?;
+        Some(ScopedCursor::new(self.clone(), Some(cursor)))
+    }
+
+    /// Adds a leaf with the text, `label`, followed by the time elapsed since the previous
+    /// checkpoint in the current branch, or since the branch was entered.
+    /// Returns the elapsed time, or zero if the tree is not enabled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// let _branch = tree.add_branch("Work");
+    /// std::thread::sleep(std::time::Duration::from_millis(5));
+    /// let elapsed = tree.checkpoint("Step 1");
+    /// assert!(elapsed >= std::time::Duration::from_millis(5));
+    /// assert!(tree.peek_string().starts_with("Work\n└╼ Step 1: "));
+    /// ```
+    pub fn checkpoint(&self, label: &str) -> std::time::Duration {
+        let mut x = self.0.lock();
+        if x.is_level_enabled(Level::Info) {
+            x.checkpoint(label)
+        } else {
+            std::time::Duration::default()
+        }
+    }
+
+    /// Adds a leaf for `error` to current branch,
+    /// with the chain of its [source](std::error::Error::source)s nested beneath it.
+    /// The error is added at `Level::Error`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// use std::fmt;
+    /// #[derive(Debug)]
+    /// struct ConfigError(std::io::Error);
+    /// impl fmt::Display for ConfigError {
+    ///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    ///         write!(f, "Could not load config")
+    ///     }
+    /// }
+    /// impl std::error::Error for ConfigError {
+    ///     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    ///         Some(&self.0)
+    ///     }
+    /// }
+    /// let tree = TreeBuilder::new();
+    /// let err = ConfigError(std::io::Error::new(std::io::ErrorKind::NotFound, "File not found"));
+    /// tree.add_error(&err);
+    /// tree.add_leaf("Using defaults");
+    /// assert_eq!("\
+    /// Could not load config
+    /// └╼ File not found
+    /// Using defaults", &tree.string());
+    /// ```
+    pub fn add_error(&self, error: &dyn std::error::Error) {
+        let mut x = self.0.lock();
+        if x.is_level_enabled(Level::Error) {
+            if let Some(id) = x.add_leaf(&error.to_string()) {
+                x.set_node_level(&id, Level::Error);
+            }
+            let mut depth = 0;
+            let mut source = error.source();
+            while let Some(err) = source {
+                x.enter();
+                if let Some(id) = x.add_leaf(&err.to_string()) {
+                    x.set_node_level(&id, Level::Error);
+                }
+                depth += 1;
+                source = err.source();
+            }
+            for _ in 0..depth {
+                x.exit();
+            }
+        }
+    }
+
+    /// Returns the text of the node identified by `id`.
+    /// Returns `None` if the node has no text or no longer exists.
+    pub fn node_text(&self, id: &NodeId) -> Option<String> {
+        self.0.lock().node_text(id)
+    }
+
+    /// Returns the text of the most recently added node, without rendering the tree.
+    /// Returns `None` if nothing was added since the tree was last cleared.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// assert_eq!(None, tree.last_leaf_text());
+    /// {
+    ///     let _branch = tree.add_branch("Branch");
+    ///     tree.add_leaf("Child");
+    /// }
+    /// assert_eq!(Some("Child".to_string()), tree.last_leaf_text());
+    /// ```
+    pub fn last_leaf_text(&self) -> Option<String> {
+        self.0.lock().last_leaf_text()
+    }
+
+    /// Returns the text of the branch that nodes are currently added to.
+    /// Returns `None` at the top level.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// assert_eq!(None, tree.current_branch_text());
+    /// let _branch = tree.add_branch("Branch");
+    /// assert_eq!(Some("Branch".to_string()), tree.current_branch_text());
+    /// tree.add_leaf("Child");
+    /// assert_eq!(Some("Branch".to_string()), tree.current_branch_text());
+    /// ```
+    pub fn current_branch_text(&self) -> Option<String> {
+        self.0.lock().current_branch_text()
+    }
+
+    /// Replaces the text of the node identified by `id`.
+    /// Returns false if the node no longer exists.
+    pub fn set_node_text(&self, id: &NodeId, text: &str) -> bool {
+        self.0.lock().set_node_text(id, text)
+    }
+
+    /// Sets the annotation of the node identified by `id`, such as a duration or a size.
+    /// Annotations are rendered right-aligned in a column at the end of the lines.
+    /// Returns false if the node no longer exists.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// let id = tree.add_leaf_id("Load").unwrap();
+    /// {
+    ///     let _branch = tree.enter_scoped();
+    ///     tree.add_leaf("config.toml");
+    ///     tree.annotate("2 KiB");
+    ///     tree.add_leaf("data.bin");
+    ///     tree.annotate("512 KiB");
+    /// }
+    /// tree.set_annotation(&id, "14ms");
+    /// assert_eq!("\
+    /// Load              14ms
+    /// ├╼ config.toml   2 KiB
+    /// └╼ data.bin    512 KiB", &tree.peek_string());
+    /// ```
+    pub fn set_annotation(&self, id: &NodeId, annotation: &str) -> bool {
+        self.0.lock().set_annotation(id, annotation)
+    }
+
+    /// Adds a branch with the text `text`, calls `f`, and adds each line that `f` wrote to stdout
+    /// or stderr as a leaf under the branch. Returns the result of `f`.
+    ///
+    /// The standard streams of the whole process are redirected while `f` runs, so output from
+    /// other threads is captured too, and only one capture runs at a time.
+    /// Calling `capture_output` within `f` doesn't capture anything: the output is added to
+    /// the outer branch instead.
+    /// Output is only captured on Unix; elsewhere, `f` is called without capturing anything.
+    /// Output written with `print!` is not captured within tests, which capture it themselves.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// use std::io::Write;
+    /// let tree = TreeBuilder::new();
+    /// let answer = tree.capture_output("Chatty library", || {
+    ///     let mut out = std::io::stdout();
+    ///     writeln!(out, "Loading...").unwrap();
+    ///     writeln!(std::io::stderr(), "warning: deprecated").unwrap();
+    ///     42
+    /// });
+    /// assert_eq!(42, answer);
+    /// # #[cfg(unix)]
+    /// assert_eq!(
+    ///     "Chatty library\n├╼ Loading...\n└╼ warning: deprecated",
+    ///     tree.peek_string()
+    /// );
+    /// ```
+    pub fn capture_output<R, F: FnOnce() -> R>(&self, text: &str, f: F) -> R {
+        let _branch = self.add_branch(text);
+        let (result, output) = capture::capture(f);
+        for line in output.lines() {
+            self.add_leaf(line);
+        }
+        result
+    }
+
+    /// Mounts `tree` as a top-level branch named `name`, rendered after the nodes of this tree,
+    /// so that independently built subsystem trees are flushed as one combined report.
+    /// The mounted tree is rendered as it is at the time this tree is rendered, unless it is
+    /// empty, and is cleared whenever this tree is cleared.
+    ///
+    /// Returns false without mounting `tree` if it is this tree, or if it mounts this tree itself.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::tree;
+    /// tree("app").add_leaf("Started");
+    /// assert!(tree("app").mount("parser", &tree("parser")));
+    /// tree("parser").add_leaf("Parsed 3 files");
+    /// assert_eq!("Started\nparser\n└╼ Parsed 3 files", tree("app").string());
+    /// assert_eq!("", tree("parser").peek_string());
+    /// assert!(!tree("parser").mount("app", &tree("app")));
+    /// ```
+    pub fn mount(&self, name: &str, tree: &TreeBuilder) -> bool {
+        internal::TreeBuilderBase::mount(&self.0, name, &tree.0)
+    }
+
+    /// Reads lines from `reader` and adds each as a leaf to the current branch,
+    /// so the output of an external command or a log file can be folded into the tree.
+    /// Returns the number of lines read, or the first error reading them, in which case
+    /// the lines before the error have already been added.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// let log = "connected\r\nsent 3 bytes\n";
+    /// assert_eq!(2, tree.append_from_reader(log.as_bytes()).unwrap());
+    /// assert_eq!("connected\nsent 3 bytes", tree.peek_string());
+    /// ```
+    pub fn append_from_reader<R: BufRead>(&self, reader: R) -> std::io::Result<usize> {
+        let mut n = 0;
+        for line in reader.lines() {
+            self.add_leaf(&line?);
+            n += 1;
+        }
+        Ok(n)
+    }
+
+    /// Adds a branch with the text `text`, and adds each line read from `reader` as a leaf under
+    /// it. See [append_from_reader](TreeBuilder::append_from_reader).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// let stdout = b"hello\n";
+    /// tree.append_branch_from_reader("echo hello", &stdout[..]).unwrap();
+    /// assert_eq!("echo hello\n└╼ hello", tree.peek_string());
+    /// ```
+    pub fn append_branch_from_reader<R: BufRead>(
+        &self,
+        text: &str,
+        reader: R,
+    ) -> std::io::Result<usize> {
+        let _branch = self.add_branch(text);
+        self.append_from_reader(reader)
+    }
+
+    /// Returns a writer that accumulates formatted text, which is added as a leaf to the current
+    /// branch when the writer is dropped. This lets code that formats with `write!` target the
+    /// tree directly.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// use std::fmt::Write;
+    /// let tree = TreeBuilder::new();
+    /// {
+    ///     let mut leaf = tree.leaf_writer();
+    ///     write!(leaf, "Matrix:").unwrap();
+    ///     for row in [[1, 0], [0, 1]] {
+    ///         write!(leaf, "\n{:?}", row).unwrap();
+    ///     }
+    /// }
+    /// assert_eq!("Matrix:\n[1, 0]\n[0, 1]", tree.peek_string());
+    /// ```
+    pub fn leaf_writer(&self) -> leaf_writer::LeafWriter {
+        leaf_writer::LeafWriter::new(self.clone())
+    }
+
+    /// Adds the `tracing` events emitted on the current thread as leaves to the current branch,
+    /// until the returned guard is dropped. Each event is added with its level and target,
+    /// and its text is the event's message followed by its other fields.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// tracing::info!("Not captured");
+    /// {
+    ///     let _branch = tree.add_branch("Connect");
+    ///     let _capture = tree.capture_tracing();
+    ///     tracing::info!("Resolved host");
+    ///     tracing::warn!(attempt = 2, "Retrying");
+    /// }
+    /// tracing::info!("Not captured either");
+    /// assert_eq!(
+    ///     "Connect\n├╼ Resolved host\n└╼ Retrying attempt=2",
+    ///     tree.peek_string()
+    /// );
+    /// ```
+    #[cfg(feature = "tracing")]
+    pub fn capture_tracing(&self) -> trace_capture::TracingCapture {
+        trace_capture::TracingCapture::new(self.clone())
+    }
+
+    /// Appends `text` to the text of the most recently added node of the current branch,
+    /// so a leaf can be built up across several statements.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// tree.add_leaf("Connecting…");
+    /// tree.append_to_last(" ok (12ms)");
+    /// assert_eq!("Connecting… ok (12ms)", tree.peek_string());
+    /// ```
+    pub fn append_to_last(&self, text: &str) {
+        self.0.lock().append_to_last(text)
+    }
+
+    /// Sets the annotation of the most recently added node.
+    /// See [set_annotation](TreeBuilder::set_annotation).
+    pub fn annotate(&self, annotation: &str) {
+        self.0.lock().annotate(annotation)
+    }
+
+    /// Attaches a large payload, such as a request body, to the most recently added node.
+    /// Terminal output only shows its size, while [peek_json](TreeBuilder::peek_json) and the
+    /// HTML export include its content.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// tree.add_leaf("POST /upload");
+    /// tree.attach("body", &"x".repeat(4300));
+    /// assert_eq!("POST /upload [attachment: 4.2 KiB]", tree.peek_string());
+    /// assert!(tree.peek_json().contains(&"x".repeat(4300)));
+    /// ```
+    pub fn attach(&self, name: &str, content: &str) {
+        self.0.lock().attach(name, content)
+    }
+
+    /// Attaches `value` to the node identified by `id`,
+    /// replacing any existing metadata of the same type.
+    /// Metadata is not rendered, but is available when visiting the tree.
+    /// Returns false if the node no longer exists.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// struct Bytes(usize);
+    /// let tree = TreeBuilder::new();
+    /// let id = tree.add_leaf_id("Read file").unwrap();
+    /// tree.set_metadata(&id, Bytes(512));
+    /// tree.add_leaf("Closed file");
+    /// let mut total = 0;
+    /// tree.visit(|node, _depth| {
+    ///     if let Some(Bytes(n)) = node.metadata::<Bytes>() {
+    ///         total += n;
+    ///     }
+    /// });
+    /// assert_eq!(512, total);
+    /// assert_eq!("Read file\nClosed file", tree.peek_string());
+    /// ```
+    pub fn set_metadata<T: std::any::Any + Send + Sync>(&self, id: &NodeId, value: T) -> bool {
+        self.0.lock().set_metadata(id, value)
+    }
+
+    /// Returns a copy of the tree that has been built so far.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// tree.add_leaf("Leaf");
+    /// assert_eq!(Some("Leaf"), tree.snapshot().children[0].text.as_deref());
+    /// ```
+    pub fn snapshot(&self) -> Tree {
+        self.0.lock().with_data(|x| x.clone())
+    }
+
+    /// Calls `f` with each node of the tree and its depth, in depth-first order.
+    /// Top-level nodes have a depth of 0.
+    ///
+    /// The tree is locked while visiting, so `f` must not modify this tree.
+    pub fn visit<F: FnMut(&Tree, usize)>(&self, f: F) {
+        self.0.lock().with_data(|x| x.visit(f))
+    }
+
+    /// Calls `f` with the id and text of each node added to this tree, as it is added.
+    ///
+    /// Observers are kept when the tree is cleared.
+    /// The tree is locked while `f` is called, so `f` must not modify this tree.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// use std::sync::{Arc, Mutex};
+    /// let tree = TreeBuilder::new();
+    /// let added = Arc::new(Mutex::new(vec![]));
+    /// let log = added.clone();
+    /// tree.on_node_added(move |id, text| log.lock().unwrap().push((id.path().to_vec(), text.to_string())));
+    /// tree.add_leaf("1");
+    /// tree.enter();
+    /// tree.add_leaf("1.1");
+    /// assert_eq!(
+    ///     vec![(vec![0], "1".to_string()), (vec![0, 0], "1.1".to_string())],
+    ///     *added.lock().unwrap()
+    /// );
+    /// ```
+    pub fn on_node_added<F: Fn(&NodeId, &str) + Send + Sync + 'static>(&self, f: F) {
+        self.0.lock().observers.node_added.push(Arc::new(f));
+    }
+
+    /// Returns a channel that receives a [TreeEvent] for each change made to this tree,
+    /// so the tree can be reconstructed or displayed elsewhere, such as on another thread.
+    ///
+    /// Subscriptions are kept when the tree is cleared, and end when the receiver is dropped.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::{TreeBuilder, TreeEvent};
+    /// let tree = TreeBuilder::new();
+    /// let events = tree.subscribe();
+    /// tree.add_leaf("1");
+    /// tree.enter();
+    /// tree.add_leaf("1.1");
+    /// tree.exit();
+    /// tree.clear();
+    /// assert_eq!(
+    ///     vec![
+    ///         TreeEvent::LeafAdded { path: vec![0], text: "1".to_string() },
+    ///         TreeEvent::Entered,
+    ///         TreeEvent::LeafAdded { path: vec![0, 0], text: "1.1".to_string() },
+    ///         TreeEvent::Exited,
+    ///         TreeEvent::Cleared,
+    ///     ],
+    ///     events.try_iter().collect::<Vec<_>>()
+    /// );
+    /// ```
+    pub fn subscribe(&self) -> std::sync::mpsc::Receiver<TreeEvent> {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        self.0.lock().observers.subscribers.push(sender);
+        receiver
+    }
+
+    /// Starts recording the changes made to this tree as [TreeEvent]s,
+    /// discarding any previous recording.
+    ///
+    /// A recording can be replayed into another tree to render it later, to use as a test
+    /// fixture, or to inspect the tree as it was after any number of changes.
+    /// Only the structure and text are recorded, not levels, targets, annotations or metadata.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// tree.start_recording();
+    /// tree.add_leaf("1");
+    /// {
+    ///     let _branch = tree.enter_scoped();
+    ///     tree.add_leaf("1.1");
+    ///     tree.add_leaf("1.2");
+    /// }
+    /// let events = tree.stop_recording();
+    /// assert_eq!(tree.peek_string(), TreeBuilder::from_events(&events).peek_string());
+    /// // The tree after the first three changes
+    /// assert_eq!("1\n└╼ 1.1", TreeBuilder::from_events(&events[..3]).peek_string());
+    /// ```
+    pub fn start_recording(&self) {
+        self.0.lock().observers.recording = Some(vec![]);
+    }
+
+    /// Stops recording and returns the events recorded since [start_recording](TreeBuilder::start_recording).
+    pub fn stop_recording(&self) -> Vec<TreeEvent> {
+        self.0.lock().observers.recording.take().unwrap_or_default()
+    }
+
+    /// Applies `events` to this tree, in order.
+    pub fn replay(&self, events: &[TreeEvent]) {
+        for event in events {
+            match event {
+                TreeEvent::LeafAdded { text, .. } => self.add_leaf(text),
+                TreeEvent::Entered => self.enter(),
+                TreeEvent::Exited => {
+                    self.exit();
+                }
+                TreeEvent::Cleared => self.clear(),
+            }
+        }
+    }
+
+    /// Creates a tree by applying `events` to a new tree.
+    pub fn from_events(events: &[TreeEvent]) -> TreeBuilder {
+        let tree = TreeBuilder::new();
+        tree.replay(events);
+        tree
+    }
+
+    /// Appends each change made to this tree to the file at `path` as it happens,
+    /// so a trace can be recovered even if the process crashes.
+    /// The file is created if it does not exist.
+    ///
+    /// The log can be loaded with [load_event_log](TreeBuilder::load_event_log),
+    /// or read with [read_log](crate::event::read_log).
+    /// See [start_recording](TreeBuilder::start_recording) for what is recorded.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let path = std::env::temp_dir().join("debug_tree_event_log_example.log");
+    /// let _ = std::fs::remove_file(&path);
+    /// let tree = TreeBuilder::new();
+    /// tree.log_events_to(&path).unwrap();
+    /// tree.add_leaf("Started");
+    /// tree.enter();
+    /// tree.add_leaf("Two\nlines");
+    /// assert_eq!(tree.peek_string(), TreeBuilder::load_event_log(&path).unwrap().peek_string());
+    /// ```
+    pub fn log_events_to<P: AsRef<std::path::Path>>(&self, path: P) -> std::io::Result<()> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        self.0
+            .lock()
+            .observers
+            .logs
+            .push(Arc::new(Mutex::new(file)));
+        Ok(())
+    }
+
+    /// Creates a tree from JSON Lines, with one builder operation per line.
+    /// See [parse_jsonl](crate::event::parse_jsonl) for the format.
+    pub fn from_jsonl(s: &str) -> Result<TreeBuilder, String> {
+        Ok(TreeBuilder::from_events(&event::parse_jsonl(s)?))
+    }
+
+    /// Creates a tree from the event log at `path`, ignoring an incomplete last record.
+    /// See [log_events_to](TreeBuilder::log_events_to).
+    pub fn load_event_log<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<TreeBuilder> {
+        Ok(TreeBuilder::from_events(&event::read_log(path)?))
+    }
+
+    /// Calls `f` with the new depth each time a branch is entered.
+    /// See [on_node_added](TreeBuilder::on_node_added).
+    pub fn on_enter<F: Fn(usize) + Send + Sync + 'static>(&self, f: F) {
+        self.0.lock().observers.entered.push(Arc::new(f));
+    }
+
+    /// Calls `f` with the new depth each time a branch is exited.
+    /// See [on_node_added](TreeBuilder::on_node_added).
+    pub fn on_exit<F: Fn(usize) + Send + Sync + 'static>(&self, f: F) {
+        self.0.lock().observers.exited.push(Arc::new(f));
+    }
+
+    /// Discards everything added to the current branch from now on, including its sub-branches,
+    /// until the branch is exited. This silences a noisy subtree without disabling the tree.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::{TreeBuilder, add_branch_to, add_leaf_to};
+    /// let tree = TreeBuilder::new();
+    /// {
+    ///     add_branch_to!(tree, "Noisy subsystem");
+    ///     tree.mute_current();
+    ///     add_leaf_to!(tree, "Polled");
+    ///     add_branch_to!(tree, "Polled again");
+    ///     add_leaf_to!(tree, "Nothing new");
+    /// }
+    /// add_leaf_to!(tree, "Done");
+    /// assert_eq!("Noisy subsystem\nDone", tree.peek_string());
+    /// ```
+    pub fn mute_current(&self) {
+        self.0.lock().mute_current()
+    }
+
+    /// Returns whether additions to the current branch are discarded by
+    /// [mute_current](TreeBuilder::mute_current).
+    pub fn is_muted(&self) -> bool {
+        self.0.lock().is_muted()
+    }
+
+    /// Steps into a new child branch.
+    /// Stepping out of the branch requires calling `exit()`.
+    ///
+    /// # Example
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// tree.add_leaf("Branch");
+    /// tree.enter();
+    /// tree.add_leaf("Child of Branch");
+    /// assert_eq!("\
+    /// Branch
+    /// └╼ Child of Branch", &tree.string());
+    /// ```
+    pub fn enter(&self) {
+        let mut x = self.0.lock();
+        if x.is_enabled() {
+            x.enter();
+        }
+    }
+
+    /// Exits the current branch, to the parent branch.
+    /// If no parent branch exists, no action is taken
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// tree.add_leaf("Branch");
+    /// tree.enter();
+    /// tree.add_leaf("Child of Branch");
+    /// tree.exit();
+    /// tree.add_leaf("Sibling of Branch");
+    /// assert_eq!("\
+    /// Branch
+    /// └╼ Child of Branch
+    /// Sibling of Branch", &tree.string());
+    /// ```
+    pub fn exit(&self) -> bool {
+        let mut x = self.0.lock();
+        if x.is_enabled() {
+            x.exit()
+        } else {
+            false
+        }
+    }
+
+    /// Enables or disables recording when each node is added, and when each branch is exited,
+    /// as [Timing] metadata. Timing is disabled by default.
+    /// The timing is used by [Tree::to_mermaid_gantt].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::{TreeBuilder, Timing};
+    /// let tree = TreeBuilder::new();
+    /// tree.set_timing(true);
+    /// {
+    ///     let _branch = tree.add_branch("Sleep");
+    ///     std::thread::sleep(std::time::Duration::from_millis(2));
+    /// }
+    /// let snapshot = tree.snapshot();
+    /// let timing = snapshot.children[0].metadata::<Timing>().unwrap();
+    /// assert!(timing.duration().unwrap() >= std::time::Duration::from_millis(2));
+    /// ```
+    pub fn set_timing(&self, enabled: bool) {
+        self.0.lock().set_timing(enabled)
+    }
+
+    /// Enables or disables recording when, and on which thread, each top-level node is added,
+    /// which [TreeConfig::thread_timeline] renders. Trees forked from this tree record it too.
+    /// It is also recorded while this tree's config override has `thread_timeline` set.
+    /// Recording is disabled by default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::{TreeBuilder, TreeConfig};
+    /// let tree = TreeBuilder::new();
+    /// tree.set_thread_timeline(true);
+    /// let worker = tree.fork();
+    /// tree.add_leaf("Main");
+    /// let worker = std::thread::Builder::new()
+    ///     .name("worker".to_string())
+    ///     .spawn(move || {
+    ///         worker.add_leaf("Worker");
+    ///         worker
+    ///     })
+    ///     .unwrap()
+    ///     .join()
+    ///     .unwrap();
+    /// tree.join(worker);
+    /// tree.set_config_override(TreeConfig::new().thread_timeline());
+    /// assert!(tree.peek_string().ends_with("\n[worker] Worker"));
+    /// ```
+    pub fn set_thread_timeline(&self, enabled: bool) {
+        self.0.lock().set_thread_timeline(enabled)
+    }
+
+    /// Enables or disables measuring the time spent inside this tree's calls to add nodes,
+    /// enter branches and exit them, which is disabled by default.
+    ///
+    /// The total is returned by [overhead](TreeBuilder::overhead), and the time spent while
+    /// directly inside each branch is recorded as [Overhead] metadata on the branch.
+    /// Formatting the text of a node happens before the call, so it isn't included.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::{TreeBuilder, Overhead};
+    /// let tree = TreeBuilder::new();
+    /// tree.set_overhead_tracking(true);
+    /// {
+    ///     let _branch = tree.add_branch("Request");
+    ///     tree.add_leaf("Parsed");
+    /// }
+    /// let total = tree.overhead().unwrap();
+    /// let snapshot = tree.snapshot();
+    /// let Overhead(branch) = snapshot.children[0].metadata::<Overhead>().copied().unwrap();
+    /// assert!(branch <= total);
+    /// ```
+    pub fn set_overhead_tracking(&self, enabled: bool) {
+        self.0.lock().set_overhead_tracking(enabled)
+    }
+
+    /// Returns the total time spent inside this tree's calls since it was last cleared,
+    /// or `None` if [overhead tracking](TreeBuilder::set_overhead_tracking) is off.
+    pub fn overhead(&self) -> Option<std::time::Duration> {
+        self.0.lock().overhead()
+    }
+
+    /// Returns a report of the total overhead, and the overhead of each branch including its
+    /// descendants, or `None` if [overhead tracking](TreeBuilder::set_overhead_tracking) is off.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// tree.set_overhead_tracking(true);
+    /// {
+    ///     let _branch = tree.add_branch("Request");
+    ///     tree.add_leaf("Parsed");
+    /// }
+    /// let report = tree.overhead_report().unwrap();
+    /// assert!(report.starts_with("debug_tree overhead: "));
+    /// assert!(report.contains("\n└╼ Request: "));
+    /// ```
+    pub fn overhead_report(&self) -> Option<String> {
+        fn branches(tree: &Tree) -> (Vec<Tree>, std::time::Duration) {
+            let mut total = tree.metadata::<Overhead>().map(|x| x.0).unwrap_or_default();
+            let mut nodes = vec![];
+            for x in tree.children.iter().filter(|x| !x.children.is_empty()) {
+                let (children, overhead) = branches(x);
+                let text = format!(
+                    "{}: {}",
+                    x.text.as_deref().unwrap_or(""),
+                    human::HumanDuration(overhead)
+                );
+                let mut node = Tree::new(Some(&text));
+                node.children = children;
+                nodes.push(node);
+                total += overhead;
+            }
+            (nodes, total)
+        }
+        let total = self.overhead()?;
+        let mut report = Tree::new(Some(&format!(
+            "debug_tree overhead: {}",
+            human::HumanDuration(total)
+        )));
+        report.children = branches(&self.snapshot()).0;
+        let mut root = Tree::new(None);
+        root.children.push(report);
+        Some(TreeBuilder::from(root).peek_string())
+    }
+
+    /// Writes the tree to `sink` and clears it whenever its estimated size exceeds `bytes`,
+    /// so that long sessions don't grow without bound. Auto-flushing is disabled by default.
+    ///
+    /// The current branch and its ancestors are kept, so that later nodes keep their context.
+    /// The size is estimated with [Tree::estimated_bytes], and existing `NodeId`s are invalidated
+    /// by each flush. If writing to `sink` fails, the tree is kept, and the error can be taken
+    /// with [take_auto_flush_error](TreeBuilder::take_auto_flush_error).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// use std::sync::{Arc, Mutex};
+    /// let out = Arc::new(Mutex::new(Vec::new()));
+    /// let tree = TreeBuilder::new();
+    /// tree.set_auto_flush(1, out.clone());
+    /// let _session = tree.add_branch("Session");
+    /// tree.add_leaf("First");
+    /// tree.add_leaf("Second");
+    /// assert_eq!("Session\n└╼ First\n", String::from_utf8_lossy(&out.lock().unwrap()));
+    /// assert_eq!("Session\n└╼ Second", tree.peek_string());
+    /// ```
+    pub fn set_auto_flush<W: Write + Send + 'static>(&self, bytes: usize, sink: Arc<Mutex<W>>) {
+        self.0.lock().set_auto_flush(bytes, sink)
+    }
+
+    /// Stops auto-flushing. See [set_auto_flush](TreeBuilder::set_auto_flush).
+    pub fn disable_auto_flush(&self) {
+        self.0.lock().disable_auto_flush()
+    }
+
+    /// Returns the error of the last auto-flush that failed, if any, and clears it.
+    /// See [set_auto_flush](TreeBuilder::set_auto_flush).
+    pub fn take_auto_flush_error(&self) -> Option<std::io::Error> {
+        self.0.lock().take_auto_flush_error()
+    }
+
+    /// Enables or disables checks for unbalanced calls to [enter](TreeBuilder::enter) and
+    /// [exit](TreeBuilder::exit), which are disabled by default.
+    ///
+    /// When enabled, a warning leaf is added if `exit` is called at the top level, or if the tree
+    /// is flushed with [string](TreeBuilder::string), [print](TreeBuilder::print),
+    /// [write](TreeBuilder::write) or [html](TreeBuilder::html) before every branch is exited.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// tree.set_cursor_checks(true);
+    /// tree.add_leaf("Branch");
+    /// tree.enter();
+    /// tree.add_leaf("Child");
+    /// assert_eq!("\
+    /// Branch
+    /// ├╼ Child
+    /// └╼ debug_tree: 1 branch was entered but not exited", &tree.string());
+    ///
+    /// tree.exit();
+    /// assert_eq!("debug_tree: exit() was called at the top level", &tree.string());
+    /// ```
+    pub fn set_cursor_checks(&self, enabled: bool) {
+        self.0.lock().set_cursor_checks(enabled);
+    }
+
+    /// Moves the cursor back to the top level, as if each entered branch was exited,
+    /// so that later additions aren't nested beneath a branch that was never exited.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// tree.add_leaf("Branch");
+    /// tree.enter();
+    /// tree.add_leaf("Child");
+    /// tree.enter();
+    /// tree.reset_cursor();
+    /// assert_eq!(0, tree.depth());
+    /// tree.add_leaf("Sibling of Branch");
+    /// assert_eq!("Branch\n└╼ Child\nSibling of Branch", &tree.peek_string());
+    /// ```
+    pub fn reset_cursor(&self) {
+        self.0.lock().reset_cursor();
+    }
+
+    /// Returns the depth of the current branch
+    /// The initial depth when no branches have been adeed is 0.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// assert_eq!(0, tree.depth());
+    /// let _b = tree.add_branch("Branch");
+    /// assert_eq!(1, tree.depth());
+    /// let _b = tree.add_branch("Child branch");
+    /// assert_eq!(2, tree.depth());
+    /// ```
+    pub fn depth(&self) -> usize {
+        self.0.lock().depth()
+    }
+
+    /// Prints the tree without clearing.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// tree.add_leaf("Leaf");
+    /// tree.peek_print();
+    /// // Leaf
+    /// tree.peek_print();
+    /// // Leaf
+    /// // Leaf 2
+    /// ```
+    pub fn peek_print(&self) {
+        self.0.lock().peek_print();
+    }
+
+    /// Prints the tree and then clears it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// tree.add_leaf("Leaf");
+    /// tree.print();
+    /// // Leaf
+    /// tree.add_leaf("Leaf 2");
+    /// tree.print();
+    /// // Leaf 2
+    /// ```
+    pub fn print(&self) {
+        self.0.lock().print();
+    }
+
+    /// Prints the tree in pages of at most `lines_per_page` lines, and clears the tree.
+    /// Standard output is flushed after each page, and the thread yields before the next one,
+    /// so that enormous trees are written in bounded chunks.
+    /// See [write_paged](TreeBuilder::write_paged) to wait for confirmation between pages.
+    /// If writing fails, the error is returned and the tree is not cleared.
+    pub fn print_paged(&self, lines_per_page: usize) -> std::io::Result<()> {
+        self.write_paged(std::io::stdout(), lines_per_page, |_| {
+            std::thread::yield_now();
+            true
+        })
+    }
+
+    /// Writes the tree to `out` in pages of at most `lines_per_page` lines, flushing `out` after
+    /// each page, and clears the tree once every page is written.
+    ///
+    /// Between pages, `between` is called with the number of pages written so far, and can wait
+    /// for confirmation or sleep to throttle the output. If it returns false, the remaining pages
+    /// are discarded, and the tree is cleared.
+    /// If writing fails, the error is returned and the tree is not cleared, so it can be written
+    /// again. Top-level entries added while the pages are written are kept, to be written later.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// for i in 1..=5 {
+    ///     tree.add_leaf(&i.to_string());
+    /// }
+    /// let mut out = Vec::new();
+    /// let mut pages = 0;
+    /// tree.write_paged(&mut out, 2, |n| {
+    ///     pages = n;
+    ///     n < 2
+    /// }).unwrap();
+    /// assert_eq!(2, pages);
+    /// assert_eq!("1\n2\n3\n4\n", String::from_utf8(out).unwrap());
+    /// assert_eq!("", tree.peek_string());
+    /// ```
+    pub fn write_paged<W: Write, F: FnMut(usize) -> bool>(
+        &self,
+        mut out: W,
+        lines_per_page: usize,
+        mut between: F,
+    ) -> std::io::Result<()> {
+        let (text, rendered) = {
+            let x = self.0.lock();
+            (x.peek_string(), x.rendered())
+        };
+        if text.is_empty() {
+            return Ok(());
+        }
+        let lines: Vec<&str> = text.split('\n').collect();
+        let mut pages = lines.chunks(lines_per_page.max(1)).enumerate().peekable();
+        while let Some((i, page)) = pages.next() {
+            for line in page {
+                writeln!(out, "{}", line)?;
+            }
+            out.flush()?;
+            if pages.peek().is_some() && !between(i + 1) {
+                break;
+            }
+        }
+        self.0.lock().remove_rendered(&rendered);
+        Ok(())
+    }
+
+    /// Returns the tree as a string without clearing the tree.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// tree.add_leaf("Leaf");
+    /// assert_eq!("Leaf", tree.peek_string());
+    /// tree.add_leaf("Leaf 2");
+    /// assert_eq!("Leaf\nLeaf 2", tree.peek_string());
+    /// ```
+    pub fn peek_string(&self) -> String {
+        self.0.lock().peek_string()
+    }
+
+    /// Returns the last `n` top-level entries of the tree as a string without clearing the tree.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// tree.add_leaf("Leaf 1");
+    /// {
+    ///     let _branch = tree.add_branch("Leaf 2");
+    ///     tree.add_leaf("Leaf 2.1");
+    /// }
+    /// tree.add_leaf("Leaf 3");
+    /// assert_eq!("Leaf 2\n└╼ Leaf 2.1\nLeaf 3", tree.peek_string_tail(2));
+    /// assert_eq!(tree.peek_string(), tree.peek_string_tail(5));
+    /// ```
+    pub fn peek_string_tail(&self, n: usize) -> String {
+        self.0.lock().peek_string_tail(n)
+    }
+
+    /// Returns the tree as a string and clears the tree.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// tree.add_leaf("Leaf");
+    /// assert_eq!("Leaf", tree.string());
+    /// tree.add_leaf("Leaf 2");
+    /// assert_eq!("Leaf 2", tree.string());
+    /// ```
+    pub fn string(&self) -> String {
+        self.0.lock().string()
+    }
+
+    /// Returns the tree as a standalone HTML page without clearing the tree.
+    /// The page embeds a script to search the text, expand or collapse all branches,
+    /// and hide the nodes below a level, so it can be explored in any browser.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::{TreeBuilder, Level};
+    /// let tree = TreeBuilder::new();
+    /// tree.add_leaf_at(Level::Warn, "Retrying <request>");
+    /// let html = tree.peek_html();
+    /// assert!(html.contains(r#"<li class="warn" data-level="warn">"#));
+    /// assert!(html.contains("Retrying &lt;request&gt;"));
+    /// ```
+    pub fn peek_html(&self) -> String {
+        self.0.lock().peek_html()
+    }
+
+    /// Returns the tree as JSON without clearing the tree, including node attachments.
+    /// See [Tree::to_json].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// tree.add_leaf("Request");
+    /// tree.attach("body", "{}");
+    /// assert_eq!(
+    ///     r#"[{"text":"Request","attachments":[{"name":"body","content":"{}"}]}]"#,
+    ///     tree.peek_json()
+    /// );
+    /// ```
+    pub fn peek_json(&self) -> String {
+        self.0.lock().peek_json()
+    }
+
+    /// Returns the tree as flat `begin` and `end` lines without clearing the tree.
+    /// See [Tree::to_spans], and [stream_spans_to](TreeBuilder::stream_spans_to) to write the
+    /// lines as the tree is built.
+    pub fn peek_spans(&self) -> String {
+        self.0.lock().peek_spans()
+    }
+
+    /// Returns Rust code that rebuilds this tree with a [TreeBuilder] named `tree`, without
+    /// clearing the tree. See [Tree::to_rust_code].
+    pub fn peek_rust_code(&self, tree: &str) -> String {
+        self.0.lock().with_data(|x| x.to_rust_code(tree))
+    }
+
+    /// Returns a CSV table of statistics for each branch, without clearing the tree.
+    /// See [Tree::to_csv_stats].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// tree.set_timing(true);
+    /// {
+    ///     let _request = tree.add_branch("Request");
+    ///     tree.add_leaf("Parsed");
+    /// }
+    /// let csv = tree.peek_csv_stats();
+    /// assert!(csv.starts_with("path,depth,children,duration_us,calls\nRequest,0,1,"));
+    /// assert!(csv.ends_with(",1\n"));
+    /// ```
+    pub fn peek_csv_stats(&self) -> String {
+        self.0.lock().peek_csv_stats()
+    }
+
+    /// Writes a CSV table of statistics for each branch to the file at `path`,
+    /// without clearing the tree. See [Tree::to_csv_stats].
+    pub fn peek_write_csv_stats(&self, path: &str) -> std::io::Result<()> {
+        let mut file = File::create(path)?;
+        file.write_all(self.peek_csv_stats().as_bytes())
+    }
+
+    /// Returns the tree as a standalone HTML page and clears the tree.
+    /// See [peek_html](TreeBuilder::peek_html).
+    pub fn html(&self) -> String {
+        self.0.lock().html()
+    }
+
+    /// Writes the tree as a standalone HTML page to the file at `path`,
+    /// without clearing the tree.
+    pub fn peek_write_html(&self, path: &str) -> std::io::Result<()> {
+        let mut file = File::create(path)?;
+        file.write_all(self.peek_html().as_bytes())
+    }
+
+    /// Writes the tree as a standalone HTML page to the file at `path`, and clears the tree.
+    pub fn write_html(&self, path: &str) -> std::io::Result<()> {
+        let mut file = File::create(path)?;
+        file.write_all(self.html().as_bytes())
+    }
+
+    /// Writes the tree to file without clearing.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// use std::fs::{read_to_string, create_dir};
+    /// use std::io::Read;
+    /// let tree = TreeBuilder::new();
+    /// create_dir("test_out").ok();
+    /// tree.add_leaf("Leaf");
+    /// assert_eq!(tree.peek_string(), "Leaf");
+    /// tree.peek_write("test_out/peek_write.txt");
+    /// assert_eq!(read_to_string("test_out/peek_write.txt").unwrap(), "Leaf");
+    /// assert_eq!(tree.peek_string(), "Leaf");
+    /// ```
+    pub fn peek_write(&self, path: &str) -> std::io::Result<()> {
+        let mut file = File::create(path)?;
+        file.write_all(self.peek_string().as_bytes())
+    }
+
+    /// Writes the tree to file without clearing.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// use std::io::Read;
+    /// use std::fs::{read_to_string, create_dir};
+    /// let tree = TreeBuilder::new();
+    /// create_dir("test_out").ok();
+    /// tree.add_leaf("Leaf");
+    /// assert_eq!(tree.peek_string(), "Leaf");
+    /// tree.write("test_out/write.txt");
+    /// assert_eq!(read_to_string("test_out/write.txt").unwrap(), "Leaf");
+    /// assert_eq!(tree.peek_string(), "");
+    /// ```
+    pub fn write(&self, path: &str) -> std::io::Result<()> {
+        let mut file = File::create(path)?;
+        file.write_all(self.string().as_bytes())
+    }
+
+    /// Emits each line of the tree as a record of the `log` crate at `level`, with `target`,
+    /// without clearing.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// tree.add_leaf("Sent through the application's logger");
+    /// tree.peek_log(log::Level::Debug, "my_app::trace");
+    /// ```
+    #[cfg(feature = "log")]
+    pub fn peek_log(&self, level: log::Level, target: &str) {
+        for line in self.peek_string().lines() {
+            log::log!(target: target, level, "{}", line);
+        }
+    }
+
+    /// Emits each line of the tree as a record of the `log` crate at `level`, with `target`,
+    /// and clears the tree. See [peek_log](TreeBuilder::peek_log).
+    #[cfg(feature = "log")]
+    pub fn flush_log(&self, level: log::Level, target: &str) {
+        for line in self.string().lines() {
+            log::log!(target: target, level, "{}", line);
+        }
+    }
+
+    /// Returns whether any nodes were added since the tree was created or last cleared,
+    /// such as by [string](TreeBuilder::string), [print](TreeBuilder::print) or
+    /// [write](TreeBuilder::write), including nodes of [mounted](TreeBuilder::mount) trees.
+    /// Peeking at the tree doesn't change this.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// tree.set_root_text("Report");
+    /// assert!(!tree.has_unflushed_content());
+    /// tree.add_leaf("Leaf");
+    /// assert!(tree.has_unflushed_content());
+    /// tree.peek_string();
+    /// assert!(tree.has_unflushed_content());
+    /// tree.string();
+    /// assert!(!tree.has_unflushed_content());
+    /// ```
+    pub fn has_unflushed_content(&self) -> bool {
+        self.0.lock().has_content()
+    }
+
+    /// Clears the tree.
+    /// The configuration override, enabled state and root text are kept.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// tree.add_leaf("Leaf");
+    /// assert_eq!("Leaf", tree.peek_string());
+    /// tree.clear();
+    /// assert_eq!("", tree.peek_string());
+    /// ```
+    pub fn clear(&self) {
+        self.0.lock().clear()
+    }
+
+    /// Sets the enabled state of the tree.
+    ///
+    /// If not enabled, the tree will not be modified by adding leaves or branches.
+    /// Additionally, if called using the `add_`... macros, arguments will not be processed.
+    /// This is particularly useful for suppressing output in production, with very little overhead.
+    ///
+    /// # Example
+    /// ```
+    /// #[macro_use]
+    /// use debug_tree::{TreeBuilder, add_leaf_to};
+    /// let mut tree = TreeBuilder::new();
+    /// tree.add_leaf("Leaf 1");
+    /// tree.set_enabled(false);
+    /// add_leaf_to!(tree, "Leaf 2");
+    /// tree.set_enabled(true);
+    /// add_leaf_to!(tree, "Leaf 3");
+    /// assert_eq!("Leaf 1\nLeaf 3", tree.peek_string());
+    /// ```
+    pub fn set_enabled(&self, enabled: bool) {
+        self.0.lock().set_enabled(enabled);
+    }
+
+    /// Sets the most verbose level that is recorded.
+    ///
+    /// Additions with a less severe level than `level` are skipped.
+    /// Additions made without a level are treated as `Level::Info`.
+    /// The initial level is `Level::Trace`, which records everything.
+    ///
+    /// # Example
+    /// ```
+    /// use debug_tree::{TreeBuilder, Level};
+    /// let tree = TreeBuilder::new();
+    /// tree.set_level(Level::Warn);
+    /// tree.add_leaf("Info");
+    /// tree.add_leaf_at(Level::Error, "Error");
+    /// assert_eq!(Level::Warn, tree.level());
+    /// assert_eq!("Error", tree.peek_string());
+    /// ```
+    pub fn set_level(&self, level: Level) {
+        self.0.lock().set_level(level);
+    }
+
+    /// Returns the most verbose level that is recorded.
+    pub fn level(&self) -> Level {
+        self.0.lock().level()
+    }
+
+    /// Returns whether additions at `level` are recorded.
+    /// This is false for every level if the tree is not enabled.
+    pub fn is_level_enabled(&self, level: Level) -> bool {
+        self.0.lock().is_level_enabled(level)
+    }
+
+    /// Returns the enabled state of the tree.
+    ///
+    /// # Example
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let mut tree = TreeBuilder::new();
+    /// assert_eq!(true, tree.is_enabled());
+    /// tree.set_enabled(false);
+    /// assert_eq!(false, tree.is_enabled());
+    /// ```
+    pub fn is_enabled(&self) -> bool {
+        self.1.load(Ordering::Relaxed)
+    }
+}
+
+pub trait AsTree {
+    fn as_tree(&self) -> TreeBuilder;
+    fn is_tree_enabled(&self) -> bool {
+        self.as_tree().is_enabled()
+    }
+}
+
+impl Default for TreeBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Renders the tree without clearing it, as with [peek_string](TreeBuilder::peek_string).
+///
+/// # Example
+///
+/// ```
+/// use debug_tree::TreeBuilder;
+/// let tree = TreeBuilder::default();
+/// tree.add_leaf("Leaf");
+/// assert_eq!("Tree:\nLeaf", format!("Tree:\n{}", tree));
+/// ```
+impl fmt::Display for TreeBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.peek_string())
+    }
+}
+
+/// Creates a builder for `tree`. Nodes added to the builder are appended to the top level.
+///
+/// # Example
+///
+/// ```
+/// use debug_tree::{Tree, TreeBuilder};
+/// let mut tree = Tree::new(None);
+/// tree.children.push(Tree::new(Some("Existing")));
+/// let builder = TreeBuilder::from(tree);
+/// builder.add_leaf("Added");
+/// assert_eq!("Existing\nAdded", builder.peek_string());
+/// ```
+impl From<Tree> for TreeBuilder {
+    fn from(tree: Tree) -> Self {
+        TreeBuilder::from_base(internal::TreeBuilderBase::from_tree(tree))
+    }
+}
+
+impl AsTree for TreeBuilder {
+    fn as_tree(&self) -> TreeBuilder {
+        self.clone()
+    }
+    fn is_tree_enabled(&self) -> bool {
+        self.is_enabled()
+    }
+}
 
 pub(crate) fn get_or_add_tree<T: AsRef<str>>(name: T) -> TreeBuilder {
     let mut map = TREE_MAP.lock().unwrap();
@@ -511,6 +2438,16 @@ pub(crate) fn get_tree<T: AsRef<str>>(name: T) -> Option<TreeBuilder> {
     TREE_MAP.lock().unwrap().get(name.as_ref()).cloned()
 }
 
+/// The named trees, in order of name.
+pub(crate) fn named_trees() -> Vec<(String, TreeBuilder)> {
+    TREE_MAP
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(name, tree)| (name.clone(), tree.clone()))
+        .collect()
+}
+
 type TreeMap = BTreeMap<String, TreeBuilder>;
 
 static TREE_MAP: Lazy<Arc<Mutex<TreeMap>>> =
@@ -549,6 +2486,15 @@ impl<T: AsRef<str>> AsTree for T {
     }
 }
 
+/// The text of formatted arguments, borrowed if they are a string literal without arguments.
+#[doc(hidden)]
+pub fn __text(args: fmt::Arguments<'_>) -> Cow<'static, str> {
+    match args.as_str() {
+        Some(x) => Cow::Borrowed(x),
+        None => Cow::Owned(args.to_string()),
+    }
+}
+
 /// Returns the tree
 /// If there is no tree then one is created and then returned.
 pub fn tree<T: AsTree>(tree: T) -> TreeBuilder {
@@ -567,43 +2513,387 @@ pub fn clear<T: AsRef<str>>(name: T) {
     name.as_tree().clear();
 }
 
-/// Returns [string](TreeBuilder::string) for the tree named `name`
-/// If there is no tree named `name` then one is created
-pub fn string<T: AsRef<str>>(name: T) -> String {
-    name.as_tree().string()
+/// Returns [string](TreeBuilder::string) for the tree named `name`
+/// If there is no tree named `name` then one is created
+pub fn string<T: AsRef<str>>(name: T) -> String {
+    name.as_tree().string()
+}
+
+/// Returns [peek_string](TreeBuilder::peek_string) for the tree named `name`
+/// If there is no tree named `name` then one is created
+pub fn peek_string<T: AsRef<str>>(name: T) -> String {
+    name.as_tree().peek_string()
+}
+
+/// Calls [print](TreeBuilder::print) for the tree named `name`
+/// If there is no tree named `name` then one is created
+pub fn print<T: AsRef<str>>(name: T) {
+    name.as_tree().print();
+}
+
+/// Calls [peek_print](TreeBuilder::peek_print)  for the tree named `name`
+/// If there is no tree named `name` then one is created
+pub fn peek_print<T: AsRef<str>>(name: T) {
+    name.as_tree().peek_print();
+}
+
+/// Calls [write](TreeBuilder::write) for the tree named `name`
+/// If there is no tree named `name` then one is created
+pub fn write<T: AsRef<str>, P: AsRef<str>>(name: T, path: P) -> std::io::Result<()> {
+    name.as_tree().write(path.as_ref())
+}
+
+/// Calls [peek_print](TreeBuilder::peek_print)  for the tree named `name`
+/// If there is no tree named `name` then one is created
+pub fn peek_write<T: AsRef<str>, P: AsRef<str>>(name: T, path: P) -> std::io::Result<()> {
+    name.as_tree().peek_write(path.as_ref())
+}
+
+/// Adds a leaf to given tree with the given text and formatting arguments
+///
+/// # Arguments
+/// * `tree` - The tree that the leaf should be added to
+/// * `text...` - Formatted text arguments, as per `format!(...)`.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// use debug_tree::{TreeBuilder, add_leaf_to};
+/// fn main() {
+///     let tree = TreeBuilder::new();
+///     add_leaf_to!(tree, "A {} leaf", "new");
+///     assert_eq!("A new leaf", &tree.peek_string());
+/// }
+/// ```
+#[macro_export]
+macro_rules! add_leaf_to {
+    ($tree:expr, target: $target:expr, $($arg:tt)*) => (if $crate::is_tree_enabled(&$tree) {
+        use $crate::AsTree;
+        $tree.as_tree().add_leaf_target_text(
+            $crate::Level::Info,
+            $target,
+            $crate::__text(format_args!($($arg)*)),
+        )
+    });
+    ($tree:expr, $($arg:tt)*) => (if $crate::is_tree_enabled(&$tree) {
+        use $crate::AsTree;
+        $tree.as_tree().add_leaf_target_text(
+            $crate::Level::Info,
+            module_path!(),
+            $crate::__text(format_args!($($arg)*)),
+        )
+    });
+}
+
+/// Adds a leaf to given tree with the given text and formatting arguments,
+/// only if `condition` is true.
+///
+/// The formatting arguments are not evaluated if the condition is false.
+///
+/// # Arguments
+/// * `tree` - The tree that the leaf should be added to
+/// * `condition` - A `bool` expression.
+/// * `text...` - Formatted text arguments, as per `format!(...)`.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// use debug_tree::{TreeBuilder, add_leaf_if_to};
+/// fn main() {
+///     let tree = TreeBuilder::new();
+///     for i in 0..4 {
+///         add_leaf_if_to!(tree, i % 2 == 1, "{} is odd", i);
+///     }
+///     assert_eq!("1 is odd\n3 is odd", &tree.peek_string());
+/// }
+/// ```
+#[macro_export]
+macro_rules! add_leaf_if_to {
+    ($tree:expr, $condition:expr, $($arg:tt)*) => {
+        if $condition {
+            $crate::add_leaf_to!($tree, $($arg)*)
+        }
+    };
+}
+
+/// Adds a leaf to given tree with the given `value` argument
+///
+/// # Arguments
+/// * `tree` - The tree that the leaf should be added to
+/// * `value` - An expression that implements the `Display` trait.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// use debug_tree::{TreeBuilder, add_leaf_value_to};
+/// fn main() {
+///     let tree = TreeBuilder::new();
+///     let value = add_leaf_value_to!(tree, 5 * 4 * 3 * 2);
+///     assert_eq!(120, value);
+///     assert_eq!("120", &tree.peek_string());
+/// }
+/// ```
+#[macro_export]
+macro_rules! add_leaf_value_to {
+    ($tree:expr, $value:expr) => {{
+        let v = $value;
+        if $crate::is_tree_enabled(&$tree) {
+            use $crate::AsTree;
+            $tree
+                .as_tree()
+                .add_leaf_target(module_path!(), &format!("{}", &v));
+        }
+        v
+    }};
+}
+
+/// Adds a byte slice to given tree as a hex dump, with one leaf for each line of 16 bytes.
+/// See [TreeBuilder::add_hexdump].
+///
+/// # Arguments
+/// * `tree` - The tree that the leaves should be added to
+/// * `bytes` - An expression that implements `AsRef<[u8]>`.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// use debug_tree::{TreeBuilder, add_hexdump_to};
+/// fn main() {
+///     let tree = TreeBuilder::new();
+///     add_hexdump_to!(tree, [0xca, 0xfe, 0x00, 0x41]);
+///     assert_eq!(
+///         "00000000  ca fe 00 41                                       |...A|",
+///         &tree.peek_string()
+///     );
+/// }
+/// ```
+#[macro_export]
+macro_rules! add_hexdump_to {
+    ($tree:expr, $bytes:expr) => {
+        if $crate::is_tree_enabled(&$tree) {
+            use $crate::AsTree;
+            $tree.as_tree().add_hexdump(AsRef::<[u8]>::as_ref(&$bytes))
+        }
+    };
+}
+
+/// Adds a leaf to given tree with a label and a human-readable duration,
+/// such as `Parsed 12 rows: 1.24ms`. See [add_duration](TreeBuilder::add_duration).
+///
+/// # Arguments
+/// * `tree` - The tree that the leaf should be added to
+/// * `duration` - A `Duration` expression.
+/// * `label...` - Formatted label arguments, as per `format!(...)`.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// use debug_tree::{TreeBuilder, add_duration_to};
+/// use std::time::Duration;
+/// fn main() {
+///     let tree = TreeBuilder::new();
+///     add_duration_to!(tree, Duration::from_nanos(850), "Parsed {} rows", 12);
+///     assert_eq!("Parsed 12 rows: 850ns", &tree.peek_string());
+/// }
+/// ```
+#[macro_export]
+macro_rules! add_duration_to {
+    ($tree:expr, $duration:expr, $($arg:tt)*) => (if $crate::is_tree_enabled(&$tree) {
+        use $crate::AsTree;
+        $tree.as_tree().add_duration(&format!($($arg)*), $duration)
+    });
+}
+
+/// Adds a leaf to given tree with a label and a human-readable size in bytes,
+/// such as `Read body: 4.2 KiB`. See [add_size](TreeBuilder::add_size).
+///
+/// # Arguments
+/// * `tree` - The tree that the leaf should be added to
+/// * `bytes` - A `u64` expression.
+/// * `label...` - Formatted label arguments, as per `format!(...)`.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// use debug_tree::{TreeBuilder, add_size_to};
+/// fn main() {
+///     let tree = TreeBuilder::new();
+///     add_size_to!(tree, 4300, "Read {}", "body");
+///     assert_eq!("Read body: 4.2 KiB", &tree.peek_string());
+/// }
+/// ```
+#[macro_export]
+macro_rules! add_size_to {
+    ($tree:expr, $bytes:expr, $($arg:tt)*) => (if $crate::is_tree_enabled(&$tree) {
+        use $crate::AsTree;
+        $tree.as_tree().add_size(&format!($($arg)*), $bytes)
+    });
 }
 
-/// Returns [peek_string](TreeBuilder::peek_string) for the tree named `name`
-/// If there is no tree named `name` then one is created
-pub fn peek_string<T: AsRef<str>>(name: T) -> String {
-    name.as_tree().peek_string()
+/// Serializes `value` and adds it to given tree as a subtree labelled with the expression.
+/// See [add_serde](TreeBuilder::add_serde).
+///
+/// # Arguments
+/// * `tree` - The tree that the subtree should be added to
+/// * `value` - An expression that implements `serde::Serialize`.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// use debug_tree::{TreeBuilder, add_serde_to};
+/// fn main() {
+///     let tree = TreeBuilder::new();
+///     let ports = [80, 443];
+///     add_serde_to!(tree, ports);
+///     assert_eq!("ports\n├╼ [0]: 80\n└╼ [1]: 443", &tree.peek_string());
+/// }
+/// ```
+#[cfg(feature = "serde")]
+#[macro_export]
+macro_rules! add_serde_to {
+    ($tree:expr, $value:expr) => {
+        if $crate::is_tree_enabled(&$tree) {
+            use $crate::AsTree;
+            $tree.as_tree().add_serde(stringify!($value), &$value)
+        }
+    };
 }
 
-/// Calls [print](TreeBuilder::print) for the tree named `name`
-/// If there is no tree named `name` then one is created
-pub fn print<T: AsRef<str>>(name: T) {
-    name.as_tree().print();
+/// Adds a `Result` as a leaf to given tree, formatted as `Ok(..)` or `Err(..)`.
+/// An `Err` is added at [Level::Error], and an `Ok` at [Level::Info].
+///
+/// Returns the given `result` argument, so it can be used with `?`.
+///
+/// # Arguments
+/// * `tree` - The tree that the leaf should be added to
+/// * `result` - A `Result` expression whose values implement the `Debug` trait.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// use debug_tree::{TreeBuilder, add_result_to};
+/// fn main() {
+///     let tree = TreeBuilder::new();
+///     let value: Result<u8, String> = add_result_to!(tree, Err("Too big".to_string()));
+///     assert!(value.is_err());
+///     assert_eq!("Err(\"Too big\")", &tree.peek_string());
+/// }
+/// ```
+#[macro_export]
+macro_rules! add_result_to {
+    ($tree:expr, $result:expr) => {{
+        let v = $result;
+        if $crate::is_tree_enabled(&$tree) {
+            use $crate::AsTree;
+            let (level, text) = match &v {
+                Ok(x) => ($crate::Level::Info, format!("Ok({:?})", x)),
+                Err(x) => ($crate::Level::Error, format!("Err({:?})", x)),
+            };
+            $tree
+                .as_tree()
+                .add_leaf_target_text(level, module_path!(), text.into());
+        }
+        v
+    }};
 }
 
-/// Calls [peek_print](TreeBuilder::peek_print)  for the tree named `name`
-/// If there is no tree named `name` then one is created
-pub fn peek_print<T: AsRef<str>>(name: T) {
-    name.as_tree().peek_print();
+/// Adds an `Option` as a leaf to given tree, formatted as `Some(..)` or `None`.
+/// `None` is added at [Level::Warn], and `Some` at [Level::Info].
+///
+/// Returns the given `option` argument, so it can be used with `?`.
+///
+/// # Arguments
+/// * `tree` - The tree that the leaf should be added to
+/// * `option` - An `Option` expression whose value implements the `Debug` trait.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// use debug_tree::{TreeBuilder, add_option_to};
+/// fn main() {
+///     let tree = TreeBuilder::new();
+///     let value = add_option_to!(tree, "a,b".split(',').nth(1));
+///     assert_eq!(Some("b"), value);
+///     assert_eq!("Some(\"b\")", &tree.peek_string());
+/// }
+/// ```
+#[macro_export]
+macro_rules! add_option_to {
+    ($tree:expr, $option:expr) => {{
+        let v = $option;
+        if $crate::is_tree_enabled(&$tree) {
+            use $crate::AsTree;
+            let (level, text) = match &v {
+                Some(x) => ($crate::Level::Info, format!("Some({:?})", x)),
+                None => ($crate::Level::Warn, "None".to_string()),
+            };
+            $tree
+                .as_tree()
+                .add_leaf_target_text(level, module_path!(), text.into());
+        }
+        v
+    }};
 }
 
-/// Calls [write](TreeBuilder::write) for the tree named `name`
-/// If there is no tree named `name` then one is created
-pub fn write<T: AsRef<str>, P: AsRef<str>>(name: T, path: P) -> std::io::Result<()> {
-    name.as_tree().write(path.as_ref())
+/// Adds an error to given tree, with the chain of its sources nested beneath it.
+/// See [add_error](TreeBuilder::add_error).
+///
+/// # Arguments
+/// * `tree` - The tree that the leaf should be added to
+/// * `error` - A reference to a value that implements the `Error` trait.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// use debug_tree::{TreeBuilder, add_error_to};
+/// fn main() {
+///     let tree = TreeBuilder::new();
+///     if let Err(err) = "x".parse::<u8>() {
+///         add_error_to!(tree, &err);
+///     }
+///     assert_eq!("invalid digit found in string", &tree.peek_string());
+/// }
+/// ```
+#[macro_export]
+macro_rules! add_error_to {
+    ($tree:expr, $error:expr) => {
+        if $crate::is_tree_enabled(&$tree) {
+            use $crate::AsTree;
+            $tree.as_tree().add_error($error)
+        }
+    };
 }
 
-/// Calls [peek_print](TreeBuilder::peek_print)  for the tree named `name`
-/// If there is no tree named `name` then one is created
-pub fn peek_write<T: AsRef<str>, P: AsRef<str>>(name: T, path: P) -> std::io::Result<()> {
-    name.as_tree().peek_write(path.as_ref())
+/// Adds a leaf at `level` to the given tree, formatting the text only if the level is enabled.
+/// Used by the severity macros, such as [tree_error_to].
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __add_leaf_at_to {
+    ($tree:expr, $level:expr, target: $target:expr, $($arg:tt)*) => (if $crate::is_tree_enabled(&$tree) {
+        use $crate::AsTree;
+        let tree = $tree.as_tree();
+        if tree.is_level_enabled($level) {
+            tree.add_leaf_target_text($level, $target, $crate::__text(format_args!($($arg)*)))
+        }
+    });
+    ($tree:expr, $level:expr, $($arg:tt)*) => (
+        $crate::__add_leaf_at_to!($tree, $level, target: module_path!(), $($arg)*)
+    );
 }
 
-/// Adds a leaf to given tree with the given text and formatting arguments
+/// Adds a leaf at the `Error` level to given tree with the given text and formatting
+/// arguments, like the `log` crate's `error!`.
+/// The text is not formatted if the level is not enabled by [set_level](TreeBuilder::set_level).
 ///
 /// # Arguments
 /// * `tree` - The tree that the leaf should be added to
@@ -613,49 +2903,93 @@ pub fn peek_write<T: AsRef<str>, P: AsRef<str>>(name: T, path: P) -> std::io::Re
 ///
 /// ```
 /// #[macro_use]
-/// use debug_tree::{TreeBuilder, add_leaf_to};
+/// use debug_tree::{TreeBuilder, Level, tree_error_to, tree_warn_to, tree_debug_to};
 /// fn main() {
 ///     let tree = TreeBuilder::new();
-///     add_leaf_to!(tree, "A {} leaf", "new");
-///     assert_eq!("A new leaf", &tree.peek_string());
+///     tree.set_level(Level::Info);
+///     tree_error_to!(tree, "Lost {} packets", 3);
+///     tree_warn_to!(tree, "Retrying");
+///     tree_debug_to!(tree, "Checked {} routes", 12);
+///     assert_eq!("Lost 3 packets\nRetrying", &tree.peek_string());
+///     assert_eq!(Level::Error, tree.snapshot().children[0].level);
 /// }
 /// ```
 #[macro_export]
-macro_rules! add_leaf_to {
-    ($tree:expr, $($arg:tt)*) => (if $crate::is_tree_enabled(&$tree) {
-        use $crate::AsTree;
-        $tree.as_tree().add_leaf(&format!($($arg)*))
-    });
+macro_rules! tree_error_to {
+    ($tree:expr, $($arg:tt)*) => ($crate::__add_leaf_at_to!($tree, $crate::Level::Error, $($arg)*));
 }
 
-/// Adds a leaf to given tree with the given `value` argument
+/// Adds a leaf at the `Warn` level to given tree with the given text and formatting
+/// arguments, like the `log` crate's `warn!`.
+/// The text is not formatted if the level is not enabled by [set_level](TreeBuilder::set_level).
 ///
 /// # Arguments
 /// * `tree` - The tree that the leaf should be added to
-/// * `value` - An expression that implements the `Display` trait.
+/// * `text...` - Formatted text arguments, as per `format!(...)`.
+///
+/// See [tree_error_to] for an example.
+///
+#[macro_export]
+macro_rules! tree_warn_to {
+    ($tree:expr, $($arg:tt)*) => ($crate::__add_leaf_at_to!($tree, $crate::Level::Warn, $($arg)*));
+}
+
+/// Adds a leaf at the `Info` level to given tree with the given text and formatting
+/// arguments, like the `log` crate's `info!`.
+/// The text is not formatted if the level is not enabled by [set_level](TreeBuilder::set_level).
+///
+/// # Arguments
+/// * `tree` - The tree that the leaf should be added to
+/// * `text...` - Formatted text arguments, as per `format!(...)`.
+///
+/// See [tree_error_to] for an example.
+///
+#[macro_export]
+macro_rules! tree_info_to {
+    ($tree:expr, $($arg:tt)*) => ($crate::__add_leaf_at_to!($tree, $crate::Level::Info, $($arg)*));
+}
+
+/// Adds a leaf at the `Debug` level to given tree with the given text and formatting
+/// arguments, like the `log` crate's `debug!`.
+/// The text is not formatted if the level is not enabled by [set_level](TreeBuilder::set_level).
+///
+/// # Arguments
+/// * `tree` - The tree that the leaf should be added to
+/// * `text...` - Formatted text arguments, as per `format!(...)`.
+///
+/// See [tree_error_to] for an example.
+///
+#[macro_export]
+macro_rules! tree_debug_to {
+    ($tree:expr, $($arg:tt)*) => ($crate::__add_leaf_at_to!($tree, $crate::Level::Debug, $($arg)*));
+}
+
+/// Adds a leaf to given tree with the time elapsed since the previous checkpoint in the current
+/// branch. See [checkpoint](TreeBuilder::checkpoint).
+///
+/// # Arguments
+/// * `tree` - The tree that the leaf should be added to
+/// * `label...` - Formatted label arguments, as per `format!(...)`.
 ///
 /// # Example
 ///
 /// ```
 /// #[macro_use]
-/// use debug_tree::{TreeBuilder, add_leaf_value_to};
+/// use debug_tree::{TreeBuilder, checkpoint_to};
 /// fn main() {
 ///     let tree = TreeBuilder::new();
-///     let value = add_leaf_value_to!(tree, 5 * 4 * 3 * 2);
-///     assert_eq!(120, value);
-///     assert_eq!("120", &tree.peek_string());
+///     checkpoint_to!(tree, "Loaded {} files", 3);
+///     assert!(tree.peek_string().starts_with("Loaded 3 files: "));
 /// }
 /// ```
 #[macro_export]
-macro_rules! add_leaf_value_to {
-    ($tree:expr, $value:expr) => {{
-        let v = $value;
+macro_rules! checkpoint_to {
+    ($tree:expr, $($arg:tt)*) => {
         if $crate::is_tree_enabled(&$tree) {
             use $crate::AsTree;
-            $tree.as_tree().add_leaf(&format!("{}", &v));
+            $tree.as_tree().checkpoint(&format!($($arg)*));
         }
-        v
-    }};
+    };
 }
 
 /// Adds a scoped branch to given tree with the given text and formatting arguments
@@ -685,6 +3019,26 @@ macro_rules! add_leaf_value_to {
 /// Sibling of Branch" , &tree.string());
 /// }
 /// ```
+///
+/// The branch can be bound to a name, so that it can be exited early with `release()`.
+/// An identifier after the tree, followed by a comma, is always taken as that name, so the
+/// text must start with a string literal: write `add_branch_to!(tree, "{}", text)`.
+///
+/// ```
+/// #[macro_use]
+/// use debug_tree::{TreeBuilder, add_branch_to, add_leaf_to};
+/// fn main() {
+///     let tree = TreeBuilder::new();
+///     add_branch_to!(tree, branch, "New {}", "Branch");
+///     add_leaf_to!(tree, "Child of {}", "Branch");
+///     branch.release();
+///     add_leaf_to!(tree, "Sibling of {}", "Branch");
+///     assert_eq!("\
+/// New Branch
+/// └╼ Child of Branch
+/// Sibling of Branch" , &tree.string());
+/// }
+/// ```
 #[macro_export]
 macro_rules! add_branch_to {
     ($tree:expr) => {
@@ -695,10 +3049,76 @@ macro_rules! add_branch_to {
             $crate::scoped_branch::ScopedBranch::none()
         };
     };
+    ($tree:expr, target: $target:expr, $($arg:tt)*) => {
+        let _debug_tree_branch = if $crate::is_tree_enabled(&$tree) {
+            use $crate::AsTree;
+            $tree.as_tree().add_branch_target_text($target, $crate::__text(format_args!($($arg)*)))
+        } else {
+            $crate::scoped_branch::ScopedBranch::none()
+        };
+    };
+    ($tree:expr, $name:ident, $($arg:tt)*) => {
+        #[allow(unused_mut)]
+        let mut $name = if $crate::is_tree_enabled(&$tree) {
+            use $crate::AsTree;
+            $tree.as_tree().add_branch_target_text(
+                module_path!(),
+                $crate::__text(format_args!($($arg)*)),
+            )
+        } else {
+            $crate::scoped_branch::ScopedBranch::none()
+        };
+    };
     ($tree:expr, $($arg:tt)*) => {
         let _debug_tree_branch = if $crate::is_tree_enabled(&$tree) {
             use $crate::AsTree;
-            $tree.as_tree().add_branch(&format!($($arg)*))
+            $tree.as_tree().add_branch_target_text(
+                module_path!(),
+                $crate::__text(format_args!($($arg)*)),
+            )
+        } else {
+            $crate::scoped_branch::ScopedBranch::none()
+        };
+    };
+}
+
+/// Adds a scoped branch to given tree with the given text and formatting arguments,
+/// only if `condition` is true.
+/// The branch will be exited at the end of the current block.
+///
+/// The formatting arguments are not evaluated and no branch is entered if the condition is false.
+///
+/// # Arguments
+/// * `tree` - The tree that the leaf should be added to
+/// * `condition` - A `bool` expression.
+/// * `text...` - Formatted text arguments, as per `format!(...)`.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// use debug_tree::{TreeBuilder, add_branch_if_to, add_leaf_to};
+/// fn main() {
+///     let tree = TreeBuilder::new();
+///     for i in 0..2 {
+///         add_branch_if_to!(tree, i > 0, "Retry {}", i);
+///         add_leaf_to!(tree, "Attempt {}", i);
+///     }
+///     assert_eq!("\
+/// Attempt 0
+/// Retry 1
+/// └╼ Attempt 1" , &tree.string());
+/// }
+/// ```
+#[macro_export]
+macro_rules! add_branch_if_to {
+    ($tree:expr, $condition:expr, $($arg:tt)*) => {
+        let _debug_tree_branch = if $condition && $crate::is_tree_enabled(&$tree) {
+            use $crate::AsTree;
+            $tree.as_tree().add_branch_target_text(
+                module_path!(),
+                $crate::__text(format_args!($($arg)*)),
+            )
         } else {
             $crate::scoped_branch::ScopedBranch::none()
         };
@@ -803,3 +3223,84 @@ macro_rules! defer_peek_write {
         })
     };
 }
+
+/// Asserts that `tree` has the same nodes as `expected`, regardless of the order of siblings.
+///
+/// `expected` is indented by two spaces for each level, as parsed by
+/// [Tree::from_indented_str], and may contain the wildcards described in [Tree::matches].
+/// The root text of `tree` is not compared.
+///
+/// # Example
+///
+/// ```
+/// use debug_tree::{TreeBuilder, assert_tree_unordered};
+/// let tree = TreeBuilder::new();
+/// for (key, values) in [("b", vec![2, 3]), ("a", vec![1])] {
+///     tree.add_leaf(key);
+///     let _branch = tree.enter_scoped();
+///     for value in values.iter().rev() {
+///         tree.add_leaf(&value.to_string());
+///     }
+/// }
+/// assert_tree_unordered!(tree, "\
+/// a
+///   1
+/// b
+///   2
+///   3");
+/// ```
+#[macro_export]
+macro_rules! assert_tree_unordered {
+    ($tree:expr, $expected:expr) => {{
+        use $crate::AsTree;
+        let mut actual = $tree.as_tree().snapshot();
+        actual.text = None;
+        let expected = $crate::Tree::from_indented_str($expected, 2)
+            .unwrap_or_else(|err| panic!("invalid expected tree: {}", err));
+        if !actual.matches_unordered(&expected) {
+            panic!(
+                "trees are not equal, ignoring order\n actual:\n{}\n expected:\n{}",
+                $crate::TreeBuilder::from(actual),
+                $crate::TreeBuilder::from(expected)
+            );
+        }
+    }};
+}
+
+/// Asserts that the nodes of `tree` match `expected` in order.
+///
+/// `expected` is indented by two spaces for each level, as parsed by
+/// [Tree::from_indented_str], and may contain the wildcards described in [Tree::matches].
+/// The root text of `tree` is not compared.
+///
+/// # Example
+///
+/// ```
+/// use debug_tree::{TreeBuilder, add_branch_to, add_leaf_to, assert_tree_matches};
+/// let tree = TreeBuilder::new();
+/// {
+///     add_branch_to!(tree, "Allocated {:p}", &tree);
+///     add_leaf_to!(tree, "Took {:?}", std::time::Instant::now().elapsed());
+///     add_leaf_to!(tree, "Freed");
+/// }
+/// assert_tree_matches!(tree, "\
+/// Allocated 0x*
+///   Took *
+///   **");
+/// ```
+#[macro_export]
+macro_rules! assert_tree_matches {
+    ($tree:expr, $expected:expr) => {{
+        use $crate::AsTree;
+        let actual = $tree.as_tree().snapshot();
+        let expected = $crate::Tree::from_indented_str($expected, 2)
+            .unwrap_or_else(|err| panic!("invalid expected tree: {}", err));
+        if !actual.matches(&expected) {
+            panic!(
+                "tree does not match\n actual:\n{}\n expected:\n{}",
+                $crate::TreeBuilder::from(actual),
+                $crate::TreeBuilder::from(expected)
+            );
+        }
+    }};
+}
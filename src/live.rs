@@ -0,0 +1,107 @@
+use crate::TreeBuilder;
+use std::io::Write;
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Repaints a tree in place whenever its rendered text changes.
+struct Painter<W: Write> {
+    tree: TreeBuilder,
+    out: W,
+    last: String,
+}
+
+impl<W: Write> Painter<W> {
+    fn paint(&mut self) {
+        let frame = self.tree.peek_string();
+        if frame == self.last {
+            return;
+        }
+        let height = match self.last.is_empty() {
+            true => 0,
+            false => self.last.split('\n').count(),
+        };
+        if height > 0 {
+            // Move to the start of the previous frame and clear everything below it
+            let _ = write!(self.out, "\x1b[{}F\x1b[J", height);
+        }
+        if !frame.is_empty() {
+            let _ = writeln!(self.out, "{}", frame);
+        }
+        let _ = self.out.flush();
+        self.last = frame;
+    }
+}
+
+/// Continuously repaints a tree in the terminal while it is being built,
+/// so long-running jobs show an updating hierarchical progress display.
+///
+/// The tree is checked for changes every `interval`, and only repainted when it has changed.
+/// A final frame is painted when the view is stopped or dropped.
+/// Lines that are wider than the terminal wrap, and are not cleared correctly.
+///
+/// # Example
+///
+/// ```
+/// use debug_tree::TreeBuilder;
+/// use debug_tree::live::LiveView;
+/// use std::time::Duration;
+/// let tree = TreeBuilder::new();
+/// let view = LiveView::start(tree.clone(), Duration::from_millis(100));
+/// tree.add_leaf("Downloading");
+/// view.stop();
+/// ```
+pub struct LiveView {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl LiveView {
+    /// Start repainting `tree` on stderr.
+    pub fn start(tree: TreeBuilder, interval: Duration) -> LiveView {
+        LiveView::start_with_writer(tree, interval, std::io::stderr())
+    }
+
+    /// Start repainting `tree` on `out`, which should be a terminal that understands ANSI
+    /// escape sequences. The first frame is painted before this returns.
+    pub fn start_with_writer<W: Write + Send + 'static>(
+        tree: TreeBuilder,
+        interval: Duration,
+        out: W,
+    ) -> LiveView {
+        let mut painter = Painter {
+            tree,
+            out,
+            last: String::new(),
+        };
+        painter.paint();
+        let (stop, stopped) = channel();
+        let thread = std::thread::spawn(move || loop {
+            match stopped.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => painter.paint(),
+                _ => {
+                    painter.paint();
+                    break;
+                }
+            }
+        });
+        LiveView {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+
+    /// Paint the final frame and stop repainting.
+    pub fn stop(self) {}
+}
+
+impl Drop for LiveView {
+    fn drop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,50 @@
+/// How inline markup in node text is rendered: `**bold**` and `` `code` ``.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Markup {
+    /// Render markup with ANSI escape sequences, for terminals
+    Ansi,
+    /// Remove the markup characters, keeping the text
+    Strip,
+}
+
+impl Markup {
+    pub(crate) fn apply(self, text: &str) -> String {
+        match self {
+            Markup::Ansi => render(text, &("\x1b[1m", "\x1b[22m"), &("\x1b[36m", "\x1b[39m")),
+            Markup::Strip => render(text, &("", ""), &("", "")),
+        }
+    }
+}
+
+/// Replaces `**bold**` and `` `code` `` in `text` with the given opening and closing strings.
+/// Markers without a matching closing marker are kept as they are.
+pub(crate) fn render(text: &str, bold: &(&str, &str), code: &(&str, &str)) -> String {
+    let mut ret = String::with_capacity(text.len());
+    let mut rest = text;
+    while !rest.is_empty() {
+        if let Some((inner, after)) = delimited(rest, "**") {
+            ret.push_str(bold.0);
+            ret.push_str(&render(inner, bold, code));
+            ret.push_str(bold.1);
+            rest = after;
+        } else if let Some((inner, after)) = delimited(rest, "`") {
+            ret.push_str(code.0);
+            ret.push_str(inner);
+            ret.push_str(code.1);
+            rest = after;
+        } else {
+            let c = rest.chars().next().unwrap();
+            ret.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    ret
+}
+
+/// If `text` starts with `marker` followed by non-empty text and another `marker`,
+/// returns the text between them and the text after the closing marker.
+fn delimited<'a>(text: &'a str, marker: &str) -> Option<(&'a str, &'a str)> {
+    let text = text.strip_prefix(marker)?;
+    let end = text.find(marker).filter(|&x| x > 0)?;
+    Some((&text[..end], &text[end + marker.len()..]))
+}
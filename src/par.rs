@@ -0,0 +1,67 @@
+use crate::TreeBuilder;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+impl TreeBuilder {
+    /// Calls `f` on each item of `items` in parallel, with a separate tree for each item,
+    /// and returns the results in the original order.
+    ///
+    /// Each item's tree is created with [fork](TreeBuilder::fork), and once every item is done,
+    /// they are joined to the current branch of this tree in the original order of the items.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// let _branch = tree.add_branch("Squares");
+    /// let squares = tree.par_map(1..=3, |x, tree| {
+    ///     let _branch = tree.add_branch(&format!("Item {}", x));
+    ///     tree.add_leaf(&format!("{}", x * x));
+    ///     x * x
+    /// });
+    /// assert_eq!(vec![1, 4, 9], squares);
+    /// assert_eq!(
+    ///     "\
+    /// Squares
+    /// ├╼ Item 1
+    /// │ └╼ 1
+    /// ├╼ Item 2
+    /// │ └╼ 4
+    /// └╼ Item 3
+    ///   └╼ 9",
+    ///     tree.peek_string()
+    /// );
+    /// ```
+    pub fn par_map<I, F, R>(&self, items: I, f: F) -> Vec<R>
+    where
+        I: IntoParallelIterator,
+        F: Fn(I::Item, &TreeBuilder) -> R + Sync + Send,
+        R: Send,
+    {
+        let results: Vec<_> = items
+            .into_par_iter()
+            .map(|x| {
+                let child = self.fork();
+                let result = f(x, &child);
+                (result, child)
+            })
+            .collect();
+        results
+            .into_iter()
+            .map(|(result, child)| {
+                self.join(child);
+                result
+            })
+            .collect()
+    }
+
+    /// Calls `f` on each item of `items` in parallel, with a separate tree for each item.
+    /// See [par_map](TreeBuilder::par_map).
+    pub fn par_for_each<I, F>(&self, items: I, f: F)
+    where
+        I: IntoParallelIterator,
+        F: Fn(I::Item, &TreeBuilder) + Sync + Send,
+    {
+        self.par_map(items, f);
+    }
+}
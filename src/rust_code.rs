@@ -0,0 +1,57 @@
+use crate::{Level, Tree};
+
+impl Tree {
+    /// Returns Rust code that rebuilds the nodes beneath this tree with the [TreeBuilder] named
+    /// `tree`, so that a captured trace can be turned into a test fixture.
+    /// Each branch is added in its own block with [add_branch](crate::TreeBuilder::add_branch),
+    /// and levels other than `Info` are kept with the `_at` variants.
+    /// Nodes without text are added with empty text.
+    ///
+    /// [TreeBuilder]: crate::TreeBuilder
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::{Level, TreeBuilder};
+    /// let tree = TreeBuilder::new();
+    /// {
+    ///     let _request = tree.add_branch("Request \"/\"");
+    ///     tree.add_leaf_at(Level::Warn, "Slow");
+    /// }
+    /// tree.add_leaf("Done");
+    /// assert_eq!(
+    ///     r#"{
+    ///     let _branch = tree.add_branch("Request \"/\"");
+    ///     tree.add_leaf_at(Level::Warn, "Slow");
+    /// }
+    /// tree.add_leaf("Done");
+    /// "#,
+    ///     tree.peek_rust_code("tree")
+    /// );
+    /// ```
+    pub fn to_rust_code(&self, tree: &str) -> String {
+        fn write(node: &Tree, tree: &str, depth: usize, out: &mut String) {
+            for x in &node.children {
+                let indent = "    ".repeat(depth);
+                let text = format!("{:?}", x.text.as_deref().unwrap_or(""));
+                let call = match (x.children.is_empty(), x.level) {
+                    (true, Level::Info) => format!("add_leaf({})", text),
+                    (true, level) => format!("add_leaf_at(Level::{:?}, {})", level, text),
+                    (false, Level::Info) => format!("add_branch({})", text),
+                    (false, level) => format!("add_branch_at(Level::{:?}, {})", level, text),
+                };
+                if x.children.is_empty() {
+                    out.push_str(&format!("{}{}.{};\n", indent, tree, call));
+                    continue;
+                }
+                out.push_str(&format!("{}{{\n", indent));
+                out.push_str(&format!("{}    let _branch = {}.{};\n", indent, tree, call));
+                write(x, tree, depth + 1, out);
+                out.push_str(&format!("{}}}\n", indent));
+            }
+        }
+        let mut out = String::new();
+        write(self, tree, 0, &mut out);
+        out
+    }
+}
@@ -1,3 +1,4 @@
+use crate::internal::Cursor;
 use crate::TreeBuilder;
 
 pub struct ScopedBranch {
@@ -24,3 +25,32 @@ impl Drop for ScopedBranch {
         self.release();
     }
 }
+
+/// Keeps the cursor of a tree inside a node until dropped, then moves it back to where it was.
+/// Returned by [add_root_branch](TreeBuilder::add_root_branch) and
+/// [enter_node](TreeBuilder::enter_node).
+pub struct ScopedCursor {
+    state: Option<(TreeBuilder, Cursor)>,
+}
+
+impl ScopedCursor {
+    pub(crate) fn new(tree: TreeBuilder, cursor: Option<Cursor>) -> ScopedCursor {
+        ScopedCursor {
+            state: cursor.map(|x| (tree, x)),
+        }
+    }
+    pub fn none() -> ScopedCursor {
+        ScopedCursor { state: None }
+    }
+    /// Moves the cursor back to where it was, without waiting to be dropped.
+    pub fn release(&mut self) {
+        if let Some((tree, cursor)) = self.state.take() {
+            tree.0.lock().leave(cursor);
+        }
+    }
+}
+impl Drop for ScopedCursor {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
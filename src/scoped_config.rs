@@ -0,0 +1,25 @@
+use crate::{TreeBuilder, TreeConfig};
+
+/// Restores the configuration override of a tree when it goes out of scope.
+pub struct ScopedConfig {
+    tree: TreeBuilder,
+    previous: Option<TreeConfig>,
+}
+
+impl ScopedConfig {
+    /// Overrides the configuration of `tree` with `config` until the returned guard is dropped.
+    pub fn new(tree: TreeBuilder, config: TreeConfig) -> ScopedConfig {
+        let previous = tree.get_config_override();
+        tree.set_config_override(config);
+        ScopedConfig { tree, previous }
+    }
+}
+
+impl Drop for ScopedConfig {
+    fn drop(&mut self) {
+        match self.previous.take() {
+            Some(x) => self.tree.set_config_override(x),
+            None => self.tree.remove_config_override(),
+        }
+    }
+}
@@ -0,0 +1,348 @@
+use crate::{Level, Tree};
+use serde::ser::{self, Serialize};
+use std::fmt;
+
+/// The structure of a serialized value: scalars become leaves, and maps, sequences, structs and
+/// enum variants with data become branches of keyed entries.
+enum Value {
+    Scalar(String),
+    Compound(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Converts the value to a node labelled `key`.
+    fn into_tree(self, key: &str) -> Tree {
+        match self {
+            Value::Scalar(x) => Tree::new(Some(&format!("{}: {}", key, x))),
+            Value::Compound(entries) if entries.is_empty() => {
+                Tree::new(Some(&format!("{}: {{}}", key)))
+            }
+            Value::Compound(entries) => {
+                let mut tree = Tree::new(Some(key));
+                tree.children = entries
+                    .into_iter()
+                    .map(|(key, value)| value.into_tree(&key))
+                    .collect();
+                tree
+            }
+        }
+    }
+
+    /// The text of the value when it is used as a map key.
+    fn into_key(self) -> String {
+        match self {
+            Value::Scalar(x) => x,
+            Value::Compound(_) => "{..}".to_string(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+/// Serializes a value into a [Value]. Strings are quoted, except in map keys.
+#[derive(Copy, Clone)]
+struct Serializer {
+    quote: bool,
+}
+
+const VALUE: Serializer = Serializer { quote: true };
+const KEY: Serializer = Serializer { quote: false };
+
+/// Collects the entries of a compound value, wrapping them in a `variant` entry if the value
+/// is an enum variant.
+struct Compound {
+    entries: Vec<(String, Value)>,
+    variant: Option<&'static str>,
+    key: Option<String>,
+}
+
+impl Compound {
+    fn new(variant: Option<&'static str>, len: Option<usize>) -> Self {
+        Compound {
+            entries: Vec::with_capacity(len.unwrap_or(0)),
+            variant,
+            key: None,
+        }
+    }
+
+    fn push_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        let key = format!("[{}]", self.entries.len());
+        self.entries.push((key, value.serialize(VALUE)?));
+        Ok(())
+    }
+
+    fn push_field<T: ?Sized + Serialize>(&mut self, key: &str, value: &T) -> Result<(), Error> {
+        self.entries
+            .push((key.to_string(), value.serialize(VALUE)?));
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, Error> {
+        let value = Value::Compound(self.entries);
+        Ok(match self.variant {
+            Some(variant) => Value::Compound(vec![(variant.to_string(), value)]),
+            None => value,
+        })
+    }
+}
+
+fn scalar<T: fmt::Display>(x: T) -> Result<Value, Error> {
+    Ok(Value::Scalar(x.to_string()))
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = Value;
+    type Error = Error;
+    type SerializeSeq = Compound;
+    type SerializeTuple = Compound;
+    type SerializeTupleStruct = Compound;
+    type SerializeTupleVariant = Compound;
+    type SerializeMap = Compound;
+    type SerializeStruct = Compound;
+    type SerializeStructVariant = Compound;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, Error> {
+        scalar(v)
+    }
+    fn serialize_i8(self, v: i8) -> Result<Value, Error> {
+        scalar(v)
+    }
+    fn serialize_i16(self, v: i16) -> Result<Value, Error> {
+        scalar(v)
+    }
+    fn serialize_i32(self, v: i32) -> Result<Value, Error> {
+        scalar(v)
+    }
+    fn serialize_i64(self, v: i64) -> Result<Value, Error> {
+        scalar(v)
+    }
+    fn serialize_i128(self, v: i128) -> Result<Value, Error> {
+        scalar(v)
+    }
+    fn serialize_u8(self, v: u8) -> Result<Value, Error> {
+        scalar(v)
+    }
+    fn serialize_u16(self, v: u16) -> Result<Value, Error> {
+        scalar(v)
+    }
+    fn serialize_u32(self, v: u32) -> Result<Value, Error> {
+        scalar(v)
+    }
+    fn serialize_u64(self, v: u64) -> Result<Value, Error> {
+        scalar(v)
+    }
+    fn serialize_u128(self, v: u128) -> Result<Value, Error> {
+        scalar(v)
+    }
+    fn serialize_f32(self, v: f32) -> Result<Value, Error> {
+        scalar(v)
+    }
+    fn serialize_f64(self, v: f64) -> Result<Value, Error> {
+        scalar(v)
+    }
+    fn serialize_char(self, v: char) -> Result<Value, Error> {
+        match self.quote {
+            true => scalar(format!("{:?}", v)),
+            false => scalar(v),
+        }
+    }
+    fn serialize_str(self, v: &str) -> Result<Value, Error> {
+        match self.quote {
+            true => scalar(format!("{:?}", v)),
+            false => scalar(v),
+        }
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, Error> {
+        scalar(format!("{:?}", v))
+    }
+    fn serialize_none(self) -> Result<Value, Error> {
+        scalar("None")
+    }
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Value, Error> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<Value, Error> {
+        scalar("()")
+    }
+    fn serialize_unit_struct(self, name: &'static str) -> Result<Value, Error> {
+        scalar(name)
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Value, Error> {
+        scalar(variant)
+    }
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value, Error> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, Error> {
+        Ok(Value::Compound(vec![(
+            variant.to_string(),
+            value.serialize(self)?,
+        )]))
+    }
+    fn serialize_seq(self, len: Option<usize>) -> Result<Compound, Error> {
+        Ok(Compound::new(None, len))
+    }
+    fn serialize_tuple(self, len: usize) -> Result<Compound, Error> {
+        Ok(Compound::new(None, Some(len)))
+    }
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<Compound, Error> {
+        Ok(Compound::new(None, Some(len)))
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Compound, Error> {
+        Ok(Compound::new(Some(variant), Some(len)))
+    }
+    fn serialize_map(self, len: Option<usize>) -> Result<Compound, Error> {
+        Ok(Compound::new(None, len))
+    }
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Compound, Error> {
+        Ok(Compound::new(None, Some(len)))
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Compound, Error> {
+        Ok(Compound::new(Some(variant), Some(len)))
+    }
+}
+
+impl ser::SerializeSeq for Compound {
+    type Ok = Value;
+    type Error = Error;
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push_element(value)
+    }
+    fn end(self) -> Result<Value, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for Compound {
+    type Ok = Value;
+    type Error = Error;
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push_element(value)
+    }
+    fn end(self) -> Result<Value, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for Compound {
+    type Ok = Value;
+    type Error = Error;
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push_element(value)
+    }
+    fn end(self) -> Result<Value, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for Compound {
+    type Ok = Value;
+    type Error = Error;
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push_element(value)
+    }
+    fn end(self) -> Result<Value, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeMap for Compound {
+    type Ok = Value;
+    type Error = Error;
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Error> {
+        self.key = Some(key.serialize(KEY)?.into_key());
+        Ok(())
+    }
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self.key.take().unwrap_or_default();
+        self.push_field(&key, value)
+    }
+    fn end(self) -> Result<Value, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for Compound {
+    type Ok = Value;
+    type Error = Error;
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.push_field(key, value)
+    }
+    fn end(self) -> Result<Value, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for Compound {
+    type Ok = Value;
+    type Error = Error;
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.push_field(key, value)
+    }
+    fn end(self) -> Result<Value, Error> {
+        self.finish()
+    }
+}
+
+/// Serializes `value` into a node labelled `label`, or into an error-level leaf if it can't
+/// be serialized.
+pub(crate) fn to_tree<T: ?Sized + Serialize>(label: &str, value: &T) -> Tree {
+    match value.serialize(VALUE) {
+        Ok(x) => x.into_tree(label),
+        Err(err) => {
+            let mut tree = Tree::new(Some(&format!("{}: {}", label, err)));
+            tree.level = Level::Error;
+            tree
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use crate::theme::visible_width;
+
+/// Lays out rendered trees, or any other text, in columns separated by `gutter`, so that traces
+/// can be compared line by line. Each column is as wide as its longest line, not counting ANSI
+/// escape sequences, and shorter columns are padded with blank lines.
+/// Trailing whitespace is removed from each line.
+///
+/// # Example
+///
+/// ```
+/// use debug_tree::{side_by_side, TreeBuilder};
+/// let old = TreeBuilder::new();
+/// let new = TreeBuilder::new();
+/// {
+///     let _a = old.add_branch("Sort");
+///     old.add_leaf("Bubble sort");
+///     old.add_leaf("Swaps: 45");
+/// }
+/// {
+///     let _a = new.add_branch("Sort");
+///     new.add_leaf("Merge sort");
+/// }
+/// assert_eq!(
+///     "\
+/// Sort            │ Sort
+/// ├╼ Bubble sort  │ └╼ Merge sort
+/// └╼ Swaps: 45    │",
+///     side_by_side(&[old.peek_string(), new.peek_string()], "  │ ")
+/// );
+/// ```
+pub fn side_by_side<S: AsRef<str>>(columns: &[S], gutter: &str) -> String {
+    let columns: Vec<Vec<&str>> = columns
+        .iter()
+        .map(|x| match x.as_ref() {
+            "" => vec![],
+            x => x.split('\n').collect(),
+        })
+        .collect();
+    let widths: Vec<usize> = columns
+        .iter()
+        .map(|x| x.iter().map(|x| visible_width(x)).max().unwrap_or(0))
+        .collect();
+    let height = columns.iter().map(Vec::len).max().unwrap_or(0);
+    let mut lines = Vec::with_capacity(height);
+    for row in 0..height {
+        let mut line = String::new();
+        for (i, column) in columns.iter().enumerate() {
+            let cell = column.get(row).copied().unwrap_or("");
+            if i > 0 {
+                line.push_str(gutter);
+            }
+            line.push_str(cell);
+            if i + 1 < columns.len() {
+                line.push_str(&" ".repeat(widths[i] - visible_width(cell)));
+            }
+        }
+        lines.push(line.trim_end().to_string());
+    }
+    lines.join("\n")
+}
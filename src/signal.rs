@@ -0,0 +1,117 @@
+use crate::default::default_trees;
+use crate::named_trees;
+use once_cell::sync::Lazy;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::os::raw::c_int;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, Once};
+use std::time::Duration;
+
+/// How often the dump thread checks whether a signal was received.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+static PENDING: AtomicBool = AtomicBool::new(false);
+static INSTALL: Once = Once::new();
+static DESTINATION: Lazy<Mutex<Option<PathBuf>>> = Lazy::new(|| Mutex::new(None));
+static LAST_ERROR: Lazy<Mutex<Option<std::io::Error>>> = Lazy::new(|| Mutex::new(None));
+
+extern "C" fn on_signal(_: c_int) {
+    // Only async-signal-safe work is allowed here; the dump thread does the rest.
+    PENDING.store(true, Ordering::SeqCst);
+}
+
+/// Renders every named tree and the default tree of every running thread, without clearing them.
+/// Empty trees are skipped.
+pub fn dump_all() -> String {
+    let mut ret = String::new();
+    let trees = named_trees()
+        .into_iter()
+        .map(|(name, tree)| (format!("tree \"{}\"", name), tree))
+        .chain(
+            default_trees()
+                .into_iter()
+                .map(|tree| ("default tree".to_string(), tree)),
+        );
+    for (name, tree) in trees {
+        let s = tree.peek_string();
+        if !s.is_empty() {
+            ret.push_str(&format!("== {} ==\n{}\n", name, s));
+        }
+    }
+    ret
+}
+
+fn dump() {
+    let s = dump_all();
+    let destination = DESTINATION.lock().unwrap().clone();
+    let result = match destination {
+        Some(path) => OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(s.as_bytes())),
+        None => std::io::stderr().write_all(s.as_bytes()),
+    };
+    if let Err(err) = result {
+        *LAST_ERROR.lock().unwrap() = Some(err);
+    }
+}
+
+/// Returns the error of the last dump that failed, if any, and clears it.
+/// Dumps happen on a background thread, so their errors are kept until they are taken here.
+pub fn take_dump_error() -> Option<std::io::Error> {
+    LAST_ERROR.lock().unwrap().take()
+}
+
+/// Installs `on_signal` as the handler for `SIGUSR1`.
+fn set_handler() -> std::io::Result<()> {
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = on_signal as extern "C" fn(c_int) as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        if libc::sigaction(libc::SIGUSR1, &action, std::ptr::null_mut()) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+fn install(destination: Option<PathBuf>) {
+    *DESTINATION.lock().unwrap() = destination;
+    INSTALL.call_once(|| {
+        std::thread::spawn(|| loop {
+            std::thread::sleep(POLL_INTERVAL);
+            if PENDING.swap(false, Ordering::SeqCst) {
+                dump();
+            }
+        });
+        if let Err(err) = set_handler() {
+            *LAST_ERROR.lock().unwrap() = Some(err);
+        }
+    });
+}
+
+/// Prints [dump_all] to stderr whenever the process receives `SIGUSR1`.
+///
+/// This replaces any existing handler for `SIGUSR1`.
+/// Errors, such as failing to write the trees, are kept for [take_dump_error].
+/// Calling it again, or calling [dump_on_sigusr1_to], only changes where the trees are written.
+///
+/// # Example
+///
+/// ```no_run
+/// debug_tree::signal::dump_on_sigusr1();
+/// // Later, from a shell: kill -USR1 <pid>
+/// ```
+pub fn dump_on_sigusr1() {
+    install(None)
+}
+
+/// Appends [dump_all] to the file at `path` whenever the process receives `SIGUSR1`.
+/// See [dump_on_sigusr1].
+pub fn dump_on_sigusr1_to<P: AsRef<Path>>(path: P) {
+    install(Some(path.as_ref().to_path_buf()))
+}
@@ -0,0 +1,205 @@
+use crate::human::HumanDuration;
+use crate::internal::Timing;
+use crate::sync::Lock;
+use crate::{Tree, TreeBuilder};
+use std::io::Write;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The indentation of span lines at each depth.
+const INDENT: &str = "  ";
+
+/// Appends `text` at `depth` to `out`, indenting each of its lines.
+fn push_line(out: &mut String, depth: usize, text: &str) {
+    for line in text.split('\n') {
+        out.push_str(&INDENT.repeat(depth));
+        out.push_str(line);
+        out.push('\n');
+    }
+}
+
+/// The summary shown after the `end` of a span.
+fn summary(children: usize, duration: Option<Duration>) -> String {
+    let children = match children {
+        1 => "1 child".to_string(),
+        n => format!("{} children", n),
+    };
+    match duration {
+        Some(x) => format!("({}, {})", children, HumanDuration(x)),
+        None => format!("({})", children),
+    }
+}
+
+impl Tree {
+    /// Renders the nodes beneath this tree as flat lines without box drawing: a leaf is rendered
+    /// as its text, and a branch as a `begin` line and an `end` line around its children,
+    /// with a summary of its number of children, and its duration if it was timed with
+    /// [set_timing](crate::TreeBuilder::set_timing). Each level is indented by two spaces.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// let tree = TreeBuilder::new();
+    /// {
+    ///     let _request = tree.add_branch("Request");
+    ///     tree.add_leaf("Parsed");
+    ///     tree.add_leaf("Handled");
+    /// }
+    /// assert_eq!(
+    ///     "begin Request\n  Parsed\n  Handled\nend Request (2 children)\n",
+    ///     tree.snapshot().to_spans()
+    /// );
+    /// ```
+    pub fn to_spans(&self) -> String {
+        fn write(tree: &Tree, depth: usize, out: &mut String) {
+            for x in &tree.children {
+                let text = x.text.as_deref().unwrap_or("");
+                if x.children.is_empty() {
+                    push_line(out, depth, text);
+                    continue;
+                }
+                push_line(out, depth, &format!("begin {}", text));
+                write(x, depth + 1, out);
+                let duration = x.metadata::<Timing>().and_then(Timing::duration);
+                let end = format!("end {} {}", text, summary(x.children.len(), duration));
+                push_line(out, depth, &end);
+            }
+        }
+        let mut out = String::new();
+        write(self, 0, &mut out);
+        out
+    }
+}
+
+/// A span that has begun but not ended.
+struct Span {
+    text: String,
+    start: Instant,
+    children: usize,
+}
+
+/// The state of a stream of span lines. A node is written once it is known whether it is
+/// entered, which is when the next change is made to the tree.
+struct SpanStream<W: Write> {
+    out: W,
+    /// The most recently added node, with its depth, if it hasn't been written yet.
+    pending: Option<(usize, String)>,
+    open: Vec<Span>,
+}
+
+impl<W: Write> SpanStream<W> {
+    fn write(&mut self, depth: usize, text: &str) {
+        let mut line = String::new();
+        push_line(&mut line, depth, text);
+        if let Err(err) = self
+            .out
+            .write_all(line.as_bytes())
+            .and_then(|_| self.out.flush())
+        {
+            eprintln!("debug_tree: failed to write span: {}", err);
+        }
+    }
+
+    /// Writes the pending node as a leaf.
+    fn flush_pending(&mut self) {
+        if let Some((depth, text)) = self.pending.take() {
+            self.write(depth, &text);
+        }
+    }
+
+    fn added(&mut self, depth: usize, text: &str) {
+        self.flush_pending();
+        if let Some(x) = self.open.last_mut() {
+            x.children += 1;
+        }
+        self.pending = Some((depth, text.to_string()));
+    }
+
+    fn entered(&mut self) {
+        let text = match self.pending.take() {
+            Some((depth, text)) => {
+                self.write(depth, &format!("begin {}", text));
+                text
+            }
+            None => {
+                let depth = self.open.len();
+                self.write(depth, "begin");
+                String::new()
+            }
+        };
+        self.open.push(Span {
+            text,
+            start: Instant::now(),
+            children: 0,
+        });
+    }
+
+    fn exited(&mut self) {
+        self.flush_pending();
+        if let Some(x) = self.open.pop() {
+            let summary = summary(x.children, Some(x.start.elapsed()));
+            let end = match x.text.as_str() {
+                "" => format!("end {}", summary),
+                text => format!("end {} {}", text, summary),
+            };
+            self.write(self.open.len(), &end);
+        }
+    }
+}
+
+impl<W: Write> Drop for SpanStream<W> {
+    /// Writes the last node added, which no further change will write.
+    fn drop(&mut self) {
+        self.flush_pending();
+    }
+}
+
+impl TreeBuilder {
+    /// Writes each change made to this tree to `out` as it happens, as the flat lines of
+    /// [Tree::to_spans]: entering a branch writes its `begin` line, and exiting it writes its
+    /// `end` line, with the time spent in the branch.
+    /// Each node is written when the next change is made, once it is known whether it is entered,
+    /// or when the tree is dropped.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::TreeBuilder;
+    /// use std::sync::{Arc, Mutex};
+    /// #[derive(Clone, Default)]
+    /// struct Log(Arc<Mutex<Vec<u8>>>);
+    /// impl std::io::Write for Log {
+    ///     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    ///         self.0.lock().unwrap().write(buf)
+    ///     }
+    ///     fn flush(&mut self) -> std::io::Result<()> {
+    ///         Ok(())
+    ///     }
+    /// }
+    /// let log = Log::default();
+    /// let tree = TreeBuilder::new();
+    /// tree.stream_spans_to(log.clone());
+    /// {
+    ///     let _request = tree.add_branch("Request");
+    ///     tree.add_leaf("Parsed");
+    ///     assert_eq!("begin Request\n", String::from_utf8_lossy(&log.0.lock().unwrap()));
+    /// }
+    /// let log = String::from_utf8_lossy(&log.0.lock().unwrap()).to_string();
+    /// assert!(log.starts_with("begin Request\n  Parsed\nend Request (1 child, "));
+    /// ```
+    pub fn stream_spans_to<W: Write + Send + 'static>(&self, out: W) {
+        let stream = Arc::new(Lock::new(SpanStream {
+            out,
+            pending: None,
+            open: vec![],
+        }));
+        let added = stream.clone();
+        self.on_node_added(move |id, text| {
+            added.lock().added(id.path().len().saturating_sub(1), text)
+        });
+        let entered = stream.clone();
+        self.on_enter(move |_| entered.lock().entered());
+        self.on_exit(move |_| stream.lock().exited());
+    }
+}
@@ -0,0 +1,41 @@
+//! The lock that guards the state of each tree.
+//!
+//! With the `parking_lot` feature this is a `parking_lot` mutex, otherwise it wraps
+//! `std::sync::Mutex`. Neither is poisoned by a panic while the lock is held.
+
+use std::panic::{RefUnwindSafe, UnwindSafe};
+
+#[cfg(feature = "parking_lot")]
+type Inner<T> = parking_lot::Mutex<T>;
+#[cfg(feature = "parking_lot")]
+type Guard<'a, T> = parking_lot::MutexGuard<'a, T>;
+
+#[cfg(not(feature = "parking_lot"))]
+type Inner<T> = std::sync::Mutex<T>;
+#[cfg(not(feature = "parking_lot"))]
+type Guard<'a, T> = std::sync::MutexGuard<'a, T>;
+
+#[derive(Debug, Default)]
+pub(crate) struct Lock<T>(Inner<T>);
+
+impl<T> Lock<T> {
+    pub fn new(value: T) -> Lock<T> {
+        Lock(Inner::new(value))
+    }
+
+    #[cfg(feature = "parking_lot")]
+    pub fn lock(&self) -> Guard<'_, T> {
+        self.0.lock()
+    }
+
+    #[cfg(not(feature = "parking_lot"))]
+    pub fn lock(&self) -> Guard<'_, T> {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+// Like `std::sync::Mutex`, so a tree can be used across `catch_unwind` with either lock.
+impl<T> UnwindSafe for Lock<T> {}
+impl<T> RefUnwindSafe for Lock<T> {}
@@ -0,0 +1,29 @@
+use std::io::IsTerminal;
+
+#[cfg(unix)]
+mod size {
+    /// The number of columns of the terminal that stdout is connected to.
+    pub fn columns() -> Option<usize> {
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        match unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } {
+            0 if size.ws_col > 0 => Some(size.ws_col as usize),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod size {
+    pub fn columns() -> Option<usize> {
+        None
+    }
+}
+
+/// The width of the terminal that stdout is connected to, if it is a terminal.
+/// The `COLUMNS` environment variable is used if the width can't be queried.
+pub(crate) fn stdout_width() -> Option<usize> {
+    if !std::io::stdout().is_terminal() {
+        return None;
+    }
+    size::columns().or_else(|| std::env::var("COLUMNS").ok()?.parse().ok())
+}
@@ -1,8 +1,9 @@
 #[cfg(test)]
+#[allow(clippy::module_inception, clippy::useless_format)]
 mod test {
+    use crate::theme::{Color, Theme};
     use crate::*;
     use futures::future::join5;
-    use std::cmp::{max, min};
     use std::fs::{create_dir, read_to_string, remove_file};
 
     #[test]
@@ -566,6 +567,1873 @@ Branch
         );
     }
 
+    #[test]
+    fn format_output_compact() {
+        let tree = example_tree();
+        tree.set_config_override(TreeConfig::new().compact());
+        tree.peek_print();
+        assert_eq!(
+            tree.string(),
+            "\
+1
+├1.1
+│├1.1.1
+│├1.1.2
+││With two
+││extra lines
+│└1.1.3
+└1.2
+ └1.2.1
+2
+├2.1
+└2.2
+3"
+        );
+    }
+
+    #[test]
+    fn child_count() {
+        let tree = example_tree();
+        tree.set_config_override(TreeConfig::new().child_count(ChildCount::Children));
+        tree.peek_print();
+        assert_eq!(
+            tree.peek_string(),
+            "\
+1 (2 children)
+├╼ 1.1 (3 children)
+│ ├╼ 1.1.1
+│ ├╼ 1.1.2
+│ │  With two
+│ │  extra lines
+│ └╼ 1.1.3
+└╼ 1.2 (1 child)
+  └╼ 1.2.1
+2 (2 children)
+├╼ 2.1
+└╼ 2.2
+3"
+        );
+        tree.set_config_override(TreeConfig::new().child_count(ChildCount::Descendants));
+        tree.peek_print();
+        assert_eq!(
+            tree.string(),
+            "\
+1 (6 descendants)
+├╼ 1.1 (3 descendants)
+│ ├╼ 1.1.1
+│ ├╼ 1.1.2
+│ │  With two
+│ │  extra lines
+│ └╼ 1.1.3
+└╼ 1.2 (1 descendant)
+  └╼ 1.2.1
+2 (2 descendants)
+├╼ 2.1
+└╼ 2.2
+3"
+        );
+    }
+
+    #[test]
+    fn line_numbers() {
+        let tree = example_tree();
+        tree.set_config_override(TreeConfig::new().show_line_numbers());
+        tree.peek_print();
+        assert_eq!(
+            tree.string(),
+            " 1 1
+ 2 ├╼ 1.1
+ 3 │ ├╼ 1.1.1
+ 4 │ ├╼ 1.1.2
+ 5 │ │  With two
+ 6 │ │  extra lines
+ 7 │ └╼ 1.1.3
+ 8 └╼ 1.2
+ 9   └╼ 1.2.1
+10 2
+11 ├╼ 2.1
+12 └╼ 2.2
+13 3"
+        );
+    }
+
+    #[test]
+    fn node_id() {
+        let tree = TreeBuilder::new();
+        let a = tree.add_leaf_id("a").unwrap();
+        let _branch = tree.enter_scoped();
+        let b = tree.add_leaf_id("b").unwrap();
+        assert_eq!(&[0], a.path());
+        assert_eq!(&[0, 0], b.path());
+        assert!(tree.set_node_text(&a, "A"));
+        assert_eq!(Some("b".to_string()), tree.node_text(&b));
+        assert_eq!("A\n└╼ b", tree.string());
+        assert!(!tree.set_node_text(&a, "A"));
+        tree.set_enabled(false);
+        assert_eq!(None, tree.add_leaf_id("c"));
+    }
+
+    #[derive(Debug)]
+    struct ChainedError(&'static str, Option<Box<ChainedError>>);
+    impl std::fmt::Display for ChainedError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+    impl std::error::Error for ChainedError {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            self.1
+                .as_ref()
+                .map(|x| x.as_ref() as &(dyn std::error::Error + 'static))
+        }
+    }
+
+    #[test]
+    fn error_chain() {
+        let tree = TreeBuilder::new();
+        let err = ChainedError(
+            "a",
+            Some(Box::new(ChainedError(
+                "b",
+                Some(Box::new(ChainedError("c", None))),
+            ))),
+        );
+        {
+            add_branch_to!(tree, "1");
+            add_error_to!(tree, &err);
+            add_leaf_to!(tree, "1.2");
+        }
+        add_leaf_to!(tree, "2");
+        tree.peek_print();
+        assert_eq!(
+            "\
+1
+├╼ a
+│ └╼ b
+│   └╼ c
+└╼ 1.2
+2",
+            tree.string()
+        );
+    }
+
+    #[test]
+    fn catch_panic() {
+        let tree = TreeBuilder::new();
+        assert_eq!(3, tree.catch("Add", || 1 + 2));
+        let result = std::panic::catch_unwind(|| {
+            tree.catch("Outer", || {
+                tree.catch("Inner", || {
+                    panic!("Failed at {}", 42);
+                })
+            })
+        });
+        assert!(result.is_err());
+        tree.add_leaf("After");
+        tree.peek_print();
+        assert_eq!(
+            "\
+Add
+Outer
+├╼ Inner
+│ └╼ panicked: Failed at 42
+└╼ panicked: Failed at 42
+After",
+            tree.string()
+        );
+    }
+
+    #[test]
+    fn checkpoint_scopes() {
+        let tree = TreeBuilder::new();
+        let _outer = tree.add_branch("Outer");
+        tree.checkpoint("Start");
+        {
+            let _inner = tree.add_branch("Inner");
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            let inner = tree.checkpoint("Inner");
+            assert!(inner >= std::time::Duration::from_millis(10));
+            assert!(tree.checkpoint("Inner again") < inner);
+        }
+        assert!(tree.checkpoint("End") >= std::time::Duration::from_millis(10));
+        assert_eq!(6, tree.peek_string().lines().count());
+    }
+
+    #[test]
+    fn level_filter() {
+        let tree = TreeBuilder::builder().level(Level::Info).build();
+        assert!(tree.is_level_enabled(Level::Warn));
+        assert!(!tree.is_level_enabled(Level::Debug));
+        {
+            let _branch = tree.add_branch_at(Level::Debug, "Debug branch");
+            tree.add_leaf_at(Level::Trace, "Trace");
+            tree.add_leaf("Info");
+        }
+        {
+            let _branch = tree.add_branch("Info branch");
+            tree.add_leaf_at(Level::Debug, "Debug");
+            tree.add_leaf_at(Level::Error, "Error");
+        }
+        tree.set_level(Level::Trace);
+        tree.add_leaf_at(Level::Trace, "Trace");
+        tree.set_enabled(false);
+        assert!(!tree.is_level_enabled(Level::Error));
+        assert_eq!(
+            "\
+Info
+Info branch
+└╼ Error
+Trace",
+            tree.string()
+        );
+    }
+
+    #[test]
+    fn module_filter() {
+        use crate::filter::ModuleFilter;
+        let filter = ModuleFilter::parse("on, app::noisy = off ,app::noisy::important=on").unwrap();
+        assert!(filter.is_enabled("app"));
+        assert!(!filter.is_enabled("app::noisy"));
+        assert!(!filter.is_enabled("app::noisy::detail"));
+        assert!(filter.is_enabled("app::noisy::important::x"));
+        assert!(ModuleFilter::parse("app=maybe").is_err());
+        assert!(!ModuleFilter::parse("").unwrap().is_enabled("app"));
+    }
+
+    #[test]
+    fn targets() {
+        let tree = TreeBuilder::new();
+        add_leaf_to!(tree, "Macro");
+        {
+            add_branch_to!(tree, target: "app::net", "Net");
+            add_leaf_to!(tree, target: "app::net::tcp", "Tcp");
+            add_leaf_to!(tree, target: "app::db", "Db inside net");
+        }
+        {
+            add_branch_to!(tree, target: "app::db", "Db");
+            add_leaf_to!(tree, target: "app::net", "Net inside db");
+            add_leaf_to!(tree, "Other");
+        }
+        let mut targets = vec![];
+        tree.visit(|x, _| targets.push(x.target));
+        assert_eq!(Some(module_path!()), targets[0]);
+        tree.set_config_override(TreeConfig::new().only_target("app::net"));
+        tree.peek_print();
+        assert_eq!(
+            "\
+Net
+├╼ Tcp
+└╼ Db inside net
+Db
+└╼ Net inside db",
+            tree.peek_string()
+        );
+        tree.set_config_override(TreeConfig::new());
+        {
+            let _branch = tree.add_branch_target("app::net", "Current");
+            tree.add_leaf_target("app::net", "Child");
+            tree.prune_target("app::net");
+            tree.add_leaf("Added after prune");
+        }
+        tree.add_leaf("Last");
+        tree.peek_print();
+        assert_eq!(
+            "\
+Macro
+Db
+└╼ Other
+Current
+├╼ Child
+└╼ Added after prune
+Last",
+            tree.string()
+        );
+    }
+
+    #[test]
+    fn multiline_continuation() {
+        let tree = TreeBuilder::new();
+        {
+            add_branch_to!(tree, "1\nsecond line");
+            {
+                add_branch_to!(tree, "1.1\nsecond line");
+                add_leaf_to!(tree, "1.1.1");
+            }
+            add_leaf_to!(tree, "1.2");
+        }
+        tree.set_config_override(TreeConfig::new().indent(4).show_first_level());
+        tree.peek_print();
+        assert_eq!(
+            "\
+└──╼ 1
+     second line
+    ├──╼ 1.1
+    │    second line
+    │   └──╼ 1.1.1
+    └──╼ 1.2",
+            tree.peek_string()
+        );
+        tree.set_config_override(TreeConfig::new().indent(4).multiline_indent(2));
+        tree.peek_print();
+        assert_eq!(
+            "\
+1
+second line
+├──╼ 1.1
+│ second line
+│   └──╼ 1.1.1
+└──╼ 1.2",
+            tree.peek_string()
+        );
+        tree.set_config_override(TreeConfig::new().indent(4).multiline_indent(7));
+        assert_eq!(
+            "\
+1
+second line
+├──╼ 1.1
+│   │  second line
+│   └──╼ 1.1.1
+└──╼ 1.2",
+            tree.string()
+        );
+    }
+
+    #[test]
+    fn root_text() {
+        let tree = example_tree();
+        tree.set_config_override(TreeConfig::new());
+        tree.set_root_text("Root");
+        assert_eq!(Some("Root".to_string()), tree.root_text());
+        tree.peek_print();
+        assert_eq!(
+            tree.peek_string(),
+            "\
+Root
+├╼ 1
+│ ├╼ 1.1
+│ │ ├╼ 1.1.1
+│ │ ├╼ 1.1.2
+│ │ │  With two
+│ │ │  extra lines
+│ │ └╼ 1.1.3
+│ └╼ 1.2
+│   └╼ 1.2.1
+├╼ 2
+│ ├╼ 2.1
+│ └╼ 2.2
+└╼ 3"
+        );
+        tree.remove_root_text();
+        assert_eq!(None, tree.root_text());
+        assert!(tree.string().starts_with("1\n"));
+    }
+
+    #[test]
+    fn control_chars() {
+        let tree = TreeBuilder::new();
+        {
+            add_branch_to!(tree, "progress\r100%");
+            add_leaf_to!(tree, "\x1b[31mred\x1b[0m\tleaf");
+        }
+        tree.set_config_override(TreeConfig::new().escape_control_chars());
+        assert_eq!(
+            "\
+progress\\r100%
+└╼ \\u{1b}[31mred\\u{1b}[0m\\tleaf",
+            tree.peek_string()
+        );
+        tree.set_config_override(TreeConfig::new().strip_control_chars());
+        assert_eq!(
+            "\
+progress100%
+└╼ redleaf",
+            tree.string()
+        );
+    }
+
+    #[test]
+    fn newline_normalization() {
+        let tree = TreeBuilder::new();
+        {
+            add_branch_to!(tree, "windows\r\nline  ");
+            add_leaf_to!(tree, "trailing \nspace\t");
+        }
+        tree.set_config_override(
+            TreeConfig::new()
+                .normalize_newlines()
+                .trim_trailing_whitespace(),
+        );
+        assert_eq!(
+            "\
+windows
+line
+└╼ trailing
+   space",
+            tree.peek_string()
+        );
+        tree.set_config_override(
+            TreeConfig::new()
+                .normalize_newlines()
+                .trim_trailing_whitespace()
+                .flatten_newlines(),
+        );
+        assert_eq!(
+            "\
+windows⏎line
+└╼ trailing⏎space",
+            tree.string()
+        );
+    }
+
+    #[test]
+    fn annotations() {
+        let tree = TreeBuilder::new();
+        {
+            add_branch_to!(tree, "Request");
+            add_leaf_to!(tree, "Parse\nheaders");
+            tree.annotate("3ms");
+            add_leaf_to!(tree, "Respond");
+            tree.annotate("120ms");
+        }
+        tree.set_config_override(TreeConfig::new().gutter_width(6).show_line_numbers());
+        assert_eq!(
+            "\
+1 Request
+2 ├╼ Parse      3ms
+3 │  headers
+4 └╼ Respond  120ms",
+            tree.string()
+        );
+    }
+
+    #[test]
+    fn align_columns() {
+        let tree = TreeBuilder::new();
+        {
+            add_branch_to!(tree, "Files");
+            add_leaf_to!(tree, "a.txt\t12\tok");
+            add_leaf_to!(tree, "Not a row");
+            {
+                add_branch_to!(tree, "longer.txt\t3\tmissing");
+                add_leaf_to!(tree, "x\ty");
+            }
+        }
+        tree.set_config_override(TreeConfig::new().align_columns());
+        assert_eq!(
+            "\
+Files
+├╼ a.txt       12  ok
+├╼ Not a row
+└╼ longer.txt  3   missing
+  └╼ x  y",
+            tree.peek_string()
+        );
+        tree.set_config_override(TreeConfig::new());
+        assert!(tree.string().starts_with("Files\n├╼ a.txt\t12\tok\n"));
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn live_view() {
+        let tree = TreeBuilder::new();
+        tree.add_leaf("Started");
+        let out = SharedBuffer::default();
+        let view = crate::live::LiveView::start_with_writer(
+            tree.clone(),
+            std::time::Duration::from_secs(3600),
+            out.clone(),
+        );
+        tree.add_leaf("Finished");
+        view.stop();
+        assert_eq!(
+            "Started\n\x1b[1F\x1b[JStarted\nFinished\n",
+            String::from_utf8(out.0.lock().unwrap().clone()).unwrap()
+        );
+    }
+
+    #[test]
+    fn html() {
+        let tree = TreeBuilder::new();
+        tree.set_root_text("Job & Co");
+        {
+            add_branch_to!(tree, "Branch");
+            tree.add_leaf_at(Level::Debug, "Detail");
+            tree.annotate("3ms");
+        }
+        let html = tree.html();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<title>Job &amp; Co</title>"));
+        assert!(html.contains(
+            r#"<li class="info" data-level="info"><details open><summary><span class="text">Branch</span></summary><ul>"#
+        ));
+        assert!(html.contains(
+            r#"<li class="debug" data-level="debug"><div class="leaf"><span class="text">Detail</span><span class="annotation">3ms</span></div></li>"#
+        ));
+        assert!(html.contains(r#"<input id="search""#));
+        assert!(html.contains(r#"<button id="collapse">"#));
+        assert_eq!("", tree.peek_string().replace("Job & Co", ""));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn signal_dump() {
+        let path = "test_out/signal_dump.txt";
+        let _ = create_dir("test_out");
+        let _ = remove_file(path);
+        tree("signal_dump").add_leaf("In flight");
+        crate::signal::dump_on_sigusr1_to(path);
+        unsafe {
+            libc::raise(libc::SIGUSR1);
+        }
+        for _ in 0..100 {
+            if read_to_string(path)
+                .map(|x| x.contains("In flight"))
+                .unwrap_or(false)
+            {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        let dump = read_to_string(path).unwrap();
+        assert!(dump.contains("== tree \"signal_dump\" ==\nIn flight\n"));
+        assert_eq!("In flight", tree("signal_dump").peek_string());
+        assert!(crate::signal::take_dump_error().is_none());
+    }
+
+    #[test]
+    fn observers() {
+        let tree = TreeBuilder::new();
+        let log = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let added = log.clone();
+        tree.on_node_added(move |id, text| {
+            added
+                .lock()
+                .unwrap()
+                .push(format!("add {:?} {}", id.path(), text))
+        });
+        let entered = log.clone();
+        tree.on_enter(move |depth| entered.lock().unwrap().push(format!("enter {}", depth)));
+        let exited = log.clone();
+        tree.on_exit(move |depth| exited.lock().unwrap().push(format!("exit {}", depth)));
+        {
+            add_branch_to!(tree, "1");
+            add_leaf_to!(tree, "1.1");
+        }
+        tree.clear();
+        add_leaf_to!(tree, "2");
+        assert_eq!(
+            vec![
+                "add [0] 1",
+                "enter 1",
+                "add [0, 0] 1.1",
+                "exit 0",
+                "add [0] 2"
+            ],
+            *log.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn record_replay() {
+        let tree = TreeBuilder::new();
+        add_leaf_to!(tree, "Before recording");
+        tree.start_recording();
+        {
+            add_branch_to!(tree, "1");
+            add_leaf_to!(tree, "1.1");
+        }
+        tree.print();
+        add_leaf_to!(tree, "2");
+        let events = tree.stop_recording();
+        add_leaf_to!(tree, "After recording");
+        assert_eq!(6, events.len());
+        assert_eq!(TreeEvent::Cleared, events[4]);
+        assert_eq!("2", TreeBuilder::from_events(&events).peek_string());
+        assert_eq!(
+            "1\n└╼ 1.1",
+            TreeBuilder::from_events(&events[..4]).peek_string()
+        );
+        assert!(tree.stop_recording().is_empty());
+    }
+
+    #[test]
+    fn event_log() {
+        let events = vec![
+            TreeEvent::LeafAdded {
+                path: vec![0],
+                text: "1".to_string(),
+            },
+            TreeEvent::Entered,
+            TreeEvent::LeafAdded {
+                path: vec![0, 0],
+                text: "Two\nlines".to_string(),
+            },
+            TreeEvent::Exited,
+            TreeEvent::Cleared,
+        ];
+        let mut log: Vec<u8> = events.iter().flat_map(|x| x.encode()).collect();
+        assert_eq!(
+            "leaf 0 1\n1\nenter\nleaf 0.0 9\nTwo\nlines\nexit\nclear\n",
+            String::from_utf8(log.clone()).unwrap()
+        );
+        assert_eq!(events, crate::event::decode_log(&log));
+        // A record cut short by a crash is ignored
+        log.extend_from_slice(b"leaf 1 10\nunfin");
+        assert_eq!(events, crate::event::decode_log(&log));
+        // As is a length that would overflow
+        let corrupt = format!("leaf 0 {}\nx\n", usize::MAX);
+        assert!(crate::event::decode_log(corrupt.as_bytes()).is_empty());
+    }
+
+    #[test]
+    fn from_indented_str() {
+        let text = "a\n    b\n\n\tc\n         d\ne";
+        let tree = Tree::from_indented_str(text, 4).unwrap();
+        assert_eq!(
+            "\
+a
+├╼ b
+└╼ c
+  └╼ d
+e",
+            TreeBuilder::from(tree).peek_string()
+        );
+        assert_eq!(
+            Err("line 2: indented more than one level deeper than the line before it".to_string()),
+            Tree::from_indented_str("a\n    b", 2).map(|_| ())
+        );
+        assert!(Tree::from_indented_str("  a", 2).is_err());
+    }
+
+    #[test]
+    fn jsonl() {
+        let tree = TreeBuilder::from_jsonl(
+            r#"{"op":"leaf","text":"aé😀\n\tb","ts":[1, 2.5e3, null, true]}
+{"op":"enter"}
+
+{"op":"leaf","text":"c","extra":{"nested":{}}}
+"#,
+        )
+        .unwrap();
+        assert_eq!("aé😀\n\tb\n└╼ c", tree.peek_string());
+        assert_eq!(
+            Err("line 2: unknown op `jump`".to_string()),
+            TreeBuilder::from_jsonl("{\"op\":\"enter\"}\n{\"op\":\"jump\"}").map(|_| ())
+        );
+        assert!(TreeBuilder::from_jsonl("{\"op\":\"leaf\"").is_err());
+    }
+
+    #[test]
+    fn json_rejects_bad_input() {
+        let tree = Tree::from_json(r#""\ud83d\ude00""#).unwrap();
+        assert_eq!("😀", TreeBuilder::from(tree).peek_string());
+        assert!(Tree::from_json(r#""\ud800\u0041""#).is_err());
+        assert_eq!(
+            Err("nested too deeply".to_string()),
+            Tree::from_json(&"[".repeat(200_000)).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn unordered() {
+        let tree = TreeBuilder::new();
+        tree.set_root_text("Ignored");
+        {
+            add_branch_to!(tree, "b");
+            add_leaf_to!(tree, "2");
+            add_leaf_to!(tree, "1");
+        }
+        add_leaf_to!(tree, "a");
+        assert_tree_unordered!(tree, "a\nb\n  1\n  2");
+        let moved = Tree::from_indented_str("a\n  1\nb\n  2", 2).unwrap();
+        let snapshot = tree.snapshot();
+        assert!(!moved.eq_unordered(&snapshot));
+        let result = std::panic::catch_unwind(|| assert_tree_unordered!(tree, "a\nb\n  1"));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn wildcards() {
+        let tree = TreeBuilder::new();
+        {
+            add_branch_to!(tree, "Request 4f2a");
+            add_leaf_to!(tree, "Took 12ms");
+            {
+                add_branch_to!(tree, "Retry");
+                add_leaf_to!(tree, "Took 3ms");
+            }
+        }
+        add_leaf_to!(tree, "Done");
+        assert_tree_matches!(tree, "Request *\n  Took *ms\n  **\nDone");
+        assert_tree_matches!(tree, "**\nDone");
+        assert_tree_matches!(tree, "*\n  **\n  *\n    *\n*");
+        assert_tree_unordered!(tree, "Done\nRequest*\n  Retry\n    **\n  **");
+        let pattern = |x| Tree::from_indented_str(x, 2).unwrap();
+        let snapshot = tree.snapshot();
+        assert!(!snapshot.matches(&pattern("Request *\n  Took *ms\nDone")));
+        assert!(!snapshot.matches(&pattern("Done\n**")));
+        assert!(!snapshot.matches(&pattern("Request*s\n  **\nDone")));
+        assert!(!snapshot.matches_unordered(&pattern("Done\nDone\n**")));
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn redact() {
+        use regex::Regex;
+        let tree = TreeBuilder::new();
+        {
+            add_branch_to!(
+                tree,
+                "Wrote /tmp/.tmpA1b2C3/out.txt at 2024-01-02T03:04:05Z"
+            );
+            add_leaf_to!(tree, "Took 12ms");
+        }
+        tree.set_config_override(
+            TreeConfig::new()
+                .redact(Regex::new(r"/tmp/[^/]+").unwrap(), "$$TMP")
+                .redact(Regex::new(r"\d{4}-\d\d-\d\dT[\d:]+Z").unwrap(), "<time>")
+                .redact(Regex::new(r"(\d+)ms").unwrap(), "<$1>"),
+        );
+        assert_eq!(
+            "Wrote $TMP/out.txt at <time>\n└╼ Took <12>",
+            tree.peek_string()
+        );
+        tree.set_config_override(tree.get_config_override().unwrap().no_redactions());
+        assert_eq!("Took 12ms", tree.peek_string().split("└╼ ").nth(1).unwrap());
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn stable_ids() {
+        let tree = TreeBuilder::new();
+        tree.set_root_text("Session 123e4567-e89b-12d3-a456-426614174000");
+        {
+            add_branch_to!(tree, "Alloc 0xdead at 0x10");
+            add_leaf_to!(tree, "Free 0xbeef");
+        }
+        add_leaf_to!(tree, "Free 0xdead (123e4567-e89b-12d3-a456-426614174000)");
+        tree.set_config_override(TreeConfig::new().stable_addresses().stable_uuids());
+        assert_eq!(
+            "\
+Session #1
+├╼ Alloc #2 at #3
+│ └╼ Free #4
+└╼ Free #2 (#1)",
+            tree.peek_string()
+        );
+    }
+
+    #[test]
+    fn collapse_chains() {
+        let tree = TreeBuilder::new();
+        {
+            add_branch_to!(tree, "expr");
+            {
+                add_branch_to!(tree, "term");
+                {
+                    add_branch_to!(tree, "factor");
+                    add_leaf_to!(tree, "1");
+                    add_leaf_to!(tree, "+");
+                    {
+                        add_branch_to!(tree, "factor");
+                        add_leaf_to!(tree, "2");
+                    }
+                }
+            }
+        }
+        add_leaf_to!(tree, "eof");
+        tree.set_config_override(TreeConfig::new().collapse_chains(" ▸ "));
+        assert_eq!(
+            "\
+expr ▸ term ▸ factor
+├╼ 1
+├╼ +
+└╼ factor ▸ 2
+eof",
+            tree.peek_string()
+        );
+    }
+
+    #[test]
+    fn mute() {
+        let tree = TreeBuilder::new();
+        {
+            add_branch_to!(tree, "1");
+            add_leaf_to!(tree, "1.1");
+            {
+                add_branch_to!(tree, "1.2");
+                tree.mute_current();
+                assert!(tree.is_muted());
+                {
+                    add_branch_to!(tree, "1.2.1");
+                    add_leaf_to!(tree, "1.2.1.1");
+                }
+                add_leaf_to!(tree, "1.2.2");
+            }
+            assert!(!tree.is_muted());
+            add_leaf_to!(tree, "1.3");
+        }
+        add_leaf_to!(tree, "2");
+        assert_eq!(
+            "\
+1
+├╼ 1.1
+├╼ 1.2
+└╼ 1.3
+2",
+            tree.peek_string()
+        );
+    }
+
+    #[test]
+    fn append_to_last() {
+        let tree = TreeBuilder::new();
+        tree.append_to_last("Nothing to append to");
+        {
+            add_branch_to!(tree, "Connecting");
+            tree.append_to_last("…");
+            add_leaf_to!(tree, "Resolving");
+            tree.append_to_last(" done");
+        }
+        tree.append_to_last(" ok");
+        assert_eq!("Connecting… ok\n└╼ Resolving done", tree.peek_string());
+    }
+
+    #[test]
+    fn fork_join() {
+        let tree = TreeBuilder::new();
+        let child = {
+            add_branch_to!(tree, "Outer");
+            let child = tree.fork();
+            {
+                add_branch_to!(tree, "Inner");
+                add_leaf_to!(tree, "Inner leaf");
+            }
+            child
+        };
+        {
+            let _branch = child.add_branch("Forked");
+            child.add_leaf("Forked leaf");
+        }
+        let other = TreeBuilder::new().fork();
+        other.add_leaf("Unrelated");
+        tree.join(child);
+        tree.join(other);
+        add_leaf_to!(tree, "Last");
+        assert_eq!(
+            "\
+Outer
+├╼ Inner
+│ └╼ Inner leaf
+└╼ Forked
+  └╼ Forked leaf
+Unrelated
+Last",
+            tree.peek_string()
+        );
+
+        let child = tree.fork();
+        tree.clear();
+        child.add_leaf("After clear");
+        tree.join(child);
+        assert_eq!("After clear", tree.peek_string());
+    }
+
+    #[test]
+    fn markup() {
+        let tree = TreeBuilder::new();
+        {
+            add_branch_to!(tree, "Called `parse` with **2** args");
+            add_leaf_to!(tree, "2 * 3 = 6, ** and ` alone are kept");
+        }
+        assert_eq!(
+            "Called `parse` with **2** args\n└╼ 2 * 3 = 6, ** and ` alone are kept",
+            tree.peek_string()
+        );
+        tree.set_config_override(TreeConfig::new().ansi_markup());
+        assert_eq!(
+            "Called \x1b[36mparse\x1b[39m with \x1b[1m2\x1b[22m args\n└╼ 2 * 3 = 6, ** and ` alone are kept",
+            tree.peek_string()
+        );
+        tree.set_config_override(TreeConfig::new().strip_markup());
+        assert_eq!(
+            "Called parse with 2 args\n└╼ 2 * 3 = 6, ** and ` alone are kept",
+            tree.peek_string()
+        );
+        assert!(tree
+            .peek_html()
+            .contains("Called <code>parse</code> with <b>2</b> args"));
+    }
+
+    #[test]
+    fn attachments() {
+        let tree = TreeBuilder::new();
+        {
+            add_branch_to!(tree, "Request");
+            tree.attach("body", "<payload>\n");
+            add_leaf_to!(tree, "Response");
+            tree.attach("body", &"x".repeat(2048));
+            tree.attach("headers", "");
+        }
+        assert_eq!(
+            "Request [attachment: 10 B]\n└╼ Response [attachment: 2.0 KiB] [attachment: 0 B]",
+            tree.peek_string()
+        );
+        let html = tree.peek_html();
+        assert!(html.contains("<summary>body (10 B)</summary><pre>&lt;payload&gt;\n</pre>"));
+        assert!(html.contains(&"x".repeat(2048)));
+        let json = tree.peek_json();
+        assert!(json.contains(r#"{"name":"body","content":"<payload>\n"}"#));
+        let tree = Tree::from_json(&json).unwrap();
+        assert_eq!(json, tree.to_json());
+        assert_eq!("headers", tree.children[0].children[0].attachments[1].name);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn add_serde() {
+        use serde::ser::{Serialize, SerializeStruct, SerializeTupleVariant, Serializer};
+        enum Shape {
+            Square(u32),
+            Rect(u32, u32),
+        }
+        impl Serialize for Shape {
+            fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                match self {
+                    Shape::Square(x) => s.serialize_newtype_variant("Shape", 0, "Square", x),
+                    Shape::Rect(w, h) => {
+                        let mut x = s.serialize_tuple_variant("Shape", 1, "Rect", 2)?;
+                        x.serialize_field(w)?;
+                        x.serialize_field(h)?;
+                        x.end()
+                    }
+                }
+            }
+        }
+        struct Layer {
+            name: &'static str,
+            shapes: Vec<Shape>,
+        }
+        impl Serialize for Layer {
+            fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                let mut x = s.serialize_struct("Layer", 2)?;
+                x.serialize_field("name", self.name)?;
+                x.serialize_field("shapes", &self.shapes)?;
+                x.end()
+            }
+        }
+        let layer = Layer {
+            name: "background",
+            shapes: vec![Shape::Square(2), Shape::Rect(3, 4)],
+        };
+        let tree = TreeBuilder::new();
+        add_serde_to!(tree, layer);
+        assert_eq!(
+            "\
+layer
+├╼ name: \"background\"
+└╼ shapes
+  ├╼ [0]
+  │ └╼ Square: 2
+  └╼ [1]
+    └╼ Rect
+      ├╼ [0]: 3
+      └╼ [1]: 4",
+            tree.peek_string()
+        );
+    }
+
+    #[test]
+    fn retain_nodes_keeps_cursor() {
+        let tree = TreeBuilder::new();
+        {
+            let _a = tree.add_branch("wrapper");
+            tree.add_leaf("a");
+            tree.add_leaf("b");
+        }
+        let _c = tree.add_branch("c");
+        tree.add_leaf("c.a");
+        tree.retain_nodes(|x| x.text.as_deref() != Some("wrapper"));
+        tree.add_leaf("c.b");
+        assert_eq!("a\nb\nc\n├╼ c.a\n└╼ c.b", tree.peek_string());
+    }
+
+    #[test]
+    fn query() {
+        let tree = Tree::from_depths(vec![(0, "a"), (1, "b"), (2, "a"), (0, "c")]).unwrap();
+        let paths = |pattern| {
+            tree.query(pattern)
+                .into_iter()
+                .map(|(path, _)| path)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            vec![vec![0], vec![0, 0], vec![0, 0, 0], vec![1]],
+            paths("**")
+        );
+        assert_eq!(vec![vec![0], vec![0, 0, 0]], paths("**/a"));
+        assert_eq!(vec![vec![0, 0, 0]], paths("a/**/a"));
+        assert_eq!(vec![vec![0, 0]], paths("*/*"));
+        assert!(paths("b").is_empty());
+    }
+
+    #[test]
+    fn literal_text_is_borrowed() {
+        use std::borrow::Cow;
+        let tree = TreeBuilder::new();
+        add_leaf_to!(tree, "Literal");
+        let n = tree.depth() + 1;
+        add_leaf_to!(tree, "Formatted {}", n);
+        tree.add_leaf_text("Static");
+        tree.add_leaf("Copied");
+        let snapshot = tree.snapshot();
+        let borrowed: Vec<bool> = snapshot
+            .children
+            .iter()
+            .map(|x| matches!(x.text, Some(Cow::Borrowed(_))))
+            .collect();
+        assert_eq!(vec![true, false, true, false], borrowed);
+        assert_eq!("Literal\nFormatted 1\nStatic\nCopied", tree.peek_string());
+    }
+
+    #[test]
+    fn overhead_tracking() {
+        let tree = TreeBuilder::new();
+        assert_eq!(None, tree.overhead());
+        assert_eq!(None, tree.overhead_report());
+        tree.set_overhead_tracking(true);
+        {
+            let _a = tree.add_branch("a");
+            let _b = tree.add_branch("b");
+            tree.add_leaf("c");
+        }
+        let snapshot = tree.snapshot();
+        let overhead = |x: &Tree| x.metadata::<Overhead>().unwrap().0;
+        let a = &snapshot.children[0];
+        assert!(overhead(a) + overhead(&a.children[0]) <= tree.overhead().unwrap());
+        tree.clear();
+        assert_eq!(Some(std::time::Duration::default()), tree.overhead());
+        tree.set_overhead_tracking(false);
+        tree.add_leaf("d");
+        assert_eq!(None, tree.snapshot().children[0].metadata::<Overhead>());
+    }
+
+    #[test]
+    fn auto_flush_keeps_ancestors() {
+        let out = Arc::new(Mutex::new(Vec::new()));
+        let tree = TreeBuilder::new();
+        tree.add_leaf("before");
+        {
+            let _a = tree.add_branch("a");
+            let _b = tree.add_branch("b");
+            tree.add_leaf("b1");
+            tree.set_auto_flush(1, out.clone());
+            tree.add_leaf("b2");
+            tree.disable_auto_flush();
+            tree.add_leaf("b3");
+        }
+        tree.add_leaf("after");
+        assert_eq!(
+            "before\na\n└╼ b\n  └╼ b1\n",
+            String::from_utf8_lossy(&out.lock().unwrap())
+        );
+        assert_eq!("a\n└╼ b\n  ├╼ b2\n  └╼ b3\nafter", tree.peek_string());
+    }
+
+    #[test]
+    fn auto_flush_error_keeps_tree() {
+        struct Full;
+        impl std::io::Write for Full {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("full"))
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        let tree = TreeBuilder::new();
+        tree.set_auto_flush(1, Arc::new(Mutex::new(Full)));
+        tree.add_leaf("first");
+        tree.add_leaf("second");
+        assert_eq!("first\nsecond", tree.peek_string());
+        assert_eq!("full", tree.take_auto_flush_error().unwrap().to_string());
+        assert!(tree.take_auto_flush_error().is_none());
+    }
+
+    #[test]
+    fn theme_colors() {
+        let tree = TreeBuilder::new();
+        tree.set_config_override(
+            TreeConfig::new()
+                .child_count(ChildCount::Children)
+                .show_line_numbers()
+                .theme(Theme {
+                    depth_palette: vec![Color::Blue, Color::Green],
+                    error: Some(Color::Red),
+                    connector: Some(Color::Dim),
+                    metadata: Some(Color::Yellow),
+                    ..Theme::new()
+                }),
+        );
+        {
+            let _a = tree.add_branch("a");
+            tree.add_leaf_at(Level::Error, "b");
+        }
+        assert_eq!(
+            "\x1b[33m1\x1b[39m \x1b[34ma\x1b[39m\x1b[33m (1 child)\x1b[39m\n\
+             \x1b[33m2\x1b[39m \x1b[2m└╼ \x1b[22m\x1b[31mb\x1b[39m",
+            tree.peek_string()
+        );
+        tree.set_config_override(TreeConfig::new().theme(Theme::monochrome()));
+        assert_eq!("a\n\x1b[2m└╼ \x1b[22m\x1b[1mb\x1b[22m", tree.peek_string());
+    }
+
+    #[test]
+    fn stream_spans_matches_to_spans() {
+        #[derive(Clone, Default)]
+        struct Log(Arc<Mutex<Vec<u8>>>);
+        impl std::io::Write for Log {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        let log = Log::default();
+        let tree = TreeBuilder::new();
+        tree.stream_spans_to(log.clone());
+        tree.add_leaf("a");
+        {
+            let _b = tree.add_branch("b");
+            let _c = tree.add_branch("c");
+            tree.add_leaf("d\ne");
+        }
+        tree.add_leaf("f");
+        tree.add_leaf("g");
+        let streamed = String::from_utf8_lossy(&log.0.lock().unwrap()).to_string();
+        let without_durations = |x: &str| {
+            x.lines()
+                .map(|x| {
+                    x.split(", ")
+                        .next()
+                        .unwrap()
+                        .trim_end_matches(')')
+                        .to_string()
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            "a\nbegin b\n  begin c\n    d\n    e\n  end c (1 child)\nend b (1 child)\nf\ng\n",
+            tree.peek_spans()
+        );
+        // The last node is written once the next change is made
+        assert_eq!(
+            without_durations(&tree.peek_spans().replace("\ng\n", "\n")),
+            without_durations(&streamed)
+        );
+        // The last node is written when the tree is dropped
+        let spans = tree.peek_spans();
+        drop(tree);
+        let streamed = String::from_utf8_lossy(&log.0.lock().unwrap()).to_string();
+        assert_eq!(without_durations(&spans), without_durations(&streamed));
+    }
+
+    #[test]
+    fn outline_numbers_are_kept_when_filtered() {
+        let tree = TreeBuilder::new();
+        tree.set_config_override(TreeConfig::new().outline_numbers().errors_only());
+        tree.add_leaf("a");
+        {
+            let _b = tree.add_branch("b");
+            tree.add_leaf("c");
+            tree.add_leaf_at(Level::Error, "d");
+        }
+        assert_eq!("2. b\n└╼ 2.2. d", tree.peek_string());
+    }
+
+    #[test]
+    fn enter_node_restores_cursor() {
+        let tree = TreeBuilder::new();
+        let id = {
+            let _a = tree.add_branch("a");
+            tree.add_leaf_id("b").unwrap()
+        };
+        let _c = tree.add_branch("c");
+        tree.add_leaf("c.1");
+        {
+            let _b = tree.enter_node(&id).unwrap();
+            let _d = tree.add_branch("d");
+            tree.add_leaf("e");
+        }
+        tree.add_leaf("c.2");
+        assert_eq!(
+            "a\n└╼ b\n  └╼ d\n    └╼ e\nc\n├╼ c.1\n└╼ c.2",
+            tree.peek_string()
+        );
+        tree.clear();
+        assert!(tree.enter_node(&id).is_none());
+    }
+
+    #[test]
+    fn rust_code_reproduces_tree() {
+        let tree = TreeBuilder::new();
+        {
+            let _a = tree.add_branch("a");
+            let _b = tree.add_branch_at(Level::Error, "b");
+            tree.add_leaf("c\nd");
+        }
+        let code = tree.peek_rust_code("t");
+        assert_eq!(
+            "{\n    let _branch = t.add_branch(\"a\");\n    {\n        \
+             let _branch = t.add_branch_at(Level::Error, \"b\");\n        \
+             t.add_leaf(\"c\\nd\");\n    }\n}\n",
+            code
+        );
+        // The same calls, as the code runs them
+        let t = TreeBuilder::new();
+        {
+            let _branch = t.add_branch("a");
+            {
+                let _branch = t.add_branch_at(Level::Error, "b");
+                t.add_leaf("c\nd");
+            }
+        }
+        assert_eq!(tree.peek_string(), t.peek_string());
+    }
+
+    #[test]
+    fn fingerprint_is_stable() {
+        let tree = Tree::from_depths(vec![(0, "a"), (1, "b"), (0, "c")]).unwrap();
+        // Changing this value breaks fingerprints stored by users
+        assert_eq!(0xaf3a_dc07_744b_b25a, tree.fingerprint());
+        let other = Tree::from_depths(vec![(0, "a"), (0, "b"), (0, "c")]).unwrap();
+        assert_ne!(tree.fingerprint(), other.fingerprint());
+        let split = Tree::from_depths(vec![(0, "ab"), (0, "c")]).unwrap();
+        assert_ne!(other.fingerprint(), split.fingerprint());
+    }
+
+    #[test]
+    fn newest_first_tail() {
+        let tree = TreeBuilder::new();
+        tree.set_config_override(TreeConfig::new().newest_first(NewestFirst::TopLevel));
+        for i in 1..=4 {
+            tree.add_leaf(&i.to_string());
+        }
+        assert_eq!("4\n3", tree.peek_string_tail(2));
+        assert_eq!("4\n3\n2\n1", tree.peek_string_tail(10));
+    }
+
+    #[test]
+    fn line_prefix_per_node() {
+        let tree = TreeBuilder::new();
+        tree.set_config_override(
+            TreeConfig::new()
+                .separator("")
+                .show_line_numbers()
+                .severity_summary()
+                .line_prefix(|node| match node {
+                    Some(x) => format!("{}| ", x.text.as_deref().unwrap_or("").len()),
+                    None => "-| ".to_string(),
+                }),
+        );
+        {
+            let _branch = tree.add_branch("ab");
+            tree.add_leaf("c\nd");
+        }
+        tree.add_leaf("e");
+        assert_eq!(
+            "2| 1 ab\n3| 2 └╼ c\n3| 3    d\n-| 4 \n1| 5 e\n-| 0 errors, 0 warnings, 3 nodes",
+            tree.peek_string()
+        );
+    }
+
+    #[test]
+    fn node_text_leaves_tree_unchanged() {
+        let tree = TreeBuilder::new();
+        {
+            let _branch = tree.add_branch("Load");
+            tree.enter();
+            tree.add_leaf("Parse");
+        }
+        tree.set_config_override(TreeConfig::new().node_text(|node| {
+            let text = node.text.as_deref().unwrap_or("?");
+            format!("{} ({})", text, node.children.len())
+        }));
+        assert_eq!("Load (1)\n└╼ ? (1)\n  └╼ Parse (0)", tree.peek_string());
+        assert_eq!(None, tree.snapshot().children[0].children[0].text);
+        tree.set_config_override(TreeConfig::new());
+        assert_eq!("Load\n└╼ \n  └╼ Parse", tree.peek_string());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn nested_capture_output() {
+        use std::io::Write;
+        let tree = TreeBuilder::new();
+        let answer = tree.capture_output("Outer", || {
+            writeln!(std::io::stdout(), "outer line").unwrap();
+            tree.capture_output("Inner", || {
+                writeln!(std::io::stdout(), "inner line").unwrap();
+                42
+            })
+        });
+        assert_eq!(42, answer);
+        let tree = tree.snapshot();
+        let outer = &tree.children[0];
+        assert_eq!(Some("Inner"), outer.children[0].text.as_deref());
+        assert!(outer.children[0].children.is_empty());
+        let lines: Vec<_> = outer.children[1..]
+            .iter()
+            .filter_map(|x| x.text.as_deref())
+            .collect();
+        assert!(lines.contains(&"outer line"));
+        assert!(lines.contains(&"inner line"));
+    }
+
+    #[test]
+    fn result_and_option_levels() {
+        let tree = TreeBuilder::new();
+        let _: Result<u8, &str> = add_result_to!(tree, Ok(1));
+        let _: Result<u8, &str> = add_result_to!(tree, Err("Too big"));
+        let _ = add_option_to!(tree, Some(2));
+        let _: Option<u8> = add_option_to!(tree, None);
+        let levels: Vec<_> = tree.snapshot().children.iter().map(|x| x.level).collect();
+        assert_eq!(
+            vec![Level::Info, Level::Error, Level::Info, Level::Warn],
+            levels
+        );
+        tree.set_config_override(TreeConfig::new().errors_only());
+        assert_eq!("Err(\"Too big\")", tree.peek_string());
+    }
+
+    #[test]
+    fn thread_timeline_only_records_when_enabled() {
+        let tree = TreeBuilder::new();
+        tree.add_leaf("Untracked");
+        tree.set_thread_timeline(true);
+        tree.add_leaf("Tracked");
+        let snapshot = tree.snapshot();
+        let origin = |i: usize| snapshot.children[i].metadata::<crate::internal::Origin>();
+        assert!(origin(0).is_none());
+        assert!(origin(1).is_some());
+    }
+
+    #[test]
+    fn concurrent_mounts_dont_form_a_cycle() {
+        for _ in 0..200 {
+            let a = TreeBuilder::new();
+            let b = TreeBuilder::new();
+            let barrier = Arc::new(std::sync::Barrier::new(2));
+            let (a2, b2, barrier2) = (a.clone(), b.clone(), barrier.clone());
+            let handle = std::thread::spawn(move || {
+                barrier2.wait();
+                b2.mount("a", &a2)
+            });
+            barrier.wait();
+            let mounted_b = a.mount("b", &b);
+            let mounted_a = handle.join().unwrap();
+            assert!(!(mounted_a && mounted_b));
+        }
+    }
+
+    #[test]
+    fn mounted_content_is_unflushed() {
+        let app = TreeBuilder::new();
+        let parser = TreeBuilder::new();
+        app.mount("parser", &parser);
+        assert!(!app.has_unflushed_content());
+        parser.add_leaf("Parsed");
+        assert!(app.has_unflushed_content());
+        assert_eq!("parser\n└╼ Parsed", app.string());
+        assert!(!app.has_unflushed_content());
+    }
+
+    #[test]
+    fn write_paged_keeps_tree_on_error() {
+        struct FailAfter(usize);
+        impl std::io::Write for FailAfter {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                match self.0 {
+                    0 => Err(std::io::Error::other("full")),
+                    _ => {
+                        self.0 -= 1;
+                        Ok(())
+                    }
+                }
+            }
+        }
+        let tree = TreeBuilder::new();
+        for i in 1..=3 {
+            tree.add_leaf(&i.to_string());
+        }
+        assert!(tree.write_paged(FailAfter(1), 1, |_| true).is_err());
+        assert_eq!("1\n2\n3", tree.peek_string());
+        let mut out = Vec::new();
+        let mut pages = vec![];
+        tree.write_paged(&mut out, 2, |n| {
+            pages.push(n);
+            true
+        })
+        .unwrap();
+        assert_eq!(vec![1], pages);
+        assert_eq!("1\n2\n3\n", String::from_utf8(out).unwrap());
+        assert_eq!("", tree.peek_string());
+        assert!(tree.print_paged(2).is_ok());
+    }
+
+    #[test]
+    fn write_paged_keeps_nodes_added_while_writing() {
+        let tree = TreeBuilder::new();
+        for i in 1..=3 {
+            tree.add_leaf(&i.to_string());
+        }
+        let mut out = Vec::new();
+        tree.write_paged(&mut out, 2, |_| {
+            tree.add_leaf("4");
+            true
+        })
+        .unwrap();
+        assert_eq!("1\n2\n3\n", String::from_utf8(out).unwrap());
+        assert_eq!("4", tree.peek_string());
+        tree.add_leaf("5");
+        assert_eq!("4\n5", tree.peek_string());
+    }
+
+    #[test]
+    fn errors_only_keeps_ancestors_and_descendants() {
+        let tree = TreeBuilder::new();
+        {
+            let _request = tree.add_branch("Request");
+            tree.add_leaf("Parsed");
+            {
+                let _query = tree.add_branch("Query");
+                tree.add_leaf("Sent");
+                let _failed = tree.add_branch_at(Level::Error, "Timed out");
+                tree.add_leaf("Retry budget exhausted");
+            }
+            tree.add_leaf("Responded");
+        }
+        tree.add_leaf("Idle");
+        tree.set_config_override(TreeConfig::new().errors_only());
+        assert_eq!(
+            "Request\n└╼ Query\n  └╼ Timed out\n    └╼ Retry budget exhausted",
+            tree.peek_string()
+        );
+        let clean = TreeBuilder::new();
+        clean.add_leaf("Idle");
+        clean.set_config_override(TreeConfig::new().errors_only());
+        assert_eq!("", clean.peek_string());
+    }
+
+    #[test]
+    fn scoped_defaults_dropped_out_of_order() {
+        let outer = TreeBuilder::new();
+        let inner = TreeBuilder::new();
+        let later = TreeBuilder::new();
+        let outer_guard = set_default_scoped(&outer);
+        let inner_guard = set_default_scoped(&inner);
+        add_leaf!("inner");
+        drop(outer_guard);
+        add_leaf!("thread default");
+        let later_guard = set_default_scoped(&later);
+        drop(inner_guard);
+        add_leaf!("later");
+        drop(later_guard);
+        assert_eq!("", outer.peek_string());
+        assert_eq!("inner", inner.peek_string());
+        assert_eq!("later", later.peek_string());
+        assert_eq!("thread default", default_tree().peek_string());
+    }
+
+    #[test]
+    fn spawn_joins_nested_and_panicking_threads() {
+        {
+            add_branch!("Request");
+            let handle = crate::spawn(|| {
+                add_branch!("Worker");
+                crate::spawn(|| add_leaf!("Nested")).join().unwrap();
+                add_leaf!("Done");
+            });
+            handle.join().unwrap();
+            let failed = crate::spawn(|| {
+                add_leaf!("Before panic");
+                panic!("worker failed");
+            });
+            assert!(failed.join().is_err());
+        }
+        add_leaf!("After");
+        assert_eq!(
+            "Request\n├╼ Worker\n│ ├╼ Nested\n│ └╼ Done\n└╼ Before panic\nAfter",
+            default_tree().peek_string()
+        );
+    }
+
+    #[test]
+    fn empty_node_policy_at_top_level() {
+        let tree = TreeBuilder::new();
+        tree.enter();
+        tree.enter();
+        tree.add_leaf("Deep");
+        tree.add_leaf("Sibling");
+        tree.exit();
+        tree.exit();
+        tree.add_leaf("Top");
+        tree.set_config_override(TreeConfig::new().empty_nodes(EmptyNodePolicy::Skip));
+        assert_eq!("Deep\nSibling\nTop", tree.peek_string());
+        tree.set_config_override(TreeConfig::new().empty_nodes(EmptyNodePolicy::Placeholder("…")));
+        assert_eq!("…\n└╼ …\n  ├╼ Deep\n  └╼ Sibling\nTop", tree.peek_string());
+        tree.set_config_override(TreeConfig::new());
+        assert_eq!("\n└╼ \n  ├╼ Deep\n  └╼ Sibling\nTop", tree.peek_string());
+        assert_eq!(None, tree.snapshot().children[0].text);
+    }
+
+    #[test]
+    fn root_adds_while_nested() {
+        let tree = TreeBuilder::new();
+        {
+            let _a = tree.add_branch("A");
+            tree.enter();
+            tree.add_root_leaf("Milestone");
+            tree.add_leaf("Deep");
+            tree.exit();
+            {
+                let _root = tree.add_root_branch("Root");
+                tree.add_root_leaf("Inner milestone");
+                tree.add_leaf("In root");
+            }
+            tree.add_leaf("Back in A");
+        }
+        tree.add_leaf("Top");
+        assert_eq!(
+            "\
+A
+├╼ 
+│ └╼ Deep
+└╼ Back in A
+Milestone
+Root
+└╼ In root
+Inner milestone
+Top",
+            tree.peek_string()
+        );
+    }
+
+    #[test]
+    fn side_by_side_edge_cases() {
+        let red = Color::Red.paint("r");
+        assert_eq!(
+            format!("{}  |  | x\nbb |  | yy\n   |  | z", red),
+            side_by_side(
+                &[
+                    format!("{}\nbb", red),
+                    String::new(),
+                    "x\nyy\nz".to_string()
+                ],
+                " | "
+            )
+        );
+        assert_eq!("", side_by_side::<&str>(&[], " | "));
+        assert_eq!("a |\nb |", side_by_side(&["a\nb", ""], " | "));
+    }
+
+    #[test]
+    fn tree_equality_edge_cases() {
+        let tree = TreeBuilder::new();
+        tree.add_leaf("a");
+        tree.enter();
+        tree.add_leaf_at(Level::Error, "b");
+        let mut annotated = tree.snapshot();
+        annotated.annotation = Some("note".to_string());
+        let plain = Tree::from_depths(vec![(0, "a"), (1, "b")]).unwrap();
+        assert_eq!(plain, annotated);
+        let mut empty = Tree::new(None);
+        empty.children.push(Tree::new(None));
+        let mut blank = Tree::new(None);
+        blank.children.push(Tree::new(Some("")));
+        assert_ne!(empty, blank);
+        assert!(!empty.eq_ignoring(&blank, |_| String::new()));
+        let deep = || {
+            let mut tree = Tree::new(None);
+            for _ in 0..100_000 {
+                let mut parent = Tree::new(Some("x"));
+                parent.children.push(tree);
+                tree = parent;
+            }
+            tree
+        };
+        assert_eq!(deep(), deep());
+    }
+
+    #[test]
+    fn configure_default_can_use_the_default_tree() {
+        std::thread::spawn(|| {
+            configure_default(|x| {
+                add_leaf!("Configuring");
+                x
+            });
+            assert_eq!("Configuring", default_tree().string());
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn extend_depths_when_disabled() {
+        let tree = TreeBuilder::new();
+        tree.set_enabled(false);
+        tree.extend_depths(vec![(0, "a"), (1, "b")]).unwrap();
+        assert!(tree.extend_depths(vec![(1, "a")]).is_err());
+        tree.set_enabled(true);
+        assert_eq!("", tree.peek_string());
+    }
+
+    #[test]
+    fn join_honours_enabled_and_level() {
+        let tree = TreeBuilder::new();
+        tree.set_level(Level::Info);
+        let child = tree.fork();
+        child.set_level(Level::Trace);
+        {
+            let _branch = child.add_branch_at(Level::Debug, "Hidden");
+            child.add_leaf_at(Level::Warn, "Shown");
+        }
+        tree.join(child);
+        assert_eq!("Shown", tree.peek_string());
+
+        let child = tree.fork();
+        child.add_leaf("Dropped");
+        tree.set_enabled(false);
+        tree.join(child);
+        tree.set_enabled(true);
+        assert_eq!("Shown", tree.peek_string());
+    }
+
+    #[test]
+    fn severity_summary() {
+        let tree = TreeBuilder::new();
+        tree.set_config_override(TreeConfig::new().severity_summary());
+        assert_eq!("", tree.peek_string());
+        for i in 0..1234 {
+            tree.add_leaf_at(if i < 2 { Level::Error } else { Level::Info }, "x");
+        }
+        assert!(tree
+            .peek_string()
+            .ends_with("x\nx\n2 errors, 0 warnings, 1,234 nodes"));
+    }
+
+    #[test]
+    fn max_width() {
+        let tree = TreeBuilder::new();
+        {
+            add_branch_to!(tree, "Branch with a long name");
+            add_leaf_to!(tree, "A child with a fairly long text");
+            add_leaf_to!(tree, "Short");
+        }
+        tree.set_config_override(TreeConfig::new().max_width(16));
+        assert_eq!(
+            "\
+Branch with a l…
+├╼ A child with…
+└╼ Short",
+            tree.peek_string()
+        );
+        tree.set_config_override(TreeConfig::new().max_width(16).wrap_overflow());
+        assert_eq!(
+            "\
+Branch with a lo
+ng name
+├╼ A child with\x20
+│  a fairly long
+│   text
+└╼ Short",
+            tree.peek_string()
+        );
+        assert!(tree.peek_string().lines().all(|x| x.chars().count() <= 16));
+    }
+
+    #[test]
+    fn drop_deep_tree() {
+        let mut tree = Tree::new(Some("Leaf"));
+        for _ in 0..1_000_000 {
+            let mut parent = Tree::new(Some("Branch"));
+            parent.children.push(tree);
+            tree = parent;
+        }
+        drop(tree);
+    }
+
+    #[test]
+    fn enabled_without_locking() {
+        let tree = TreeBuilder::new();
+        let seen = Arc::new(Mutex::new(vec![]));
+        let (handle, seen2) = (tree.clone(), seen.clone());
+        // Callbacks run while the tree is locked.
+        tree.on_node_added(move |_, _| seen2.lock().unwrap().push(handle.is_enabled()));
+        add_leaf_to!(tree, "Leaf");
+        tree.clear();
+        assert!(tree.is_enabled());
+        tree.set_enabled(false);
+        tree.clear();
+        assert!(!tree.is_enabled());
+        add_leaf_to!(tree, "Skipped");
+        assert_eq!(vec![true], *seen.lock().unwrap());
+    }
+
+    #[test]
+    fn thread_timeline() {
+        let tree = TreeBuilder::new();
+        tree.set_thread_timeline(true);
+        let child = tree.fork();
+        let handle = std::thread::Builder::new()
+            .name("worker".to_string())
+            .spawn(move || {
+                child.add_leaf("Started");
+                child
+            })
+            .unwrap();
+        let child = handle.join().unwrap();
+        {
+            add_branch_to!(tree, "Main work");
+            add_leaf_to!(tree, "Step");
+        }
+        tree.join(child);
+        assert_eq!("Main work\n└╼ Step\nStarted", tree.peek_string());
+        tree.set_config_override(TreeConfig::new().thread_timeline());
+        assert_eq!(
+            format!(
+                "[worker] Started\n[{}] Main work\n└╼ Step",
+                std::thread::current().name().unwrap()
+            ),
+            tree.peek_string()
+        );
+    }
+
+    #[cfg(feature = "log")]
+    #[test]
+    fn flush_log() {
+        struct Logger(Mutex<Vec<String>>);
+        impl log::Log for Logger {
+            fn enabled(&self, _: &log::Metadata) -> bool {
+                true
+            }
+            fn log(&self, record: &log::Record) {
+                if record.target() == "debug_tree::flush_log" {
+                    self.0
+                        .lock()
+                        .unwrap()
+                        .push(format!("{} {}", record.level(), record.args()));
+                }
+            }
+            fn flush(&self) {}
+        }
+        static LOGGER: Lazy<Logger> = Lazy::new(|| Logger(Mutex::new(vec![])));
+        log::set_logger(&*LOGGER).unwrap();
+        log::set_max_level(log::LevelFilter::Trace);
+
+        let tree = TreeBuilder::new();
+        {
+            add_branch_to!(tree, "Branch");
+            add_leaf_to!(tree, "Leaf");
+        }
+        tree.peek_log(log::Level::Info, "debug_tree::flush_log");
+        tree.flush_log(log::Level::Warn, "debug_tree::flush_log");
+        assert_eq!("", tree.peek_string());
+        assert_eq!(
+            vec!["INFO Branch", "INFO └╼ Leaf", "WARN Branch", "WARN └╼ Leaf"],
+            *LOGGER.0.lock().unwrap()
+        );
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_map() {
+        let tree = TreeBuilder::new();
+        tree.set_level(Level::Info);
+        add_branch_to!(tree, "Parallel");
+        let results = tree.par_map(0..200, |x, tree| {
+            let _branch = tree.add_branch(&format!("{}", x));
+            tree.add_leaf_at(Level::Warn, &format!("warn {}", x));
+            tree.add_leaf_at(Level::Debug, &format!("debug {}", x));
+            x
+        });
+        assert_eq!((0..200).collect::<Vec<_>>(), results);
+        let snapshot = tree.snapshot();
+        let items = &snapshot.children[0].children;
+        assert_eq!(200, items.len());
+        for (i, x) in items.iter().enumerate() {
+            assert_eq!(Some(i.to_string().as_str()), x.text.as_deref());
+            assert_eq!(1, x.children.len());
+            assert_eq!(
+                Some(format!("warn {}", i).as_str()),
+                x.children[0].text.as_deref()
+            );
+            assert_eq!(Level::Warn, x.children[0].level);
+        }
+    }
+
+    #[test]
+    fn separator() {
+        let tree = example_tree();
+        tree.set_config_override(TreeConfig::new().separator(""));
+        tree.peek_print();
+        assert_eq!(
+            tree.peek_string(),
+            "\
+1
+├╼ 1.1
+│ ├╼ 1.1.1
+│ ├╼ 1.1.2
+│ │  With two
+│ │  extra lines
+│ └╼ 1.1.3
+└╼ 1.2
+  └╼ 1.2.1
+
+2
+├╼ 2.1
+└╼ 2.2
+
+3"
+        );
+        tree.set_config_override(TreeConfig::new().separator("---").show_first_level());
+        tree.peek_print();
+        assert_eq!(
+            tree.string(),
+            "\
+├╼ 1
+│ ├╼ 1.1
+│ │ ├╼ 1.1.1
+│ │ ├╼ 1.1.2
+│ │ │  With two
+│ │ │  extra lines
+│ │ └╼ 1.1.3
+│ └╼ 1.2
+│   └╼ 1.2.1
+│ ---
+├╼ 2
+│ ├╼ 2.1
+│ └╼ 2.2
+│ ---
+└╼ 3"
+        );
+    }
+
     async fn wait_a_bit(tree: TreeBuilder, index: usize) {
         tree.print();
         add_branch_to!(tree, "inside async branch {}", index);
@@ -0,0 +1,202 @@
+use crate::level::Level;
+
+/// A terminal color or text attribute, rendered with ANSI escape sequences.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    BrightBlack,
+    BrightRed,
+    BrightGreen,
+    BrightYellow,
+    BrightBlue,
+    BrightMagenta,
+    BrightCyan,
+    BrightWhite,
+    /// A color of the 256-color palette
+    Fixed(u8),
+    /// A 24-bit color
+    Rgb(u8, u8, u8),
+    /// Bold text, in the default color
+    Bold,
+    /// Faint text, in the default color
+    Dim,
+}
+
+impl Color {
+    /// The escape sequences that start and end text in this color.
+    fn codes(self) -> (String, &'static str) {
+        let fg = |x: u8| (format!("\x1b[{}m", x), "\x1b[39m");
+        match self {
+            Color::Black => fg(30),
+            Color::Red => fg(31),
+            Color::Green => fg(32),
+            Color::Yellow => fg(33),
+            Color::Blue => fg(34),
+            Color::Magenta => fg(35),
+            Color::Cyan => fg(36),
+            Color::White => fg(37),
+            Color::BrightBlack => fg(90),
+            Color::BrightRed => fg(91),
+            Color::BrightGreen => fg(92),
+            Color::BrightYellow => fg(93),
+            Color::BrightBlue => fg(94),
+            Color::BrightMagenta => fg(95),
+            Color::BrightCyan => fg(96),
+            Color::BrightWhite => fg(97),
+            Color::Fixed(x) => (format!("\x1b[38;5;{}m", x), "\x1b[39m"),
+            Color::Rgb(r, g, b) => (format!("\x1b[38;2;{};{};{}m", r, g, b), "\x1b[39m"),
+            Color::Bold => ("\x1b[1m".to_string(), "\x1b[22m"),
+            Color::Dim => ("\x1b[2m".to_string(), "\x1b[22m"),
+        }
+    }
+
+    /// Wraps `text` in the escape sequences of this color. Empty text is left as it is.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::theme::Color;
+    /// assert_eq!("\x1b[31merror\x1b[39m", Color::Red.paint("error"));
+    /// ```
+    pub fn paint(self, text: &str) -> String {
+        if text.is_empty() {
+            return String::new();
+        }
+        let (start, end) = self.codes();
+        format!("{}{}{}", start, text, end)
+    }
+}
+
+/// Paints `text` with `color`, if any.
+pub(crate) fn paint(color: Option<Color>, text: &str) -> String {
+    match color {
+        Some(x) => x.paint(text),
+        None => text.to_string(),
+    }
+}
+
+/// The number of characters in `text`, not counting ANSI escape sequences.
+pub(crate) fn visible_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // Skip the parameters and final byte of a CSI sequence, e.g. `\x1b[31m`
+            if chars.next() == Some('[') {
+                for x in chars.by_ref() {
+                    if ('\u{40}'..='\u{7e}').contains(&x) {
+                        break;
+                    }
+                }
+            }
+        } else {
+            width += 1;
+        }
+    }
+    width
+}
+
+/// The colors used to render a tree in a terminal.
+/// Start from a built-in theme, or from [Theme::new] for a theme without any colors,
+/// and change its fields to define your own.
+///
+/// # Example
+///
+/// ```
+/// use debug_tree::theme::{Color, Theme};
+/// use debug_tree::{TreeBuilder, TreeConfig};
+/// let tree = TreeBuilder::new();
+/// tree.set_config_override(TreeConfig::new().theme(Theme {
+///     connector: Some(Color::Blue),
+///     ..Theme::new()
+/// }));
+/// {
+///     let _branch = tree.add_branch("Request");
+///     tree.add_leaf("Response");
+/// }
+/// assert_eq!("Request\n\x1b[34m└╼ \x1b[39mResponse", tree.peek_string());
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Theme {
+    /// The colors of node text at each depth, repeating for deeper nodes.
+    /// Empty to leave node text uncolored.
+    pub depth_palette: Vec<Color>,
+    /// The colors of error-level and warning-level node text, instead of the depth palette.
+    pub error: Option<Color>,
+    pub warn: Option<Color>,
+    /// The color of connectors and the vertical lines of parents.
+    pub connector: Option<Color>,
+    /// The color of child counts, attachment sizes, annotations and line numbers.
+    pub metadata: Option<Color>,
+}
+
+impl Theme {
+    /// A theme without any colors.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bright colors for terminals with a dark background.
+    pub fn dark() -> Self {
+        Self {
+            depth_palette: vec![
+                Color::BrightWhite,
+                Color::BrightCyan,
+                Color::BrightGreen,
+                Color::BrightMagenta,
+                Color::BrightBlue,
+            ],
+            error: Some(Color::BrightRed),
+            warn: Some(Color::BrightYellow),
+            connector: Some(Color::BrightBlack),
+            metadata: Some(Color::Dim),
+        }
+    }
+
+    /// Darker colors for terminals with a light background.
+    pub fn light() -> Self {
+        Self {
+            depth_palette: vec![
+                Color::Black,
+                Color::Blue,
+                Color::Green,
+                Color::Magenta,
+                Color::Cyan,
+            ],
+            error: Some(Color::Red),
+            warn: Some(Color::Fixed(130)),
+            connector: Some(Color::Fixed(244)),
+            metadata: Some(Color::Dim),
+        }
+    }
+
+    /// Attributes only, for terminals without color: errors and warnings are bold,
+    /// and connectors and metadata are faint.
+    pub fn monochrome() -> Self {
+        Self {
+            depth_palette: vec![],
+            error: Some(Color::Bold),
+            warn: Some(Color::Bold),
+            connector: Some(Color::Dim),
+            metadata: Some(Color::Dim),
+        }
+    }
+
+    /// The color of the text of a node at `level` and `depth`, where top-level nodes are at
+    /// depth 0.
+    pub fn text_color(&self, level: Level, depth: usize) -> Option<Color> {
+        match level {
+            Level::Error if self.error.is_some() => self.error,
+            Level::Warn if self.warn.is_some() => self.warn,
+            _ if self.depth_palette.is_empty() => None,
+            _ => Some(self.depth_palette[depth % self.depth_palette.len()]),
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use crate::default::{default_tree, set_default_scoped};
+use crate::TreeBuilder;
+use std::thread::{JoinHandle, Scope, ScopedJoinHandle};
+
+/// Joins a forked tree back to its parent when dropped, even if the thread panics.
+struct JoinOnDrop {
+    parent: TreeBuilder,
+    child: TreeBuilder,
+}
+
+impl Drop for JoinOnDrop {
+    fn drop(&mut self) {
+        self.parent.join(self.child.clone());
+    }
+}
+
+/// Forks the default tree of the current thread, and returns a closure that runs `f` with the
+/// fork as its default tree, then joins the fork back to the current branch.
+fn with_forked_default<F: FnOnce() -> T, T>(f: F) -> impl FnOnce() -> T {
+    let parent = default_tree();
+    let child = parent.fork();
+    move || {
+        let join = JoinOnDrop { parent, child };
+        let _default = set_default_scoped(&join.child);
+        f()
+    }
+}
+
+/// Spawns a thread like [std::thread::spawn], whose default tree adds to the current branch of
+/// the calling thread's default tree. The additions of the thread are joined to that branch when
+/// it finishes, with [join](TreeBuilder::join).
+///
+/// # Example
+///
+/// ```
+/// use debug_tree::{add_branch, add_leaf, default_tree};
+/// add_branch!("Request");
+/// let handle = debug_tree::spawn(|| {
+///     add_leaf!("Worker");
+///     42
+/// });
+/// assert_eq!(42, handle.join().unwrap());
+/// add_leaf!("Done");
+/// assert_eq!("Request\n├╼ Worker\n└╼ Done", default_tree().peek_string());
+/// ```
+pub fn spawn<F, T>(f: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    std::thread::spawn(with_forked_default(f))
+}
+
+/// Spawns a scoped thread like [Scope::spawn], whose default tree adds to the current branch of
+/// the calling thread's default tree. See [spawn].
+///
+/// # Example
+///
+/// ```
+/// use debug_tree::{add_branch, add_leaf, default_tree};
+/// let parts = ["header", "body"];
+/// add_branch!("Parse");
+/// std::thread::scope(|s| {
+///     for part in &parts {
+///         debug_tree::spawn_scoped(s, move || add_leaf!("Parsed {}", part)).join().unwrap();
+///     }
+/// });
+/// assert_eq!(
+///     "Parse\n├╼ Parsed header\n└╼ Parsed body",
+///     default_tree().peek_string()
+/// );
+/// ```
+pub fn spawn_scoped<'scope, 'env, F, T>(
+    scope: &'scope Scope<'scope, 'env>,
+    f: F,
+) -> ScopedJoinHandle<'scope, T>
+where
+    F: FnOnce() -> T + Send + 'scope,
+    T: Send + 'scope,
+{
+    scope.spawn(with_forked_default(f))
+}
@@ -0,0 +1,97 @@
+use crate::{Level, TreeBuilder};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::DefaultGuard;
+use tracing::{Event, Metadata, Subscriber};
+
+/// Adds each `tracing` event it receives as a leaf to a tree.
+struct TreeSubscriber {
+    tree: TreeBuilder,
+    next_id: AtomicU64,
+}
+
+/// Collects the fields of an event as `message key=value ..`.
+#[derive(Default)]
+struct Fields {
+    message: String,
+    rest: String,
+}
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            self.rest
+                .push_str(&format!(" {}={:?}", field.name(), value));
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.rest
+                .push_str(&format!(" {}={:?}", field.name(), value));
+        }
+    }
+}
+
+fn level(level: &tracing::Level) -> Level {
+    match *level {
+        tracing::Level::ERROR => Level::Error,
+        tracing::Level::WARN => Level::Warn,
+        tracing::Level::INFO => Level::Info,
+        tracing::Level::DEBUG => Level::Debug,
+        tracing::Level::TRACE => Level::Trace,
+    }
+}
+
+impl Subscriber for TreeSubscriber {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.tree.is_level_enabled(level(metadata.level()))
+    }
+
+    fn new_span(&self, _: &Attributes<'_>) -> Id {
+        Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed))
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let text = format!("{}{}", fields.message, fields.rest);
+        let metadata = event.metadata();
+        let mut x = self.tree.0.lock();
+        if let Some(id) = x.add_leaf(text.trim_start()) {
+            x.set_node_level(&id, level(metadata.level()));
+            x.set_target(&id, metadata.target());
+        }
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+/// Adds the `tracing` events of the current thread to a tree until it is dropped.
+/// Returned by [capture_tracing](TreeBuilder::capture_tracing).
+pub struct TracingCapture {
+    _guard: DefaultGuard,
+}
+
+impl TracingCapture {
+    pub fn new(tree: TreeBuilder) -> TracingCapture {
+        TracingCapture {
+            _guard: tracing::subscriber::set_default(TreeSubscriber {
+                tree,
+                next_id: AtomicU64::new(1),
+            }),
+        }
+    }
+}
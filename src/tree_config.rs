@@ -1,4 +1,8 @@
+use crate::markup::Markup;
+use crate::theme::Theme;
+use crate::Tree;
 use once_cell::sync::Lazy;
+use std::fmt;
 use std::sync::{Arc, Mutex};
 
 #[derive(Debug, Clone)]
@@ -28,7 +32,150 @@ pub struct TreeSymbols {
     pub multiline_continued: Option<&'static str>,
 }
 
+/// Which nodes are counted when showing the size of a branch
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ChildCount {
+    /// Direct children only, e.g. `(3 children)`
+    Children,
+    /// All nodes beneath the branch, e.g. `(12 descendants)`
+    Descendants,
+}
+
+/// How nodes without text are rendered, such as the branch created by calling
+/// [enter](crate::TreeBuilder::enter) without adding a leaf first.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum EmptyNodePolicy {
+    /// Render the connector without any text
+    #[default]
+    Blank,
+    /// Render this text in place of the missing text, e.g. `…`
+    Placeholder(&'static str),
+    /// Leave the node out, moving its children up to take its place
+    Skip,
+}
+
+/// Which nodes are listed newest-first when rendering
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum NewestFirst {
+    /// Top-level entries only, keeping the children of each in the order they were added
+    TopLevel,
+    /// The children of every node
+    All,
+}
+
+/// A function that returns the text prepended to each rendered line, before its connectors.
+/// It is called with the node that the line belongs to, or `None` for lines that don't belong to
+/// a node, such as separators and the severity summary.
+#[derive(Clone)]
+pub struct LinePrefix(pub Arc<LinePrefixFn>);
+
+type LinePrefixFn = dyn Fn(Option<&Tree>) -> String + Send + Sync;
+
+/// A function that returns the text rendered for a node, in place of its stored text.
+/// It is called with the node as it was added, so it can format the node from its metadata.
+#[derive(Clone)]
+pub struct NodeText(pub Arc<NodeTextFn>);
+
+pub(crate) type NodeTextFn = dyn Fn(&Tree) -> String + Send + Sync;
+
+impl fmt::Debug for NodeText {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("NodeText(..)")
+    }
+}
+
+impl fmt::Debug for LinePrefix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("LinePrefix(..)")
+    }
+}
+
+/// How control characters in node text are rendered.
+/// Newlines are always kept, as they separate the lines of multi-line text.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ControlChars {
+    /// Replace control characters with their escape sequence, e.g. `\r` or `\u{1b}`
+    Escape,
+    /// Remove control characters, along with the rest of any ANSI escape sequence
+    Strip,
+}
+
+impl ControlChars {
+    pub(crate) fn apply(self, text: &str) -> String {
+        let mut ret = String::with_capacity(text.len());
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c == '\n' || !c.is_control() {
+                ret.push(c);
+                continue;
+            }
+            match self {
+                ControlChars::Escape => ret.extend(c.escape_default()),
+                ControlChars::Strip => {
+                    // Skip the parameters and final byte of a CSI sequence, e.g. `\x1b[31m`
+                    if c == '\u{1b}' && chars.peek() == Some(&'[') {
+                        chars.next();
+                        for x in chars.by_ref() {
+                            if ('\u{40}'..='\u{7e}').contains(&x) {
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        ret
+    }
+}
+
+/// How node text that doesn't fit within the maximum width is rendered.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Overflow {
+    /// Cut the text short, ending it with `…`
+    Truncate,
+    /// Continue the text on the following lines, aligned like multi-line text
+    Wrap,
+}
+
+/// Built-in box-drawing styles
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Style {
+    /// Light lines (`└─`)
+    Light,
+    /// Light lines with rounded corners (`╰─`)
+    Rounded,
+    /// Double lines (`╚═`)
+    Double,
+    /// Heavy lines (`┗━`)
+    Heavy,
+    /// Dashed lines (`'╌`)
+    Dashed,
+}
+
+impl Style {
+    /// The symbols used to draw this style
+    pub fn symbols(self) -> TreeSymbols {
+        match self {
+            Style::Light => TreeSymbols::new(),
+            Style::Rounded => TreeSymbols::with_rounded(),
+            Style::Double => TreeSymbols::with_pipes(),
+            Style::Heavy => TreeSymbols::with_thick(),
+            Style::Dashed => TreeSymbols::with_dashed(),
+        }
+    }
+}
+
+impl From<Style> for TreeSymbols {
+    fn from(style: Style) -> Self {
+        style.symbols()
+    }
+}
+
+/// The settings used to render a tree. It is built with [TreeConfig::new] and its builder
+/// methods, and can't be built from a struct literal, so that fields can be added without
+/// breaking callers, including fields that depend on optional features.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct TreeConfig {
     pub symbols: TreeSymbols,
 
@@ -37,6 +184,105 @@ pub struct TreeConfig {
     pub indent: usize,
 
     pub show_first_level: bool,
+
+    /// Line printed between successive top-level entries, if any.
+    /// An empty string prints a blank line.
+    pub separator: Option<&'static str>,
+
+    /// If set, each branch line is suffixed with its number of children or descendants.
+    pub child_count: Option<ChildCount>,
+
+    /// If true, each rendered line is prefixed with its line number.
+    pub line_numbers: bool,
+
+    /// If not empty, only nodes whose target is one of these modules (or their submodules)
+    /// are rendered, along with their descendants and ancestors.
+    pub only_targets: Vec<String>,
+
+    /// How nodes without text are rendered.
+    pub empty_nodes: EmptyNodePolicy,
+
+    /// If set, nodes are listed newest-first instead of in the order they were added.
+    pub newest_first: Option<NewestFirst>,
+
+    /// If true, the text of each node is prefixed with its hierarchical index, such as `1.3.2.`,
+    /// numbered before any nodes are filtered out.
+    pub outline_numbers: bool,
+
+    /// If true, only error-level nodes are rendered, along with their descendants and ancestors.
+    pub errors_only: bool,
+
+    /// If true, a line counting the error-level and warning-level nodes, and all nodes,
+    /// is appended after the tree.
+    pub severity_summary: bool,
+
+    /// The number of columns between a node's connector and the continuation lines of its
+    /// multi-line text. By default, continuation lines are aligned under the first line.
+    pub multiline_indent: Option<usize>,
+
+    /// If set, control characters in node text are escaped or stripped when rendering.
+    pub control_chars: Option<ControlChars>,
+
+    /// If true, `\r\n` in node text is rendered as `\n`.
+    pub normalize_newlines: bool,
+
+    /// If true, trailing whitespace is removed from each line of node text.
+    pub trim_trailing_whitespace: bool,
+
+    /// If true, newlines in node text are rendered as `⏎`, keeping each node on a single line.
+    pub flatten_newlines: bool,
+
+    /// The minimum width of the gutter in which node annotations are right-aligned.
+    pub gutter_width: usize,
+
+    /// If true, tab-separated columns in the text of sibling nodes are aligned.
+    pub align_columns: bool,
+
+    /// If set, each chain of nodes with a single child is rendered as one line,
+    /// with the text of the nodes joined by this separator.
+    pub collapse_chains: Option<&'static str>,
+
+    /// If set, inline markup in node text, `**bold**` and `` `code` ``, is rendered or stripped.
+    /// HTML exports render it as tags.
+    pub markup: Option<Markup>,
+
+    /// If set, node text, connectors and metadata are colored with ANSI escape sequences.
+    /// HTML exports ignore the theme.
+    pub theme: Option<Theme>,
+
+    /// If set, the text it returns is rendered for each node instead of its stored text.
+    pub node_text: Option<NodeText>,
+
+    /// If set, the text it returns is prepended to each rendered line, outside of line numbers,
+    /// so that tree output can follow the format of other log lines.
+    pub line_prefix: Option<LinePrefix>,
+
+    /// If set, node text is truncated or wrapped so that lines fit within this many columns.
+    /// Annotations and line numbers are not included in the width.
+    pub max_width: Option<usize>,
+
+    /// How node text is rendered when it doesn't fit within `max_width`.
+    pub overflow: Overflow,
+
+    /// If true and `max_width` is not set, printing to a terminal uses the terminal's width.
+    pub terminal_width: bool,
+
+    /// If true, top-level nodes are rendered in the order they were added, prefixed with the
+    /// name of the thread that added them, even if they were joined from a forked tree later.
+    /// This needs the tree to record when each node was added, which it does while
+    /// [set_thread_timeline](crate::TreeBuilder::set_thread_timeline) is on.
+    pub thread_timeline: bool,
+
+    /// Replacements applied to the text of each node when rendering, in order.
+    /// Set with [redact](TreeConfig::redact).
+    #[cfg(feature = "regex")]
+    pub(crate) redactions: Vec<(regex::Regex, String)>,
+
+    /// Patterns whose distinct matches are replaced with `#1`, `#2`, ... when rendering,
+    /// numbered in order of appearance across the whole tree.
+    /// Set with [stable_id](TreeConfig::stable_id).
+    #[cfg(feature = "regex")]
+    pub(crate) stable_ids: Vec<regex::Regex>,
 }
 impl TreeSymbols {
     pub fn new() -> Self {
@@ -150,6 +396,35 @@ impl TreeConfig {
             symbols: TreeSymbols::new(),
             indent: 2,
             show_first_level: false,
+            separator: None,
+            child_count: None,
+            line_numbers: false,
+            only_targets: Vec::new(),
+            empty_nodes: EmptyNodePolicy::Blank,
+            newest_first: None,
+            outline_numbers: false,
+            errors_only: false,
+            severity_summary: false,
+            multiline_indent: None,
+            control_chars: None,
+            normalize_newlines: false,
+            trim_trailing_whitespace: false,
+            flatten_newlines: false,
+            gutter_width: 0,
+            align_columns: false,
+            collapse_chains: None,
+            thread_timeline: false,
+            markup: None,
+            theme: None,
+            node_text: None,
+            line_prefix: None,
+            max_width: None,
+            overflow: Overflow::Truncate,
+            terminal_width: true,
+            #[cfg(feature = "regex")]
+            redactions: Vec::new(),
+            #[cfg(feature = "regex")]
+            stable_ids: Vec::new(),
         }
     }
     pub fn with_symbols(symbols: TreeSymbols) -> Self {
@@ -157,12 +432,51 @@ impl TreeConfig {
             symbols,
             indent: 2,
             show_first_level: false,
+            separator: None,
+            child_count: None,
+            line_numbers: false,
+            only_targets: Vec::new(),
+            empty_nodes: EmptyNodePolicy::Blank,
+            newest_first: None,
+            outline_numbers: false,
+            errors_only: false,
+            severity_summary: false,
+            multiline_indent: None,
+            control_chars: None,
+            normalize_newlines: false,
+            trim_trailing_whitespace: false,
+            flatten_newlines: false,
+            gutter_width: 0,
+            align_columns: false,
+            collapse_chains: None,
+            thread_timeline: false,
+            markup: None,
+            theme: None,
+            node_text: None,
+            line_prefix: None,
+            max_width: None,
+            overflow: Overflow::Truncate,
+            terminal_width: true,
+            #[cfg(feature = "regex")]
+            redactions: Vec::new(),
+            #[cfg(feature = "regex")]
+            stable_ids: Vec::new(),
         }
     }
     pub fn indent(mut self, x: usize) -> Self {
         self.indent = x;
         self
     }
+    /// Minimal-width rendering: single-character connectors, an indent of 1 and no leaf symbol.
+    pub fn compact(mut self) -> Self {
+        self.indent = 1;
+        self.symbols.leaf = "";
+        self
+    }
+    pub fn multiline_indent(mut self, x: usize) -> Self {
+        self.multiline_indent = Some(x);
+        self
+    }
     pub fn show_first_level(mut self) -> Self {
         self.show_first_level = true;
         self
@@ -171,10 +485,409 @@ impl TreeConfig {
         self.show_first_level = false;
         self
     }
+    pub fn separator(mut self, x: &'static str) -> Self {
+        self.separator = Some(x);
+        self
+    }
+    pub fn no_separator(mut self) -> Self {
+        self.separator = None;
+        self
+    }
+    pub fn child_count(mut self, x: ChildCount) -> Self {
+        self.child_count = Some(x);
+        self
+    }
+    pub fn no_child_count(mut self) -> Self {
+        self.child_count = None;
+        self
+    }
+    pub fn escape_control_chars(mut self) -> Self {
+        self.control_chars = Some(ControlChars::Escape);
+        self
+    }
+    pub fn strip_control_chars(mut self) -> Self {
+        self.control_chars = Some(ControlChars::Strip);
+        self
+    }
+    pub fn keep_control_chars(mut self) -> Self {
+        self.control_chars = None;
+        self
+    }
+    pub fn normalize_newlines(mut self) -> Self {
+        self.normalize_newlines = true;
+        self
+    }
+    pub fn trim_trailing_whitespace(mut self) -> Self {
+        self.trim_trailing_whitespace = true;
+        self
+    }
+    pub fn flatten_newlines(mut self) -> Self {
+        self.flatten_newlines = true;
+        self
+    }
+    pub fn gutter_width(mut self, x: usize) -> Self {
+        self.gutter_width = x;
+        self
+    }
+    pub fn align_columns(mut self) -> Self {
+        self.align_columns = true;
+        self
+    }
+    pub fn no_align_columns(mut self) -> Self {
+        self.align_columns = false;
+        self
+    }
+    /// Replaces the matches of `pattern` in the text of each node with `replacement` when
+    /// rendering, such as to scrub addresses or timestamps from snapshot tests.
+    /// The replacement can refer to capture groups, as in [regex::Regex::replace_all].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::{TreeBuilder, TreeConfig};
+    /// use debug_tree::regex::Regex;
+    /// let tree = TreeBuilder::new();
+    /// tree.set_config_override(
+    ///     TreeConfig::new().redact(Regex::new(r"0x[0-9a-f]+").unwrap(), "<addr>"),
+    /// );
+    /// tree.add_leaf("Allocated 0x7ffd2a3c");
+    /// assert_eq!("Allocated <addr>", tree.peek_string());
+    /// ```
+    #[cfg(feature = "regex")]
+    pub fn redact(mut self, pattern: regex::Regex, replacement: &str) -> Self {
+        self.redactions.push((pattern, replacement.to_string()));
+        self
+    }
+    #[cfg(feature = "regex")]
+    pub fn no_redactions(mut self) -> Self {
+        self.redactions.clear();
+        self
+    }
+    /// Replaces each distinct match of `pattern` with a stable id when rendering, so output is
+    /// deterministic while matches of the same value can still be told apart from others.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::{TreeBuilder, TreeConfig};
+    /// let tree = TreeBuilder::new();
+    /// tree.set_config_override(TreeConfig::new().stable_addresses());
+    /// tree.add_leaf("Node 0x7ff1 -> 0x7ff9");
+    /// tree.add_leaf("Node 0x7ff9 -> 0x7ff1");
+    /// assert_eq!("Node #1 -> #2\nNode #2 -> #1", tree.peek_string());
+    /// ```
+    #[cfg(feature = "regex")]
+    pub fn stable_id(mut self, pattern: regex::Regex) -> Self {
+        self.stable_ids.push(pattern);
+        self
+    }
+    /// Replaces hexadecimal addresses such as `0x7ffd2a3c` with stable ids.
+    /// See [stable_id](TreeConfig::stable_id).
+    #[cfg(feature = "regex")]
+    pub fn stable_addresses(self) -> Self {
+        self.stable_id(regex::Regex::new(r"\b0x[0-9a-fA-F]+\b").unwrap())
+    }
+    /// Replaces UUIDs with stable ids. See [stable_id](TreeConfig::stable_id).
+    #[cfg(feature = "regex")]
+    pub fn stable_uuids(self) -> Self {
+        self.stable_id(
+            regex::Regex::new(
+                r"\b[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}\b",
+            )
+            .unwrap(),
+        )
+    }
+    #[cfg(feature = "regex")]
+    pub fn no_stable_ids(mut self) -> Self {
+        self.stable_ids.clear();
+        self
+    }
+    /// Set how nodes without text are rendered. See [EmptyNodePolicy].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::{EmptyNodePolicy, TreeBuilder, TreeConfig};
+    /// let tree = TreeBuilder::new();
+    /// tree.add_leaf("Parent");
+    /// tree.enter();
+    /// tree.enter();
+    /// tree.add_leaf("Child");
+    /// tree.set_config_override(TreeConfig::new().empty_nodes(EmptyNodePolicy::Placeholder("…")));
+    /// assert_eq!("Parent\n└╼ …\n  └╼ Child", tree.peek_string());
+    /// tree.set_config_override(TreeConfig::new().empty_nodes(EmptyNodePolicy::Skip));
+    /// assert_eq!("Parent\n└╼ Child", tree.peek_string());
+    /// ```
+    pub fn empty_nodes(mut self, policy: EmptyNodePolicy) -> Self {
+        self.empty_nodes = policy;
+        self
+    }
+    /// List nodes newest-first, so that the most recent activity is at the top.
+    /// See [NewestFirst].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::{NewestFirst, TreeBuilder, TreeConfig};
+    /// let tree = TreeBuilder::new();
+    /// for i in 1..=2 {
+    ///     let _poll = tree.add_branch(&format!("Poll {}", i));
+    ///     tree.add_leaf("Sent");
+    ///     tree.add_leaf("Received");
+    /// }
+    /// tree.set_config_override(TreeConfig::new().newest_first(NewestFirst::TopLevel));
+    /// assert_eq!(
+    ///     "Poll 2\n├╼ Sent\n└╼ Received\nPoll 1\n├╼ Sent\n└╼ Received",
+    ///     tree.peek_string()
+    /// );
+    /// tree.set_config_override(TreeConfig::new().newest_first(NewestFirst::All));
+    /// assert_eq!(
+    ///     "Poll 2\n├╼ Received\n└╼ Sent\nPoll 1\n├╼ Received\n└╼ Sent",
+    ///     tree.peek_string()
+    /// );
+    /// ```
+    /// Render each node as the text returned by `f` instead of its stored text, such as to
+    /// format nodes from their metadata differently for each output, without changing the tree.
+    /// `f` is called for every node beneath the root, including those without text.
+    /// See [NodeText].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::{TreeBuilder, TreeConfig};
+    /// struct RequestId(u32);
+    /// let tree = TreeBuilder::new();
+    /// let id = tree.add_leaf_id("Request").unwrap();
+    /// tree.set_metadata(&id, RequestId(7));
+    /// tree.add_leaf("Done");
+    /// tree.set_config_override(TreeConfig::new().node_text(|node| {
+    ///     let text = node.text.as_deref().unwrap_or("");
+    ///     match node.metadata::<RequestId>() {
+    ///         Some(id) => format!("{} #{}", text, id.0),
+    ///         None => text.to_string(),
+    ///     }
+    /// }));
+    /// assert_eq!("Request #7\nDone", tree.peek_string());
+    /// ```
+    pub fn node_text<F: Fn(&Tree) -> String + Send + Sync + 'static>(mut self, f: F) -> Self {
+        self.node_text = Some(NodeText(Arc::new(f)));
+        self
+    }
+    pub fn no_node_text(mut self) -> Self {
+        self.node_text = None;
+        self
+    }
+    /// Prepend the text returned by `f` to each rendered line, such as a timestamp or log level,
+    /// so that tree output can follow the format of other log lines. See [LinePrefix].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::{Level, TreeBuilder, TreeConfig};
+    /// let tree = TreeBuilder::new();
+    /// tree.set_config_override(TreeConfig::new().line_prefix(|node| {
+    ///     let level = node.map_or(Level::Info, |x| x.level);
+    ///     format!("[{:<5}] ", format!("{:?}", level).to_uppercase())
+    /// }));
+    /// {
+    ///     let _sync = tree.add_branch("Sync");
+    ///     tree.add_leaf_at(Level::Warn, "Slow response");
+    /// }
+    /// assert_eq!("[INFO ] Sync\n[WARN ] └╼ Slow response", tree.peek_string());
+    /// ```
+    pub fn line_prefix<F: Fn(Option<&Tree>) -> String + Send + Sync + 'static>(
+        mut self,
+        f: F,
+    ) -> Self {
+        self.line_prefix = Some(LinePrefix(Arc::new(f)));
+        self
+    }
+    pub fn no_line_prefix(mut self) -> Self {
+        self.line_prefix = None;
+        self
+    }
+    pub fn newest_first(mut self, x: NewestFirst) -> Self {
+        self.newest_first = Some(x);
+        self
+    }
+    pub fn no_newest_first(mut self) -> Self {
+        self.newest_first = None;
+        self
+    }
+    /// Prefix each node with its hierarchical index, such as `1.`, `1.3.` and `1.3.2.`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::{TreeBuilder, TreeConfig};
+    /// let tree = TreeBuilder::new();
+    /// tree.set_config_override(TreeConfig::new().outline_numbers());
+    /// {
+    ///     let _a = tree.add_branch("Load");
+    ///     tree.add_leaf("Read");
+    ///     tree.add_leaf("Parse");
+    /// }
+    /// tree.add_leaf("Run");
+    /// assert_eq!("1. Load\n├╼ 1.1. Read\n└╼ 1.2. Parse\n2. Run", tree.peek_string());
+    /// ```
+    pub fn outline_numbers(mut self) -> Self {
+        self.outline_numbers = true;
+        self
+    }
+    pub fn no_outline_numbers(mut self) -> Self {
+        self.outline_numbers = false;
+        self
+    }
+    pub fn collapse_chains(mut self, separator: &'static str) -> Self {
+        self.collapse_chains = Some(separator);
+        self
+    }
+    pub fn no_collapse_chains(mut self) -> Self {
+        self.collapse_chains = None;
+        self
+    }
+    /// Render `**bold**` and `` `code` `` in node text with ANSI escape sequences.
+    pub fn ansi_markup(mut self) -> Self {
+        self.markup = Some(Markup::Ansi);
+        self
+    }
+    /// Remove the markup characters of `**bold**` and `` `code` `` from node text.
+    pub fn strip_markup(mut self) -> Self {
+        self.markup = Some(Markup::Strip);
+        self
+    }
+    pub fn no_markup(mut self) -> Self {
+        self.markup = None;
+        self
+    }
+    /// Color the rendered tree with `theme`. See [Theme].
+    pub fn theme(mut self, theme: Theme) -> Self {
+        self.theme = Some(theme);
+        self
+    }
+    pub fn no_theme(mut self) -> Self {
+        self.theme = None;
+        self
+    }
+    /// Fit node text within `width` columns. See [Overflow].
+    pub fn max_width(mut self, width: usize) -> Self {
+        self.max_width = Some(width);
+        self
+    }
+    pub fn no_max_width(mut self) -> Self {
+        self.max_width = None;
+        self
+    }
+    pub fn truncate_overflow(mut self) -> Self {
+        self.overflow = Overflow::Truncate;
+        self
+    }
+    pub fn wrap_overflow(mut self) -> Self {
+        self.overflow = Overflow::Wrap;
+        self
+    }
+    /// Fit node text within the width of the terminal when printing to one,
+    /// unless `max_width` is set. This is the default.
+    pub fn terminal_width(mut self) -> Self {
+        self.terminal_width = true;
+        self
+    }
+    pub fn ignore_terminal_width(mut self) -> Self {
+        self.terminal_width = false;
+        self
+    }
+    pub fn thread_timeline(mut self) -> Self {
+        self.thread_timeline = true;
+        self
+    }
+    pub fn no_thread_timeline(mut self) -> Self {
+        self.thread_timeline = false;
+        self
+    }
+    pub fn show_line_numbers(mut self) -> Self {
+        self.line_numbers = true;
+        self
+    }
+    pub fn hide_line_numbers(mut self) -> Self {
+        self.line_numbers = false;
+        self
+    }
+    pub fn only_target(mut self, x: &str) -> Self {
+        self.only_targets.push(x.to_string());
+        self
+    }
+    pub fn all_targets(mut self) -> Self {
+        self.only_targets.clear();
+        self
+    }
+    /// Render only the paths that lead to error-level nodes: each error keeps its descendants
+    /// and ancestors, and everything else is hidden.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::{TreeBuilder, TreeConfig, Level};
+    /// let tree = TreeBuilder::new();
+    /// {
+    ///     let _a = tree.add_branch("Request 1");
+    ///     tree.add_leaf("Ok");
+    /// }
+    /// {
+    ///     let _a = tree.add_branch("Request 2");
+    ///     tree.add_leaf("Parsed headers");
+    ///     let _b = tree.add_branch_at(Level::Error, "Read body failed");
+    ///     tree.add_leaf("Connection reset");
+    /// }
+    /// tree.set_config_override(TreeConfig::new().errors_only());
+    /// assert_eq!("\
+    /// Request 2
+    /// └╼ Read body failed
+    ///   └╼ Connection reset", tree.peek_string());
+    /// ```
+    pub fn errors_only(mut self) -> Self {
+        self.errors_only = true;
+        self
+    }
+    pub fn no_errors_only(mut self) -> Self {
+        self.errors_only = false;
+        self
+    }
+    /// Append a summary line after the tree, such as `3 errors, 12 warnings, 8,401 nodes`,
+    /// so readers can tell at a glance whether anything went wrong.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use debug_tree::{TreeBuilder, TreeConfig, Level};
+    /// let tree = TreeBuilder::new();
+    /// {
+    ///     let _a = tree.add_branch("Sync");
+    ///     tree.add_leaf_at(Level::Warn, "Slow response");
+    ///     tree.add_leaf("Done");
+    /// }
+    /// tree.set_config_override(TreeConfig::new().severity_summary());
+    /// assert_eq!("\
+    /// Sync
+    /// ├╼ Slow response
+    /// └╼ Done
+    /// 0 errors, 1 warning, 3 nodes", tree.peek_string());
+    /// ```
+    pub fn severity_summary(mut self) -> Self {
+        self.severity_summary = true;
+        self
+    }
+    pub fn no_severity_summary(mut self) -> Self {
+        self.severity_summary = false;
+        self
+    }
     pub fn symbols(mut self, x: TreeSymbols) -> Self {
         self.symbols = x;
         self
     }
+    pub fn style(mut self, x: Style) -> Self {
+        self.symbols = x.symbols();
+        self
+    }
 }
 
 impl Default for TreeSymbols {
@@ -206,6 +919,11 @@ pub fn set_tree_config_symbols(x: TreeSymbols) {
     DEFAULT_CONFIG.lock().unwrap().symbols = x;
 }
 
+/// Set the default tree symbols config to a built-in style
+pub fn set_tree_style(x: Style) {
+    set_tree_config_symbols(x.symbols());
+}
+
 /// The default tree symbols config
 pub fn tree_config_symbols() -> TreeSymbols {
     DEFAULT_CONFIG.lock().unwrap().symbols.clone()